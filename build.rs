@@ -0,0 +1,4 @@
+fn main() {
+    // `bench` is set by benchmarking builds that use the `testing` module.
+    println!("cargo:rustc-check-cfg=cfg(bench)");
+}
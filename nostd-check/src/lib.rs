@@ -0,0 +1,81 @@
+//! A `no_std` crate that exercises `bridgetree` built without its `std` feature, ensuring
+//! that frontiers and trees remain usable when only `core` and `alloc` are available.
+#![no_std]
+
+use bridgetree::{BridgeTree, Frontier, Hashable, Level};
+
+/// A hash type that is not backed by any allocation, such as might be used by an embedded
+/// signer. Nodes are combined by a simple mixing function; this is not a cryptographic hash.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Node(pub u64);
+
+impl Hashable for Node {
+    fn empty_leaf() -> Self {
+        Node(0)
+    }
+
+    fn combine(level: Level, a: &Self, b: &Self) -> Self {
+        let level = u64::from(u8::from(level));
+        Node(
+            a.0.rotate_left(17)
+                .wrapping_mul(0x9e37_79b9_7f4a_7c15)
+                .wrapping_add(b.0 ^ level),
+        )
+    }
+}
+
+/// Builds a frontier over the given leaves and returns its root.
+pub fn frontier_root(leaves: &[Node]) -> Node {
+    let mut frontier = Frontier::<Node, 8>::empty();
+    for leaf in leaves {
+        frontier.append(leaf);
+    }
+    frontier.root()
+}
+
+/// Builds a tree over the given leaves, marking the first, and returns the witness for the
+/// marked leaf as of the tree's current root. The remaining leaves are appended twice, with
+/// the first copy being discarded by rewinding to a checkpoint.
+pub fn first_leaf_witness(leaves: &[Node]) -> Option<[Node; 8]> {
+    let mut tree = BridgeTree::<Node, 8>::new(10);
+    let (first, rest) = leaves.split_first()?;
+    tree.append(first);
+    let position = tree.mark()?;
+    tree.checkpoint();
+    for leaf in rest {
+        tree.append(leaf);
+    }
+    if !tree.rewind() {
+        return None;
+    }
+    for leaf in rest {
+        tree.append(leaf);
+    }
+
+    let root = tree.root(0)?;
+    let path = tree.witness(position, &root)?;
+    let mut witness = [Node(0); 8];
+    witness.copy_from_slice(&path);
+    Some(witness)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{first_leaf_witness, frontier_root, Node};
+    use bridgetree::{Hashable, Level};
+
+    #[test]
+    fn frontier_and_tree_agree() {
+        let leaves = [Node(1), Node(2), Node(3), Node(4), Node(5)];
+        let root = frontier_root(&leaves);
+
+        let witness = first_leaf_witness(&leaves).unwrap();
+        let computed = witness
+            .iter()
+            .enumerate()
+            .fold(leaves[0], |node, (i, sibling)| {
+                Node::combine(Level::from(i as u8), &node, sibling)
+            });
+        assert_eq!(computed, root);
+    }
+}
@@ -0,0 +1,130 @@
+//! A compact serialized representation of [`Frontier`] values, for use with serde's `with`
+//! field attribute:
+//!
+//! ```
+//! use bridgetree::Frontier;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Snapshot {
+//!     #[serde(with = "bridgetree::compact")]
+//!     frontier: Frontier<String, 32>,
+//! }
+//! ```
+//!
+//! The position of a frontier's most recently appended leaf is determined by the levels at
+//! which the frontier has ommers, since an ommer exists at level `l` exactly when bit `l` of the
+//! position is set. Instead of the position and the depth of the tree recorded by the derived
+//! serialization, the compact form records only the leaf and each ommer along with its level,
+//! from which the position is reconstructed on deserialization. The depth of the tree is not
+//! recorded; deserialization fails if the frontier would exceed the depth of the frontier type
+//! being deserialized.
+
+use alloc::format;
+use alloc::vec::Vec;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Frontier, Position};
+
+/// Serializes the frontier in compact form.
+pub fn serialize<H: Serialize, S: Serializer, const DEPTH: u8>(
+    frontier: &Frontier<H, DEPTH>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    frontier
+        .value()
+        .map(|f| {
+            let position = usize::from(f.position());
+            let levels = (0..usize::BITS as u8).filter(|l| (position >> l) & 0x1 == 1);
+            (f.leaf(), levels.zip(f.ommers()).collect::<Vec<_>>())
+        })
+        .serialize(serializer)
+}
+
+/// Deserializes a frontier from its compact form, reconstructing the position of its most
+/// recently appended leaf from the levels of its ommers.
+pub fn deserialize<'de, H: Deserialize<'de>, D: Deserializer<'de>, const DEPTH: u8>(
+    deserializer: D,
+) -> Result<Frontier<H, DEPTH>, D::Error> {
+    match Option::<(H, Vec<(u8, H)>)>::deserialize(deserializer)? {
+        None => Ok(Frontier::empty()),
+        Some((leaf, level_ommers)) => {
+            let mut position = 0usize;
+            let mut ommers = Vec::with_capacity(level_ommers.len());
+            for (level, ommer) in level_ommers {
+                // levels must be strictly increasing, so that each ommer sets a higher bit of
+                // the position than any preceding ommer
+                if u32::from(level) >= usize::BITS || position >> level != 0 {
+                    return Err(D::Error::custom(format!(
+                        "invalid ommer level {} in compact frontier",
+                        level
+                    )));
+                }
+                position |= 1 << level;
+                ommers.push(ommer);
+            }
+
+            Frontier::from_parts(Position::from(position), leaf, ommers)
+                .map_err(|e| D::Error::custom(format!("invalid compact frontier: {:?}", e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Frontier, Position};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Compact<const DEPTH: u8> {
+        #[serde(with = "crate::compact")]
+        frontier: Frontier<String, DEPTH>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut frontier = Frontier::<String, 8>::empty();
+        for c in 'a'..='z' {
+            let compact = Compact {
+                frontier: frontier.clone(),
+            };
+            let compact_bytes = serde_json::to_vec(&compact).unwrap();
+            assert_eq!(
+                serde_json::from_slice::<Compact<8>>(&compact_bytes).unwrap(),
+                compact
+            );
+
+            // the compact form is smaller than the derived serialization
+            let derived_bytes = serde_json::to_vec(&frontier).unwrap();
+            assert!(compact_bytes.len() < derived_bytes.len());
+
+            frontier.append(&c.to_string());
+        }
+        assert_eq!(
+            frontier.value().map(|f| f.position()),
+            Some(Position::from(25))
+        );
+    }
+
+    #[test]
+    fn invalid_levels() {
+        // a frontier that exceeds the depth of the tree
+        let value = serde_json::json!({ "frontier": ["b", [[0, "a"], [4, "x"]]] });
+        assert!(serde_json::from_value::<Compact<8>>(value.clone()).is_ok());
+        assert!(serde_json::from_value::<Compact<4>>(value).is_err());
+
+        // levels that are not strictly increasing
+        let value = serde_json::json!({ "frontier": ["b", [[1, "a"], [1, "x"]]] });
+        assert!(serde_json::from_value::<Compact<8>>(value).is_err());
+        let value = serde_json::json!({ "frontier": ["b", [[2, "a"], [1, "x"]]] });
+        assert!(serde_json::from_value::<Compact<8>>(value).is_err());
+
+        // a level beyond the width of a position
+        let value = serde_json::json!({ "frontier": ["b", [[200, "a"]]] });
+        assert!(serde_json::from_value::<Compact<8>>(value).is_err());
+    }
+}
@@ -0,0 +1,631 @@
+//! A compact binary encoding of frontiers, bridges and trees that is independent of serde.
+//!
+//! The encoding has an exact byte layout, so that it may be used where a storage format must be
+//! specified independently of any serialization framework. Hash values are encoded using the
+//! [`HashSer`] trait, and all other values are encoded as follows:
+//!
+//! - Levels and boolean flags are encoded as a single byte; flags are `0` or `1`.
+//! - Positions, indices and lengths are encoded as 64-bit little-endian unsigned integers.
+//! - Optional values are encoded as a flag, followed by the value if the flag is set.
+//! - Sequences, sets and maps are encoded as their length followed by each of their elements in
+//!   order; map entries are encoded as a key followed by its value. Sets and maps are ordered by
+//!   ascending key, so that equal trees always have identical encodings.
+//! - A [`NonEmptyFrontier`] is encoded as its position, its leaf, and its sequence of ommers.
+//! - A [`Frontier`] is encoded as an optional [`NonEmptyFrontier`].
+//! - An [`Address`] is encoded as its level followed by its index.
+//! - A [`MerkleBridge`] is encoded as its optional prior position, its set of tracked addresses,
+//!   its map from addresses to ommers, and its frontier.
+//! - A [`BridgeTree`] is encoded as the version byte [`TREE_VERSION`], the depth of the tree as a
+//!   level, its sequence of prior bridges, its optional current bridge, its map from marked
+//!   positions to bridge indices, its set of positions having mark metadata, its sequence of
+//!   checkpoints, and the maximum number of checkpoints. Each checkpoint is encoded as the number
+//!   of bridges it retains, whether its position was marked, its set of marked positions, and its
+//!   map from forgotten positions to bridge indices.
+//!
+//! Only trees without checkpoint or mark metadata may be encoded in this form.
+//!
+//! When the `zcash-serialization` feature is enabled, a [`Frontier`] may also be read from and
+//! written to the serialized form of the `CommitmentTree` type used by `zcashd`, using
+//! `Frontier::from_commitment_tree_bytes` and `Frontier::write_commitment_tree_bytes`. In
+//! that form, sequence lengths are encoded as Bitcoin `CompactSize` values. A [`WitnessBundle`]
+//! may likewise be converted to and from the serialized form of the `IncrementalWitness` type
+//! used by `librustzcash`, using `WitnessBundle::from_incremental_witness_bytes` and
+//! `WitnessBundle::to_incremental_witness_bytes`.
+
+#[cfg(feature = "zcash-serialization")]
+mod commitment_tree;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::iter::FromIterator;
+
+use crate::{
+    Address, BridgeTree, BridgeTreeError, Checkpoint, ConstDepth, Depth, DepthBridgeTree,
+    DynBridgeTree, Frontier, Hashable, Level, MerkleBridge, NonEmptyFrontier, Position,
+};
+
+#[cfg(doc)]
+use crate::WitnessBundle;
+
+/// The version byte that leads the encoding of a [`BridgeTree`].
+pub const TREE_VERSION: u8 = 0;
+
+/// A trait for hash values that can be written to and read from a byte stream.
+pub trait HashSer: Sized {
+    /// Reads a hash value from the given reader.
+    fn read<R: Read>(reader: R) -> io::Result<Self>;
+
+    /// Writes this hash value to the given writer.
+    fn write<W: Write>(&self, writer: W) -> io::Result<()>;
+}
+
+fn invalid_data<E: std::fmt::Debug>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e))
+}
+
+fn write_u8<W: Write>(mut writer: W, value: u8) -> io::Result<()> {
+    writer.write_all(&[value])
+}
+
+fn read_u8<R: Read>(mut reader: R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn write_bool<W: Write>(writer: W, value: bool) -> io::Result<()> {
+    write_u8(writer, u8::from(value))
+}
+
+fn read_bool<R: Read>(reader: R) -> io::Result<bool> {
+    match read_u8(reader)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        b => Err(invalid_data(format!("invalid flag byte {}", b))),
+    }
+}
+
+fn write_usize<W: Write>(mut writer: W, value: usize) -> io::Result<()> {
+    writer.write_all(&(value as u64).to_le_bytes())
+}
+
+fn read_usize<R: Read>(mut reader: R) -> io::Result<usize> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    usize::try_from(u64::from_le_bytes(buf)).map_err(invalid_data)
+}
+
+fn write_position<W: Write>(writer: W, position: Position) -> io::Result<()> {
+    write_usize(writer, position.into())
+}
+
+fn read_position<R: Read>(reader: R) -> io::Result<Position> {
+    read_usize(reader).map(Position::from)
+}
+
+fn write_optional<W: Write, T>(
+    mut writer: W,
+    value: Option<&T>,
+    write_value: impl Fn(&mut W, &T) -> io::Result<()>,
+) -> io::Result<()> {
+    write_bool(&mut writer, value.is_some())?;
+    value.map_or(Ok(()), |v| write_value(&mut writer, v))
+}
+
+fn read_optional<R: Read, T>(
+    mut reader: R,
+    read_value: impl Fn(&mut R) -> io::Result<T>,
+) -> io::Result<Option<T>> {
+    if read_bool(&mut reader)? {
+        read_value(&mut reader).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn write_sequence<W: Write, T, I: ExactSizeIterator<Item = T>>(
+    mut writer: W,
+    values: I,
+    write_value: impl Fn(&mut W, T) -> io::Result<()>,
+) -> io::Result<()> {
+    write_usize(&mut writer, values.len())?;
+    values
+        .into_iter()
+        .try_for_each(|v| write_value(&mut writer, v))
+}
+
+fn read_sequence<R: Read, T, C: FromIterator<T>>(
+    mut reader: R,
+    read_value: impl Fn(&mut R) -> io::Result<T>,
+) -> io::Result<C> {
+    let len = read_usize(&mut reader)?;
+    (0..len).map(|_| read_value(&mut reader)).collect()
+}
+
+fn write_address<W: Write>(mut writer: W, addr: &Address) -> io::Result<()> {
+    write_u8(&mut writer, addr.level().into())?;
+    write_usize(&mut writer, addr.index())
+}
+
+fn read_address<R: Read>(mut reader: R) -> io::Result<Address> {
+    let level = Level::from(read_u8(&mut reader)?);
+    Ok(Address::from_parts(level, read_usize(&mut reader)?))
+}
+
+fn write_positions<'a, W: Write>(
+    writer: W,
+    positions: impl ExactSizeIterator<Item = &'a Position>,
+) -> io::Result<()> {
+    write_sequence(writer, positions, |w, p| write_position(w, *p))
+}
+
+fn write_indices<W: Write>(writer: W, indices: &BTreeMap<Position, usize>) -> io::Result<()> {
+    write_sequence(writer, indices.iter(), |w, (p, i)| {
+        write_position(&mut *w, *p)?;
+        write_usize(w, *i)
+    })
+}
+
+fn read_indices<R: Read>(reader: R) -> io::Result<BTreeMap<Position, usize>> {
+    read_sequence(reader, |r| Ok((read_position(&mut *r)?, read_usize(r)?)))
+}
+
+impl<H: HashSer> NonEmptyFrontier<H> {
+    /// Writes the frontier to the given writer, in the encoding described in the
+    /// [`encoding`](crate::encoding) module.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_position(&mut writer, self.position)?;
+        self.leaf.write(&mut writer)?;
+        write_sequence(&mut writer, self.ommers.iter(), |w, h| h.write(w))
+    }
+
+    /// Reads a frontier from the given reader, in the encoding described in the
+    /// [`encoding`](crate::encoding) module.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let position = read_position(&mut reader)?;
+        let leaf = H::read(&mut reader)?;
+        let ommers = read_sequence(&mut reader, |r| H::read(r))?;
+        NonEmptyFrontier::from_parts(position, leaf, ommers).map_err(invalid_data)
+    }
+}
+
+impl<H: HashSer, const DEPTH: u8> Frontier<H, DEPTH> {
+    /// Writes the frontier to the given writer, in the encoding described in the
+    /// [`encoding`](crate::encoding) module.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        write_optional(writer, self.value(), |w, f| f.write_to(w))
+    }
+
+    /// Reads a frontier from the given reader, in the encoding described in the
+    /// [`encoding`](crate::encoding) module. Returns an error if the frontier would exceed the
+    /// capacity of a tree of depth `DEPTH`.
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self> {
+        read_optional(reader, |r| NonEmptyFrontier::read_from(r))?
+            .map_or(Ok(Frontier::empty()), |f| {
+                Frontier::try_from(f).map_err(invalid_data)
+            })
+    }
+}
+
+impl<H: HashSer> MerkleBridge<H> {
+    /// Writes the bridge to the given writer, in the encoding described in the
+    /// [`encoding`](crate::encoding) module.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_optional(&mut writer, self.prior_position.as_ref(), |w, p| {
+            write_position(w, *p)
+        })?;
+        write_sequence(&mut writer, self.tracking.iter(), |w, a| {
+            write_address(w, a)
+        })?;
+        write_sequence(&mut writer, self.ommers.iter(), |w, (a, h)| {
+            write_address(&mut *w, a)?;
+            h.write(w)
+        })?;
+        self.frontier.write_to(&mut writer)
+    }
+
+    /// Reads a bridge from the given reader, in the encoding described in the
+    /// [`encoding`](crate::encoding) module.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let prior_position = read_optional(&mut reader, |r| read_position(r))?;
+        let tracking = read_sequence::<_, _, BTreeSet<_>>(&mut reader, |r| read_address(r))?;
+        let ommers = read_sequence::<_, _, BTreeMap<_, _>>(&mut reader, |r| {
+            Ok((read_address(&mut *r)?, H::read(r)?))
+        })?;
+        let frontier = NonEmptyFrontier::read_from(&mut reader)?;
+        Ok(MerkleBridge::from_parts(
+            prior_position,
+            tracking,
+            ommers,
+            frontier,
+        ))
+    }
+}
+
+impl<H: HashSer, D: Depth> DepthBridgeTree<H, D, (), ()> {
+    /// Writes the tree to the given writer, in the encoding described in the
+    /// [`encoding`](crate::encoding) module.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_u8(&mut writer, TREE_VERSION)?;
+        write_u8(&mut writer, self.depth.level().into())?;
+        write_sequence(&mut writer, self.prior_bridges.iter(), |w, b| b.write_to(w))?;
+        write_optional(&mut writer, self.current_bridge.as_ref(), |w, b| {
+            b.write_to(w)
+        })?;
+        write_indices(&mut writer, &self.saved)?;
+        write_positions(&mut writer, self.mark_meta.keys())?;
+        write_sequence(&mut writer, self.checkpoints.iter(), |w, c| {
+            write_usize(&mut *w, c.bridges_len)?;
+            write_bool(&mut *w, c.is_marked)?;
+            write_positions(&mut *w, c.marked.iter())?;
+            write_indices(w, &c.forgotten)
+        })?;
+        write_usize(&mut writer, self.max_checkpoints)
+    }
+}
+
+impl<H: HashSer + Hashable + Ord + Clone, D: Depth> DepthBridgeTree<H, D, (), ()> {
+    // Reads the encoded tree, obtaining the depth of the tree from its encoded level.
+    fn read_with_depth<R: Read>(
+        mut reader: R,
+        depth: impl FnOnce(Level) -> io::Result<D>,
+    ) -> io::Result<Self> {
+        let version = read_u8(&mut reader)?;
+        if version != TREE_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported tree encoding version {}",
+                version
+            )));
+        }
+        let depth = depth(Level::from(read_u8(&mut reader)?))?;
+        let prior_bridges = read_sequence(&mut reader, |r| MerkleBridge::read_from(r))?;
+        let current_bridge = read_optional(&mut reader, |r| MerkleBridge::read_from(r))?;
+        let saved = read_indices(&mut reader)?;
+        let mark_meta = read_sequence(&mut reader, |r| Ok((read_position(r)?, ())))?;
+        let checkpoints = read_sequence(&mut reader, |r| {
+            Ok(Checkpoint::from_parts(
+                read_usize(&mut *r)?,
+                read_bool(&mut *r)?,
+                read_sequence(&mut *r, |r| read_position(r))?,
+                read_indices(r)?,
+                (),
+            ))
+        })?;
+        let max_checkpoints = read_usize(&mut reader)?;
+
+        Self::from_parts_internal(
+            depth,
+            prior_bridges,
+            current_bridge,
+            saved,
+            mark_meta,
+            checkpoints,
+            max_checkpoints,
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<H: HashSer + Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
+    /// Reads a tree from the given reader, in the encoding described in the
+    /// [`encoding`](crate::encoding) module. Returns an error if the encoded tree has a depth
+    /// other than `DEPTH`, or is not internally consistent.
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with_depth(reader, |level| {
+            if level == Level::from(DEPTH) {
+                Ok(ConstDepth)
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    BridgeTreeError::DepthMismatch {
+                        expected: Level::from(DEPTH),
+                        found: level,
+                    },
+                ))
+            }
+        })
+    }
+}
+
+impl<H: HashSer + Hashable + Ord + Clone> DynBridgeTree<H> {
+    /// Reads a tree of any depth from the given reader, in the encoding described in the
+    /// [`encoding`](crate::encoding) module. Returns an error if the encoded tree is not
+    /// internally consistent.
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self> {
+        Self::read_with_depth(reader, Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::io::{self, Read, Write};
+
+    use super::HashSer;
+    use crate::{
+        Address, BridgeTree, Checkpoint, DynBridgeTree, Frontier, Level, MerkleBridge, Position,
+    };
+
+    impl HashSer for String {
+        fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+            let mut len = [0u8; 1];
+            reader.read_exact(&mut len)?;
+            let mut bytes = vec![0u8; len[0].into()];
+            reader.read_exact(&mut bytes)?;
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+
+        fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+            writer.write_all(&[self.len() as u8])?;
+            writer.write_all(self.as_bytes())
+        }
+    }
+
+    fn tree() -> BridgeTree<String, 3> {
+        let mut tree = BridgeTree::<String, 3>::new(10);
+        tree.append(&"a".to_string());
+        tree.mark();
+        tree.checkpoint();
+        tree.append(&"b".to_string());
+        tree
+    }
+
+    #[test]
+    fn frontier_round_trip() {
+        let mut frontier = Frontier::<String, 4>::empty();
+        for c in 'a'..='p' {
+            let mut bytes = vec![];
+            frontier.write_to(&mut bytes).unwrap();
+            assert_eq!(Frontier::read_from(&bytes[..]).unwrap(), frontier);
+            frontier.append(&c.to_string());
+        }
+
+        // a frontier that exceeds the capacity of the tree is rejected
+        let mut bytes = vec![];
+        frontier.write_to(&mut bytes).unwrap();
+        assert!(Frontier::<String, 3>::read_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn tree_round_trip() {
+        let mut tree = tree();
+        for c in 'c'..='h' {
+            tree.append(&c.to_string());
+            if c == 'd' || c == 'f' {
+                tree.mark();
+                tree.checkpoint();
+            }
+            if c == 'g' {
+                tree.remove_mark(Position::from(3));
+            }
+
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+            assert_eq!(BridgeTree::read_from(&bytes[..]).unwrap(), tree);
+            for bridge in tree.prior_bridges() {
+                let mut bytes = vec![];
+                bridge.write_to(&mut bytes).unwrap();
+                assert_eq!(&MerkleBridge::read_from(&bytes[..]).unwrap(), bridge);
+            }
+        }
+    }
+
+    #[test]
+    fn tree_fixture() {
+        #[rustfmt::skip]
+        let fixture: &[u8] = &[
+            // version, depth
+            0, 3,
+            // prior bridges
+            1, 0, 0, 0, 0, 0, 0, 0,
+                // prior position
+                0,
+                // tracking
+                1, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 0, 0, 0, 0,
+                // ommers
+                0, 0, 0, 0, 0, 0, 0, 0,
+                // frontier
+                0, 0, 0, 0, 0, 0, 0, 0,
+                1, b'a',
+                0, 0, 0, 0, 0, 0, 0, 0,
+            // current bridge
+            1,
+                // prior position
+                1, 0, 0, 0, 0, 0, 0, 0, 0,
+                // tracking
+                1, 0, 0, 0, 0, 0, 0, 0,
+                    1, 0, 0, 0, 0, 0, 0, 0, 0,
+                // ommers
+                1, 0, 0, 0, 0, 0, 0, 0,
+                    0, 1, 0, 0, 0, 0, 0, 0, 0,
+                    1, b'b',
+                // frontier
+                1, 0, 0, 0, 0, 0, 0, 0,
+                1, b'b',
+                1, 0, 0, 0, 0, 0, 0, 0,
+                    1, b'a',
+            // saved
+            1, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0,
+            // mark metadata
+            0, 0, 0, 0, 0, 0, 0, 0,
+            // checkpoints
+            1, 0, 0, 0, 0, 0, 0, 0,
+                1, 0, 0, 0, 0, 0, 0, 0,
+                1,
+                0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0,
+            // max checkpoints
+            10, 0, 0, 0, 0, 0, 0, 0,
+        ];
+
+        let tree = tree();
+        let mut bytes = vec![];
+        tree.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, fixture);
+        assert_eq!(BridgeTree::read_from(fixture).unwrap(), tree);
+
+        let dyn_tree = DynBridgeTree::<String>::read_from(fixture).unwrap();
+        assert_eq!(dyn_tree.depth(), Level::from(3));
+        assert_eq!(dyn_tree.root(0), tree.root(0));
+
+        // the depth of the tree is validated
+        assert!(BridgeTree::<String, 4>::read_from(fixture).is_err());
+
+        // an unknown version is rejected
+        let mut unknown = fixture.to_vec();
+        unknown[0] = 1;
+        let err = BridgeTree::<String, 3>::read_from(&unknown[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // truncated input is rejected
+        assert!(BridgeTree::<String, 3>::read_from(&fixture[..fixture.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn tree_invariants() {
+        let read_err = |tree: &BridgeTree<String, 3>| {
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+            let err = BridgeTree::<String, 3>::read_from(&bytes[..]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            err.to_string()
+        };
+
+        // a mark that refers to a bridge that does not exist
+        let mut invalid = tree();
+        invalid.saved.insert(Position::from(0), 5);
+        assert_eq!(
+            read_err(&invalid),
+            "a mark refers to bridge 5, which does not exist"
+        );
+
+        // checkpoints that are out of order
+        let mut invalid = tree();
+        invalid.checkpoints.push(Checkpoint::from_parts(
+            0,
+            false,
+            BTreeSet::new(),
+            BTreeMap::new(),
+            (),
+        ));
+        assert_eq!(
+            read_err(&invalid),
+            "checkpoint 1 retains fewer bridges than the checkpoint before it"
+        );
+
+        // a bridge holding more ommers than the leaves appended to it could have produced
+        let mut invalid = tree();
+        let address = Address::from_parts(Level::from(1), 1);
+        invalid
+            .current_bridge
+            .as_mut()
+            .unwrap()
+            .ommers
+            .insert(address, "cd".to_string());
+        assert_eq!(
+            read_err(&invalid),
+            format!(
+                "bridge 1 holds an ommer at {:?} that it did not complete",
+                address
+            )
+        );
+
+        // a leaf beyond the capacity of the tree
+        let mut fixture = vec![];
+        tree().write_to(&mut fixture).unwrap();
+        fixture[1] = 0;
+        let err = DynBridgeTree::<String>::read_from(&fixture[..]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the leaf at {:?} exceeds the capacity of the tree",
+                Position::from(1)
+            )
+        );
+    }
+
+    #[test]
+    fn deterministic_encoding() {
+        let build = |last: char| {
+            let mut tree = BridgeTree::<String, 4>::new(10);
+            for c in 'a'..=last {
+                tree.append(&c.to_string());
+                if c == 'b' || c == 'c' || c == 'f' {
+                    tree.mark();
+                }
+                if c == 'b' || c == 'e' {
+                    tree.checkpoint();
+                }
+            }
+            tree.remove_mark(Position::from(1));
+            tree
+        };
+
+        // reconstructs a tree with each of its maps and sets populated in reverse order
+        fn reversed(tree: &BridgeTree<String, 4>) -> BridgeTree<String, 4> {
+            let reversed_bridge = |b: &MerkleBridge<String>| {
+                MerkleBridge::from_parts(
+                    b.prior_position(),
+                    b.tracking().iter().rev().cloned().collect(),
+                    b.ommers()
+                        .iter()
+                        .rev()
+                        .map(|(k, v)| (*k, v.clone()))
+                        .collect(),
+                    b.frontier().clone(),
+                )
+            };
+            BridgeTree::from_parts(
+                tree.prior_bridges().iter().map(reversed_bridge).collect(),
+                tree.current_bridge().as_ref().map(reversed_bridge),
+                tree.marked_indices()
+                    .iter()
+                    .rev()
+                    .map(|(k, v)| (*k, *v))
+                    .collect(),
+                tree.marked_meta().clone(),
+                tree.checkpoints()
+                    .iter()
+                    .map(|c| {
+                        Checkpoint::from_parts(
+                            c.bridges_len(),
+                            c.is_marked(),
+                            c.marked().iter().rev().cloned().collect(),
+                            c.forgotten().iter().rev().map(|(k, v)| (*k, *v)).collect(),
+                            (),
+                        )
+                    })
+                    .collect(),
+                tree.max_checkpoints(),
+            )
+            .unwrap()
+        }
+
+        let encode = |tree: &BridgeTree<String, 4>| {
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+            bytes
+        };
+        let tree = build('h');
+        let bytes = encode(&tree);
+        assert_eq!(encode(&build('h')), bytes);
+        assert_eq!(encode(&reversed(&tree)), bytes);
+        assert_eq!(encode(&BridgeTree::read_from(&bytes[..]).unwrap()), bytes);
+
+        // JSON requires map keys to be strings, so the serde encoding is checked using a tree
+        // whose bridges have not yet collected any ommers, which are keyed by address
+        let tree = build('c');
+        let json = serde_json::to_vec(&tree).unwrap();
+        assert_eq!(serde_json::to_vec(&build('c')).unwrap(), json);
+        assert_eq!(serde_json::to_vec(&reversed(&tree)).unwrap(), json);
+        let decoded = serde_json::from_slice::<BridgeTree<String, 4>>(&json).unwrap();
+        assert_eq!(serde_json::to_vec(&decoded).unwrap(), json);
+    }
+}
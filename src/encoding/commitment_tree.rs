@@ -0,0 +1,879 @@
+//! Conversion between [`Frontier`] values and the serialized form of the `CommitmentTree` type
+//! used by `zcashd` and `librustzcash` to persist note commitment trees.
+//!
+//! A commitment tree is encoded as an optional left node, an optional right node, and a sequence
+//! of optional parent nodes, where optional values are encoded as in the rest of the
+//! [`encoding`](crate::encoding) module and the length of the sequence is encoded as a Bitcoin
+//! `CompactSize`. The left and right nodes are the leaves of the lowest pair of leaves of the
+//! tree to which a leaf has been appended, and the parent at index `i` is the root of the
+//! complete subtree at level `i + 1` to the left of the most recently appended leaf, if any. The
+//! nodes that are present thus correspond to the leaf and the ommers of a frontier, and the
+//! position of the frontier is given by which of the right node and parents are present.
+//!
+//! An incremental witness is encoded as the commitment tree as of the witnessed leaf, a
+//! `CompactSize`-prefixed sequence of the roots of the complete subtrees to the right of the
+//! witnessed leaf in order of increasing level, and an optional cursor: the commitment tree of
+//! the leaves appended to the next such subtree, if it is incomplete.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+use super::{invalid_data, read_optional, read_u8, write_optional, write_u8, HashSer};
+use crate::{
+    Address, Frontier, Hashable, Level, MerkleBridge, NonEmptyFrontier, Position, WitnessBundle,
+};
+
+/// The maximum length of a sequence accepted by `zcashd` when reading a `CompactSize`.
+const MAX_COMPACT_SIZE: u64 = 0x0200_0000;
+
+fn write_compact_size<W: Write>(mut writer: W, size: usize) -> io::Result<()> {
+    let size = size as u64;
+    if size < 0xfd {
+        write_u8(writer, size as u8)
+    } else if size <= 0xffff {
+        write_u8(&mut writer, 0xfd)?;
+        writer.write_all(&(size as u16).to_le_bytes())
+    } else if size <= 0xffff_ffff {
+        write_u8(&mut writer, 0xfe)?;
+        writer.write_all(&(size as u32).to_le_bytes())
+    } else {
+        write_u8(&mut writer, 0xff)?;
+        writer.write_all(&size.to_le_bytes())
+    }
+}
+
+fn read_compact_size<R: Read>(mut reader: R) -> io::Result<usize> {
+    let (size, min) = match read_u8(&mut reader)? {
+        0xfd => {
+            let mut buf = [0u8; 2];
+            reader.read_exact(&mut buf)?;
+            (u64::from(u16::from_le_bytes(buf)), 0xfd)
+        }
+        0xfe => {
+            let mut buf = [0u8; 4];
+            reader.read_exact(&mut buf)?;
+            (u64::from(u32::from_le_bytes(buf)), 0x1_0000)
+        }
+        0xff => {
+            let mut buf = [0u8; 8];
+            reader.read_exact(&mut buf)?;
+            (u64::from_le_bytes(buf), 0x1_0000_0000)
+        }
+        b => (u64::from(b), 0),
+    };
+
+    if size < min {
+        Err(invalid_data(format!("non-canonical CompactSize {}", size)))
+    } else if size > MAX_COMPACT_SIZE {
+        Err(invalid_data(format!(
+            "CompactSize {} exceeds maximum",
+            size
+        )))
+    } else {
+        usize::try_from(size).map_err(invalid_data)
+    }
+}
+
+fn read_commitment_tree<H: HashSer, R: Read>(
+    mut reader: R,
+) -> io::Result<Option<NonEmptyFrontier<H>>> {
+    let left = read_optional(&mut reader, |r| H::read(r))?;
+    let right = read_optional(&mut reader, |r| H::read(r))?;
+    let parent_count = read_compact_size(&mut reader)?;
+    let parents = (0..parent_count)
+        .map(|_| read_optional(&mut reader, |r| H::read(r)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let (leaf, mut position, mut ommers) = match (left, right) {
+        (None, None) if parents.iter().all(Option::is_none) => {
+            return Ok(None);
+        }
+        (None, _) => {
+            return Err(invalid_data("commitment tree has nodes but no left node"));
+        }
+        (Some(left), None) => (left, 0usize, vec![]),
+        (Some(left), Some(right)) => (right, 1usize, vec![left]),
+    };
+
+    for (i, parent) in parents.into_iter().enumerate() {
+        if let Some(parent) = parent {
+            let level = i + 1;
+            if level >= usize::BITS as usize {
+                return Err(invalid_data("commitment tree has too many parents"));
+            }
+            position |= 1 << level;
+            ommers.push(parent);
+        }
+    }
+
+    NonEmptyFrontier::from_parts(Position::from(position), leaf, ommers)
+        .map(Some)
+        .map_err(invalid_data)
+}
+
+fn write_commitment_tree<H: HashSer, W: Write>(
+    mut writer: W,
+    frontier: Option<&NonEmptyFrontier<H>>,
+) -> io::Result<()> {
+    let frontier = match frontier {
+        Some(frontier) => frontier,
+        None => {
+            write_u8(&mut writer, 0)?;
+            write_u8(&mut writer, 0)?;
+            return write_compact_size(writer, 0);
+        }
+    };
+
+    let position = usize::from(frontier.position());
+    let (left, right) = if position & 1 == 1 {
+        (frontier.ommer_at(Level::from(0)), Some(frontier.leaf()))
+    } else {
+        (Some(frontier.leaf()), None)
+    };
+    write_optional(&mut writer, left, |w, h| h.write(w))?;
+    write_optional(&mut writer, right, |w, h| h.write(w))?;
+
+    let parent_count = (usize::BITS - (position >> 1).leading_zeros()) as usize;
+    write_compact_size(&mut writer, parent_count)?;
+    (1..=parent_count).try_for_each(|level| {
+        let parent = frontier.ommer_at(Level::from(level as u8));
+        write_optional(&mut writer, parent, |w, h| h.write(w))
+    })
+}
+
+impl<H: HashSer, const DEPTH: u8> Frontier<H, DEPTH> {
+    /// Reads a frontier from the serialized form of a `zcashd` or `librustzcash`
+    /// `CommitmentTree`, as described in the [`encoding`](crate::encoding) module. Returns an
+    /// error if the encoding is malformed or if the frontier would exceed the capacity of a tree
+    /// of depth `DEPTH`.
+    pub fn from_commitment_tree_bytes<R: Read>(reader: R) -> io::Result<Self> {
+        read_commitment_tree(reader)?.map_or(Ok(Frontier::empty()), |f| {
+            Frontier::try_from(f).map_err(invalid_data)
+        })
+    }
+
+    /// Writes the frontier in the serialized form of a `zcashd` or `librustzcash`
+    /// `CommitmentTree`, as described in the [`encoding`](crate::encoding) module. The sequence
+    /// of parents is written without trailing empty parents, as `zcashd` does.
+    pub fn write_commitment_tree_bytes<W: Write>(&self, writer: W) -> io::Result<()> {
+        write_commitment_tree(writer, self.value())
+    }
+}
+
+impl<H: HashSer + Hashable + Ord + Clone> WitnessBundle<H> {
+    /// Reads a witness from the serialized form of a `librustzcash` `IncrementalWitness`, as
+    /// described in the [`encoding`](crate::encoding) module.
+    ///
+    /// The serialized witness does not retain the most recently appended leaf of the tree once
+    /// that leaf completes one of the subtrees that it records, and so the frontier of the tree
+    /// as of its most recently appended leaf must be provided as `tip`; wallets persist this
+    /// frontier alongside their witnesses. Returns an error if the encoding is malformed, or if
+    /// `tip` is not consistent with the witness.
+    pub fn from_incremental_witness_bytes<R: Read>(
+        mut reader: R,
+        tip: NonEmptyFrontier<H>,
+    ) -> io::Result<Self> {
+        let frontier = read_commitment_tree(&mut reader)?
+            .ok_or_else(|| invalid_data("incremental witness has an empty tree"))?;
+        let filled_count = read_compact_size(&mut reader)?;
+        let filled = (0..filled_count)
+            .map(|_| H::read(&mut reader))
+            .collect::<io::Result<Vec<_>>>()?;
+        let cursor = read_optional(&mut reader, |r| read_commitment_tree(r))?.flatten();
+
+        // Each filled node is the root of the next complete subtree to the right of the
+        // witnessed leaf, and is the sibling of the address tracked by the bridge at that time.
+        let position = frontier.position();
+        let mut tracked = Address::from(position).current_incomplete();
+        let mut ommers = BTreeMap::new();
+        let mut last_filled = None;
+        for node in filled {
+            ommers.insert(tracked.sibling(), node);
+            last_filled = Some(tracked.sibling());
+            tracked = tracked.next_incomplete_parent();
+            if u32::from(u8::from(tracked.level())) >= usize::BITS {
+                return Err(invalid_data(
+                    "incremental witness has too many filled nodes",
+                ));
+            }
+        }
+
+        // The cursor, if any, is the frontier of the partially filled subtree that follows.
+        let next = tracked.sibling();
+        let start = usize::from(next.position_range().start);
+        let expected_tip = match &cursor {
+            Some(cursor) => {
+                if cursor.position().is_complete_subtree(next.level()) {
+                    return Err(invalid_data("incremental witness has a complete cursor"));
+                }
+                let consistent = cursor.leaf() == tip.leaf()
+                    && (0..u8::from(next.level()))
+                        .all(|l| cursor.ommer_at(l.into()) == tip.ommer_at(l.into()));
+                if !consistent {
+                    return Err(invalid_data("tip is inconsistent with the witness cursor"));
+                }
+                start
+                    .checked_add(usize::from(cursor.position()))
+                    .ok_or_else(|| invalid_data("incremental witness cursor is out of range"))?
+            }
+            None => {
+                match last_filled {
+                    Some(addr) => {
+                        if tip.root(Some(addr.level())) != ommers[&addr] {
+                            return Err(invalid_data("tip is inconsistent with the filled nodes"));
+                        }
+                    }
+                    // no leaves have been appended since the witnessed leaf
+                    None => {
+                        if tip != frontier {
+                            return Err(invalid_data(
+                                "tip is inconsistent with the witnessed tree",
+                            ));
+                        }
+                    }
+                }
+                start - 1
+            }
+        };
+        if usize::from(tip.position()) != expected_tip {
+            return Err(invalid_data(format!(
+                "tip position {:?} does not match witness tip position {}",
+                tip.position(),
+                expected_tip
+            )));
+        }
+
+        let bridge = MerkleBridge::from_parts(
+            Some(position),
+            vec![tracked].into_iter().collect(),
+            ommers,
+            tip,
+        );
+        Ok(WitnessBundle::from_parts(frontier, bridge).expect("the bridge follows the frontier"))
+    }
+
+    /// Writes the witness in the serialized form of a `librustzcash` `IncrementalWitness`, as
+    /// described in the [`encoding`](crate::encoding) module. Returns an error if the bundle's
+    /// bridge does not retain a subtree root that the encoding requires.
+    pub fn to_incremental_witness_bytes<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write_commitment_tree(&mut writer, Some(self.frontier()))?;
+
+        let tip = self.tip();
+        let mut tracked = Address::from(self.position()).current_incomplete();
+        let mut filled = vec![];
+        while tracked.sibling().position_range().end <= tip.position() + 1 {
+            let node = self
+                .bridge()
+                .ommers()
+                .get(&tracked.sibling())
+                .ok_or_else(|| invalid_data("bridge is missing a filled subtree root"))?;
+            filled.push(node);
+            tracked = tracked.next_incomplete_parent();
+        }
+        write_compact_size(&mut writer, filled.len())?;
+        filled.into_iter().try_for_each(|h| h.write(&mut writer))?;
+
+        let next = tracked.sibling();
+        let cursor = (next.position_range().start <= tip.position()).then(|| {
+            let ommers = (0..u8::from(next.level()))
+                .filter_map(|l| tip.ommer_at(l.into()).cloned())
+                .collect();
+            NonEmptyFrontier::from_parts(
+                Position::from(tip.position() - next.position_range().start),
+                tip.leaf().clone(),
+                ommers,
+            )
+            .expect("the cursor ommers are those of the tip below the cursor's level")
+        });
+        write_optional(&mut writer, cursor.as_ref(), |w, c| {
+            write_commitment_tree(w, Some(c))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use std::io::{self, Read, Write};
+
+    use super::{read_compact_size, write_compact_size};
+    use crate::{
+        encoding::HashSer, BridgeTree, Frontier, Hashable, Level, NonEmptyFrontier, Position,
+        WitnessBundle,
+    };
+
+    /// A 32-byte node, as used by the Sprout, Sapling and Orchard commitment trees.
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Node([u8; 32]);
+
+    impl Hashable for Node {
+        fn empty_leaf() -> Self {
+            Node([0; 32])
+        }
+
+        fn combine(level: Level, a: &Self, b: &Self) -> Self {
+            let mut node = [0; 32];
+            for (i, byte) in node.iter_mut().enumerate() {
+                *byte = a.0[i]
+                    .wrapping_mul(3)
+                    .wrapping_add(b.0[i])
+                    .wrapping_add(u8::from(level));
+            }
+            Node(node)
+        }
+    }
+
+    impl HashSer for Node {
+        fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+            let mut node = [0; 32];
+            reader.read_exact(&mut node)?;
+            Ok(Node(node))
+        }
+
+        fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+            writer.write_all(&self.0)
+        }
+    }
+
+    fn leaf(i: u8) -> Node {
+        Node([i + 1; 32])
+    }
+
+    fn frontier(size: u8) -> Frontier<Node, 32> {
+        let mut frontier = Frontier::empty();
+        for i in 0..size {
+            frontier.append(&leaf(i));
+        }
+        frontier
+    }
+
+    fn check_fixture(frontier: &Frontier<Node, 32>, fixture: &[u8]) {
+        let mut bytes = vec![];
+        frontier.write_commitment_tree_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, fixture);
+        assert_eq!(
+            &Frontier::<Node, 32>::from_commitment_tree_bytes(fixture).unwrap(),
+            frontier
+        );
+    }
+
+    fn node_bytes(node: &Node) -> Vec<u8> {
+        let mut bytes = vec![1];
+        bytes.extend_from_slice(&node.0);
+        bytes
+    }
+
+    #[test]
+    fn empty_tree_fixture() {
+        check_fixture(&Frontier::empty(), &[0, 0, 0]);
+    }
+
+    #[test]
+    fn single_left_node_fixture() {
+        let mut fixture = node_bytes(&leaf(0));
+        fixture.extend_from_slice(&[0, 0]);
+        check_fixture(&frontier(1), &fixture);
+    }
+
+    #[test]
+    fn populated_fixtures() {
+        let l01 = Node::combine(Level::from(0), &leaf(0), &leaf(1));
+        let l23 = Node::combine(Level::from(0), &leaf(2), &leaf(3));
+        let l0123 = Node::combine(Level::from(1), &l01, &l23);
+        let l45 = Node::combine(Level::from(0), &leaf(4), &leaf(5));
+
+        // left and right nodes with no parents
+        let mut fixture = node_bytes(&leaf(0));
+        fixture.extend(node_bytes(&leaf(1)));
+        fixture.push(0);
+        check_fixture(&frontier(2), &fixture);
+
+        // a left node, with an empty parent below a populated one
+        let mut fixture = node_bytes(&leaf(4));
+        fixture.extend_from_slice(&[0, 2, 0]);
+        fixture.extend(node_bytes(&l0123));
+        check_fixture(&frontier(5), &fixture);
+
+        // left and right nodes with all parents populated
+        let mut fixture = node_bytes(&leaf(6));
+        fixture.extend(node_bytes(&leaf(7)));
+        fixture.push(2);
+        fixture.extend(node_bytes(&l45));
+        fixture.extend(node_bytes(&l0123));
+        check_fixture(&frontier(8), &fixture);
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn tree_state_hex_fixtures() {
+        // `finalState` as reported by `z_gettreestate` for a tree to which no leaves have been
+        // appended, such as the Sapling tree at Sapling activation
+        let empty = from_hex("000000");
+        let frontier = Frontier::<Node, 32>::from_commitment_tree_bytes(&empty[..]).unwrap();
+        assert_eq!(frontier, Frontier::empty());
+        let mut bytes = vec![];
+        frontier.write_commitment_tree_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, empty);
+
+        // left and right nodes, an empty parent at level 1 and populated parents at levels 2
+        // and 3: the tree of 14 leaves, of which the last was appended at position 13
+        let state = from_hex(concat!(
+            "01",
+            "0101010101010101010101010101010101010101010101010101010101010101",
+            "01",
+            "0202020202020202020202020202020202020202020202020202020202020202",
+            "03",
+            "00",
+            "01",
+            "0303030303030303030303030303030303030303030303030303030303030303",
+            "01",
+            "0404040404040404040404040404040404040404040404040404040404040404",
+        ));
+        let frontier = Frontier::<Node, 32>::from_commitment_tree_bytes(&state[..]).unwrap();
+        let tip = frontier.value().unwrap();
+        assert_eq!(tip.position(), Position::from(13));
+        assert_eq!(tip.leaf(), &Node([2; 32]));
+        assert_eq!(
+            tip.ommers(),
+            &[Node([1; 32]), Node([3; 32]), Node([4; 32])][..]
+        );
+        let mut bytes = vec![];
+        frontier.write_commitment_tree_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, state);
+    }
+
+    #[test]
+    fn round_trip() {
+        for size in 0..=70 {
+            let frontier = frontier(size);
+            let mut bytes = vec![];
+            frontier.write_commitment_tree_bytes(&mut bytes).unwrap();
+            assert_eq!(
+                Frontier::<Node, 32>::from_commitment_tree_bytes(&bytes[..]).unwrap(),
+                frontier
+            );
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        // a right node without a left node
+        let mut bytes = vec![0];
+        bytes.extend(node_bytes(&leaf(0)));
+        bytes.push(0);
+        assert!(Frontier::<Node, 32>::from_commitment_tree_bytes(&bytes[..]).is_err());
+
+        // a parent without a left node
+        let mut bytes = vec![0, 0, 1];
+        bytes.extend(node_bytes(&leaf(0)));
+        assert!(Frontier::<Node, 32>::from_commitment_tree_bytes(&bytes[..]).is_err());
+
+        // trailing empty parents are accepted
+        let mut bytes = node_bytes(&leaf(0));
+        bytes.extend_from_slice(&[0, 2, 0, 0]);
+        assert_eq!(
+            Frontier::<Node, 32>::from_commitment_tree_bytes(&bytes[..]).unwrap(),
+            frontier(1)
+        );
+
+        // a frontier that exceeds the depth of the tree is rejected
+        let mut bytes = node_bytes(&leaf(0));
+        bytes.extend_from_slice(&[0, 3, 0, 0]);
+        bytes.extend(node_bytes(&leaf(1)));
+        assert!(Frontier::<Node, 2>::from_commitment_tree_bytes(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn compact_size() {
+        for (size, encoded) in &[
+            (0, vec![0]),
+            (0xfc, vec![0xfc]),
+            (0xfd, vec![0xfd, 0xfd, 0x00]),
+            (0xffff, vec![0xfd, 0xff, 0xff]),
+            (0x1_0000, vec![0xfe, 0x00, 0x00, 0x01, 0x00]),
+        ] {
+            let mut bytes = vec![];
+            write_compact_size(&mut bytes, *size).unwrap();
+            assert_eq!(&bytes, encoded);
+            assert_eq!(read_compact_size(&encoded[..]).unwrap(), *size);
+        }
+
+        // non-canonical and oversized encodings are rejected
+        assert!(read_compact_size(&[0xfd, 0xfc, 0x00][..]).is_err());
+        assert!(read_compact_size(&[0xfe, 0xff, 0xff, 0x00, 0x00][..]).is_err());
+        assert!(read_compact_size(&[0xfe, 0x01, 0x00, 0x00, 0x02][..]).is_err());
+    }
+
+    /// A port of the `CommitmentTree` type of `librustzcash`, used as a reference
+    /// implementation of the serialized form.
+    #[derive(Clone, Default)]
+    struct CommitmentTree {
+        left: Option<Node>,
+        right: Option<Node>,
+        parents: Vec<Option<Node>>,
+    }
+
+    impl CommitmentTree {
+        fn append(&mut self, node: Node) {
+            match (self.left.take(), self.right.take()) {
+                (None, _) => self.left = Some(node),
+                (left, None) => {
+                    self.left = left;
+                    self.right = Some(node);
+                }
+                (Some(left), Some(right)) => {
+                    let mut combined = Node::combine(Level::from(0), &left, &right);
+                    self.left = Some(node);
+                    for (i, parent) in self.parents.iter_mut().enumerate() {
+                        match parent.take() {
+                            Some(p) => {
+                                combined = Node::combine(Level::from(i as u8 + 1), &p, &combined)
+                            }
+                            None => {
+                                *parent = Some(combined);
+                                return;
+                            }
+                        }
+                    }
+                    self.parents.push(Some(combined));
+                }
+            }
+        }
+
+        fn is_complete(&self, depth: usize) -> bool {
+            if depth == 0 {
+                self.left.is_some() && self.right.is_none() && self.parents.is_empty()
+            } else {
+                self.left.is_some()
+                    && self.right.is_some()
+                    && self.parents.len() == depth - 1
+                    && self.parents.iter().all(Option::is_some)
+            }
+        }
+
+        fn root(&self, depth: usize, filler: &mut Vec<Node>) -> Node {
+            let mut next = |level: usize| {
+                if filler.is_empty() {
+                    Node::empty_root(Level::from(level as u8))
+                } else {
+                    filler.remove(0)
+                }
+            };
+            let left = self.left.clone().unwrap_or_else(|| next(0));
+            let right = self.right.clone().unwrap_or_else(|| next(0));
+            let mut root = Node::combine(Level::from(0), &left, &right);
+            for i in 0..depth - 1 {
+                let level = Level::from(i as u8 + 1);
+                root = match self.parents.get(i).cloned().flatten() {
+                    Some(parent) => Node::combine(level, &parent, &root),
+                    None => Node::combine(level, &root, &next(i + 1)),
+                };
+            }
+            root
+        }
+
+        fn write(&self, bytes: &mut Vec<u8>) {
+            let write_optional = |bytes: &mut Vec<u8>, node: &Option<Node>| match node {
+                Some(node) => bytes.extend(node_bytes(node)),
+                None => bytes.push(0),
+            };
+            write_optional(bytes, &self.left);
+            write_optional(bytes, &self.right);
+            write_compact_size(&mut *bytes, self.parents.len()).unwrap();
+            for parent in &self.parents {
+                write_optional(bytes, parent);
+            }
+        }
+    }
+
+    /// A port of the `IncrementalWitness` type of `librustzcash`, used as a reference
+    /// implementation of the serialized form and of witness maintenance.
+    struct IncrementalWitness {
+        tree: CommitmentTree,
+        filled: Vec<Node>,
+        cursor_depth: usize,
+        cursor: Option<CommitmentTree>,
+    }
+
+    impl IncrementalWitness {
+        const DEPTH: usize = 8;
+
+        fn from_tree(tree: CommitmentTree) -> Self {
+            IncrementalWitness {
+                tree,
+                filled: vec![],
+                cursor_depth: 0,
+                cursor: None,
+            }
+        }
+
+        fn next_depth(&self) -> usize {
+            let mut skip = self.filled.len();
+            if self.tree.left.is_none() {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    return 0;
+                }
+            }
+            if self.tree.right.is_none() {
+                if skip > 0 {
+                    skip -= 1;
+                } else {
+                    return 0;
+                }
+            }
+            let mut d = 1;
+            for p in &self.tree.parents {
+                if p.is_none() {
+                    if skip > 0 {
+                        skip -= 1;
+                    } else {
+                        return d;
+                    }
+                }
+                d += 1;
+            }
+            d + skip
+        }
+
+        fn append(&mut self, node: Node) {
+            if let Some(mut cursor) = self.cursor.take() {
+                cursor.append(node);
+                if cursor.is_complete(self.cursor_depth) {
+                    self.filled
+                        .push(cursor.root(self.cursor_depth, &mut vec![]));
+                } else {
+                    self.cursor = Some(cursor);
+                }
+            } else {
+                self.cursor_depth = self.next_depth();
+                assert!(self.cursor_depth < Self::DEPTH);
+                if self.cursor_depth == 0 {
+                    self.filled.push(node);
+                } else {
+                    let mut cursor = CommitmentTree::default();
+                    cursor.append(node);
+                    self.cursor = Some(cursor);
+                }
+            }
+        }
+
+        fn path(&self) -> Vec<Node> {
+            let mut filler = self.filled.clone();
+            filler.extend(
+                self.cursor
+                    .as_ref()
+                    .map(|c| c.root(self.cursor_depth, &mut vec![])),
+            );
+            let mut next = |level: usize| {
+                if filler.is_empty() {
+                    Node::empty_root(Level::from(level as u8))
+                } else {
+                    filler.remove(0)
+                }
+            };
+
+            let mut path = vec![match (&self.tree.left, &self.tree.right) {
+                (Some(left), Some(_)) => left.clone(),
+                _ => next(0),
+            }];
+            for i in 0..Self::DEPTH - 1 {
+                path.push(match self.tree.parents.get(i).cloned().flatten() {
+                    Some(parent) => parent,
+                    None => next(i + 1),
+                });
+            }
+            path
+        }
+
+        fn write(&self) -> Vec<u8> {
+            let mut bytes = vec![];
+            self.tree.write(&mut bytes);
+            write_compact_size(&mut bytes, self.filled.len()).unwrap();
+            for node in &self.filled {
+                bytes.extend_from_slice(&node.0);
+            }
+            match &self.cursor {
+                Some(cursor) => {
+                    bytes.push(1);
+                    cursor.write(&mut bytes);
+                }
+                None => bytes.push(0),
+            }
+            bytes
+        }
+    }
+
+    fn tip(size: u8) -> NonEmptyFrontier<Node> {
+        frontier(size).value().unwrap().clone()
+    }
+
+    #[test]
+    fn incremental_witness_fixture() {
+        // the first of two leaves, witnessed after the second is appended
+        let mut fixture = node_bytes(&leaf(0));
+        fixture.extend_from_slice(&[0, 0, 1]);
+        fixture.extend_from_slice(&leaf(1).0);
+        fixture.push(0);
+
+        let bundle = WitnessBundle::from_incremental_witness_bytes(&fixture[..], tip(2)).unwrap();
+        assert_eq!(bundle.position(), Position::from(0));
+        assert_eq!(bundle.leaf(), &leaf(0));
+        assert_eq!(bundle.path(8).unwrap()[0], leaf(1));
+
+        let mut bytes = vec![];
+        bundle.to_incremental_witness_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, fixture);
+
+        // the second of two leaves, witnessed after a third is appended; the third leaf is
+        // held in the cursor, a subtree of depth 1
+        let mut fixture = vec![];
+        for chunk in &[
+            &[1][..],
+            &[1; 32],
+            &[1],
+            &[2; 32],
+            &[0, 0, 1, 1],
+            &[3; 32],
+            &[0, 0],
+        ] {
+            fixture.extend_from_slice(chunk);
+        }
+        let mut tree = CommitmentTree::default();
+        tree.append(leaf(0));
+        tree.append(leaf(1));
+        let mut witness = IncrementalWitness::from_tree(tree);
+        witness.append(leaf(2));
+        assert_eq!(witness.write(), fixture);
+
+        let bundle = WitnessBundle::from_incremental_witness_bytes(&fixture[..], tip(3)).unwrap();
+        assert_eq!(bundle.position(), Position::from(1));
+        assert_eq!(bundle.leaf(), &leaf(1));
+        // the sibling at level 1 is combine(0, leaf(2), empty) and the remainder are the
+        // empty roots of each level
+        let path = [1, 9, 1, 6, 27, 112, 197, 26]
+            .iter()
+            .map(|b| Node([*b; 32]))
+            .collect::<Vec<_>>();
+        assert_eq!(bundle.path(8).unwrap(), path);
+        assert_eq!(witness.path(), path);
+
+        let mut bytes = vec![];
+        bundle.to_incremental_witness_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, fixture);
+    }
+
+    #[test]
+    fn incremental_witness_round_trip() {
+        for marked in 0u8..20 {
+            let mut tree = CommitmentTree::default();
+            for i in 0..=marked {
+                tree.append(leaf(i));
+            }
+            let mut witness = IncrementalWitness::from_tree(tree);
+            let mut expected = WitnessBundle::new(tip(marked + 1));
+            for size in marked + 1..40 {
+                let bytes = witness.write();
+                let bundle =
+                    WitnessBundle::from_incremental_witness_bytes(&bytes[..], tip(size)).unwrap();
+                assert_eq!(bundle, expected);
+                assert_eq!(bundle.path(8).unwrap(), witness.path());
+
+                let mut written = vec![];
+                bundle.to_incremental_witness_bytes(&mut written).unwrap();
+                assert_eq!(written, bytes);
+
+                witness.append(leaf(size));
+                expected.append(leaf(size));
+            }
+        }
+    }
+
+    #[test]
+    fn imported_witness_is_maintained() {
+        let mut tree = CommitmentTree::default();
+        for i in 0..=5 {
+            tree.append(leaf(i));
+        }
+        let mut witness = IncrementalWitness::from_tree(tree);
+        for i in 6..11 {
+            witness.append(leaf(i));
+        }
+
+        let bundle =
+            WitnessBundle::from_incremental_witness_bytes(&witness.write()[..], tip(11)).unwrap();
+        let mut bundle_tree = BridgeTree::<Node, 8>::from_witness_bundle(10, bundle.clone());
+        let mut bundle = bundle;
+        for i in 11..60 {
+            witness.append(leaf(i));
+            bundle.append(leaf(i));
+            bundle_tree.append(&leaf(i));
+
+            let path = witness.path();
+            assert_eq!(bundle.path(8).unwrap(), path);
+            let root = bundle_tree.root(0).unwrap();
+            assert_eq!(bundle.root(8), root);
+            assert_eq!(bundle_tree.witness(Position::from(5), &root), Some(path));
+        }
+    }
+
+    #[test]
+    fn incremental_witness_inconsistent_tip() {
+        let mut tree = CommitmentTree::default();
+        tree.append(leaf(0));
+        let mut witness = IncrementalWitness::from_tree(tree);
+        for i in 1..6 {
+            witness.append(leaf(i));
+        }
+        let bytes = witness.write();
+        assert!(WitnessBundle::from_incremental_witness_bytes(&bytes[..], tip(6)).is_ok());
+
+        // the tip is at the wrong position
+        assert!(WitnessBundle::from_incremental_witness_bytes(&bytes[..], tip(5)).is_err());
+        assert!(WitnessBundle::from_incremental_witness_bytes(&bytes[..], tip(7)).is_err());
+
+        // the tip's leaf does not match the cursor
+        let wrong_leaf = |tip: NonEmptyFrontier<Node>| {
+            NonEmptyFrontier::from_parts(tip.position(), leaf(9), tip.ommers().to_vec()).unwrap()
+        };
+        assert!(
+            WitnessBundle::from_incremental_witness_bytes(&bytes[..], wrong_leaf(tip(6))).is_err()
+        );
+
+        // no leaves have been appended since the witnessed leaf, so the tip must be the
+        // witnessed tree itself
+        let mut tree = CommitmentTree::default();
+        for i in 0..3 {
+            tree.append(leaf(i));
+        }
+        let bytes = IncrementalWitness::from_tree(tree).write();
+        assert!(WitnessBundle::from_incremental_witness_bytes(&bytes[..], tip(3)).is_ok());
+        assert!(
+            WitnessBundle::from_incremental_witness_bytes(&bytes[..], wrong_leaf(tip(3))).is_err()
+        );
+    }
+
+    #[test]
+    fn incremental_witness_too_many_filled_nodes() {
+        // each filled node is the root of a subtree at a higher level than the last, so a
+        // witness cannot have more filled nodes than there are levels in a tree
+        let mut bytes = node_bytes(&leaf(0));
+        bytes.extend_from_slice(&[0, 0, 70]);
+        for i in 0..70 {
+            bytes.extend_from_slice(&leaf(i).0);
+        }
+        bytes.push(0);
+        let err = WitnessBundle::from_incremental_witness_bytes(&bytes[..], tip(1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,15 +1,102 @@
+use alloc::vec::Vec;
+
 use crate::position::Level;
 
 /// A trait describing the operations that make a type suitable for use as
 /// a leaf or node value in a merkle tree.
+///
+/// The values appended to a tree are used as its leaves without further hashing; a Merkle
+/// construction that hashes leaf data before it enters the tree should apply that hash before
+/// appending. Domain separation between leaves and internal nodes may then be achieved either in
+/// the leaf hash itself or in [`Hashable::combine`], which is always told the level of the nodes
+/// being combined, such that leaves are only ever combined at level 0.
+///
+/// Trees and frontiers clone leaf and node values where the same value must be retained in more
+/// than one place, such as when a new bridge is created to follow the current one. For hash
+/// types that are expensive to clone, the values may be shared by reference counting, so that
+/// each clone only increments a count:
+///
+/// ```
+/// use std::sync::Arc;
+/// use bridgetree::{BridgeTree, Hashable, Level};
+///
+/// #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// struct ArcHash(Arc<[u8; 64]>);
+///
+/// impl Hashable for ArcHash {
+///     fn empty_leaf() -> Self {
+///         ArcHash(Arc::new([0; 64]))
+///     }
+///
+///     fn combine(level: Level, a: &Self, b: &Self) -> Self {
+///         // a stand-in for a real hash function
+///         let mut node = [u8::from(level); 64];
+///         for (i, n) in node.iter_mut().enumerate() {
+///             *n ^= a.0[i].rotate_left(1) ^ b.0[(i + 1) % 64];
+///         }
+///         ArcHash(Arc::new(node))
+///     }
+/// }
+///
+/// let mut tree = BridgeTree::<ArcHash, 8>::new(10);
+/// let leaf = ArcHash(Arc::new([1; 64]));
+/// tree.append(&leaf);
+/// tree.mark();
+/// tree.checkpoint();
+/// assert!(Arc::strong_count(&leaf.0) > 1);
+/// ```
 pub trait Hashable: Sized {
+    /// Returns the value of a leaf that has not yet been appended to the tree. This is the
+    /// value used in place of a leaf hash, and so is not itself passed through any leaf hash.
     fn empty_leaf() -> Self;
 
+    /// Combines the values of two sibling nodes at the given level to produce the value of their
+    /// parent at the next level; at level 0, `a` and `b` are leaf values.
     fn combine(level: Level, a: &Self, b: &Self) -> Self;
 
+    /// Combines each pair of nodes at the given level, returning the results in order.
+    ///
+    /// This is used when many independent nodes at the same level need to be computed, such as
+    /// when constructing a frontier from a large number of leaves. The default implementation
+    /// calls [`Hashable::combine`] for each pair; implementations that can hash multiple inputs
+    /// at once may override it.
+    fn combine_batch(level: Level, pairs: &[(Self, Self)]) -> Vec<Self> {
+        pairs
+            .iter()
+            .map(|(a, b)| Self::combine(level, a, b))
+            .collect()
+    }
+
     fn empty_root(level: Level) -> Self {
         Level::from(0)
             .iter_to(level)
             .fold(Self::empty_leaf(), |v, lvl| Self::combine(lvl, &v, &v))
     }
+
+    /// Scrubs a leaf or node value that a tree or frontier is about to discard, such as the
+    /// values removed by [`BridgeTree::rewind`] or [`BridgeTree::remove_mark`].
+    ///
+    /// The default implementation does nothing; hash types that implement `Zeroize` should
+    /// override it to call `Zeroize::zeroize`.
+    ///
+    /// [`BridgeTree::rewind`]: crate::BridgeTree::rewind
+    /// [`BridgeTree::remove_mark`]: crate::BridgeTree::remove_mark
+    #[cfg(feature = "zeroize")]
+    fn scrub(&mut self) {}
+}
+
+/// Returns the roots of empty subtrees at each level from 0 through `level` inclusive, such that
+/// the element at index `k` is the root of an empty subtree at level `k`. The roots are computed
+/// by successive calls to [`Hashable::combine`], beginning with [`Hashable::empty_leaf`].
+pub fn empty_roots<H: Hashable>(level: Level) -> Vec<H> {
+    let mut roots = Vec::with_capacity(usize::from(u8::from(level)) + 1);
+    roots.push(H::empty_leaf());
+    for lvl in Level::from(0).iter_to(level) {
+        let root = {
+            let prev = roots.last().expect("roots is nonempty");
+            H::combine(lvl, prev, prev)
+        };
+        roots.push(root);
+    }
+    roots
 }
@@ -30,25 +30,265 @@
 //! reset the state to.
 //!
 //! In this module, the term "ommer" is used as for the sibling of a parent node in a binary tree.
+//!
+//! ## Thread safety
+//!
+//! The data structures in this crate contain no interior mutability, and so are `Send` and `Sync`
+//! whenever the hash type `H` (and any metadata types) are. When the `rayon` feature is enabled,
+//! `Frontier::build_parallel` may be used to construct a frontier from a large number of
+//! leaves using multiple threads.
+//!
+//! ## Metrics
+//!
+//! When the `metrics` feature is enabled, the tree reports counts of the operations performed
+//! on it via the [`metrics`](https://docs.rs/metrics) facade, to be collected by whichever
+//! recorder the application has installed. The following counters are reported:
+//!
+//! - `bridgetree_appends`: leaves appended to a tree.
+//! - `bridgetree_marks_added`: positions newly marked.
+//! - `bridgetree_marks_removed`: marks removed.
+//! - `bridgetree_checkpoints_added`: checkpoints created.
+//! - `bridgetree_checkpoints_dropped`: checkpoints discarded without being rewound to, either
+//!   because `max_checkpoints` was exceeded, because they were drained, or because they were
+//!   removed by checkpoint compaction.
+//! - `bridgetree_rewinds`: attempts to rewind the tree.
+//! - `bridgetree_rewinds_failed`: rewind attempts that failed and left the tree unmodified.
+//! - `bridgetree_witnesses`: witnesses produced for marked positions.
+//! - `bridgetree_combines`: nodes combined by this crate, including by frontiers. Each pair
+//!   passed to [`Hashable::combine_batch`] is counted; invocations of [`Hashable::combine`]
+//!   made by implementations of [`Hashable::empty_root`] are not.
+//!
+//! Neither witnesses nor combinations computed by the tree's internal consistency checks, such as
+//! [`BridgeTree::verify_root_consistency`] and the self-checks performed in debug builds, are
+//! counted, so the counts do not depend upon the build profile.
+//!
+//! When the feature is disabled, no counting is performed.
+//!
+//! ## Zeroization
+//!
+//! When the `zeroize` feature is enabled, the tree and its components implement `Zeroize`
+//! whenever the hash type `H` does, so that the leaf and node values that they contain can be
+//! scrubbed from memory on demand, and implement `ZeroizeOnDrop` whenever `H` does, as each
+//! of those values is then zeroized as the tree is dropped.
+//!
+//! Values that the tree discards in the course of its operation, such as those removed by
+//! [`BridgeTree::remove_mark`], [`BridgeTree::rewind`], or [`BridgeTree::garbage_collect`], and
+//! the ommers that a frontier replaces as leaves are appended, are passed to
+//! `Hashable::scrub` before they are dropped; a hash type that implements `Zeroize` should
+//! implement `scrub` by zeroizing itself. Scrubbing is best-effort: temporary values produced
+//! while computing roots and witnesses, and copies left behind when collections reallocate,
+//! are not scrubbed.
+//!
+//! ## `no_std` support
+//!
+//! The crate depends only on `core` and `alloc` unless the `std` feature, which is enabled by
+//! default, is enabled. Without it, the crate may be used in environments such as embedded
+//! devices and secure enclaves that provide an allocator but no standard library; the
+//! [`encoding`] module, which is written in terms of `std::io`, is then unavailable.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod compact;
+#[cfg(feature = "std")]
+pub mod encoding;
 mod hashing;
 mod position;
+mod sharded;
 
 #[cfg(any(bench, test, feature = "test-dependencies"))]
 pub mod testing;
 
+use alloc::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::Debug;
+use core::mem::size_of;
+use core::ops::Range;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
-use std::convert::TryFrom;
-use std::fmt::Debug;
-use std::mem::size_of;
-use std::ops::Range;
+
+#[cfg(feature = "zeroize")]
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::position::Source;
 pub use crate::{
-    hashing::Hashable,
-    position::{Address, Level, Position},
+    hashing::{empty_roots, Hashable},
+    position::{Address, Level, Position, SlotKind},
+    sharded::ShardedTree,
 };
 
+/// Increments the named counter via the `metrics` facade when the `metrics` feature is
+/// enabled, and does nothing otherwise.
+macro_rules! count {
+    ($name:literal) => {
+        count!($name, 1)
+    };
+    ($name:literal, $value:expr) => {
+        #[cfg(feature = "metrics")]
+        metrics::counter!($name, $value as u64);
+    };
+}
+
+/// Returns the number of leaves that may be appended to a tree of the specified depth whose
+/// most recently appended leaf is at the specified position, saturating at `usize::MAX`.
+fn remaining_capacity(depth: Level, position: Option<Position>) -> usize {
+    let size = position.map_or(0, |p| u128::from(u64::from(p)) + 1);
+    1u128
+        .checked_shl(u8::from(depth).into())
+        .map_or(usize::MAX, |capacity| {
+            usize::try_from(capacity - size).unwrap_or(usize::MAX)
+        })
+}
+
+/// Combines the specified nodes at the given level, counting the invocation.
+fn combine<H: Hashable>(level: Level, a: &H, b: &H) -> H {
+    count!("bridgetree_combines");
+    H::combine(level, a, b)
+}
+
+/// Inserts the root of a complete subtree at the specified level into a vector of ommers,
+/// combining it with ommers at the same level as needed.
+///
+/// `ommers` must be ordered by increasing level, and `ommer_levels` must yield the strictly
+/// increasing levels of the ommers in the same order. The `carry` value is the root of the
+/// subtree immediately to the right of the ommer at its level, if any; each such ommer is
+/// removed and combined with the carry as its left sibling, producing a carry at the next
+/// level, until a level having no ommer is reached, at which point the carry is inserted. The
+/// ommers at other levels are moved into place unchanged.
+///
+/// This is the carry operation used when a leaf is appended to a [`NonEmptyFrontier`], in which
+/// case the carry is the prior leaf at level 0.
+pub fn fold_carry<H: Hashable>(
+    ommers: &mut Vec<H>,
+    carry: H,
+    level: Level,
+    ommer_levels: impl Iterator<Item = Level>,
+) {
+    let plan = CarryPlan::new(ommers, &carry, level, ommer_levels);
+    plan.apply(ommers, carry);
+}
+
+/// The effect of a carry on a vector of ommers, computed without modifying the ommers so that
+/// a panic in [`Hashable::combine`] leaves them intact.
+struct CarryPlan<H> {
+    /// The number of ommers that have a corresponding level; any further ommers are discarded.
+    retained: usize,
+    /// The range of ommers that are combined with the carry and replaced by its final value.
+    replaced: Range<usize>,
+    /// The final value of the carry, or `None` if the carry was not combined with any ommer.
+    combined: Option<H>,
+}
+
+impl<H: Hashable> CarryPlan<H> {
+    fn new(
+        ommers: &[H],
+        carry: &H,
+        level: Level,
+        ommer_levels: impl Iterator<Item = Level>,
+    ) -> Self {
+        let mut retained = 0;
+        let mut replaced = 0..0;
+        let mut combined: Option<H> = None;
+        let mut carry_level = Some(level);
+        for (i, (ommer, ommer_level)) in ommers.iter().zip(ommer_levels).enumerate() {
+            retained = i + 1;
+            match carry_level {
+                Some(l) if l == ommer_level => {
+                    // the ommer is the left sibling of the carry, and is replaced by the parent
+                    let parent = combine(ommer_level, ommer, combined.as_ref().unwrap_or(carry));
+                    if let Some(prior) = combined.replace(parent) {
+                        discard(prior);
+                    }
+                    carry_level = Some(ommer_level + 1);
+                    replaced.end = i + 1;
+                }
+                Some(l) if l < ommer_level => {
+                    // the carry is inserted at the first empty slot; the rest of the ommers
+                    // remain unchanged
+                    carry_level = None;
+                }
+                Some(_) => {
+                    // the ommer lies below the carry
+                    replaced = (i + 1)..(i + 1);
+                }
+                None => {}
+            }
+        }
+
+        CarryPlan {
+            retained,
+            replaced,
+            combined,
+        }
+    }
+
+    /// Replaces the combined ommers with the final value of the carry, which is `carry` itself
+    /// if it was not combined with any ommer.
+    fn apply(self, ommers: &mut Vec<H>, carry: H) {
+        if ommers.len() > self.retained {
+            ommers.drain(self.retained..).for_each(discard);
+        }
+        let carry = match self.combined {
+            Some(combined) => {
+                discard(carry);
+                combined
+            }
+            None => carry,
+        };
+        ommers
+            .splice(self.replaced, core::iter::once(carry))
+            .for_each(discard);
+    }
+}
+
+/// Scrubs a value that is about to be discarded, if the `zeroize` feature is enabled.
+#[allow(unused_variables)]
+fn scrub<H: Hashable>(value: &mut H) {
+    #[cfg(feature = "zeroize")]
+    value.scrub();
+}
+
+/// Drops a value, first scrubbing it if the `zeroize` feature is enabled.
+fn discard<H: Hashable>(mut value: H) {
+    scrub(&mut value);
+}
+
+/// Records in an index of marked leaf values that the specified value is marked at the
+/// specified position.
+fn index_mark<H: Ord + Clone>(
+    index: &mut BTreeMap<H, BTreeSet<Position>>,
+    value: &H,
+    position: Position,
+) {
+    match index.get_mut(value) {
+        Some(positions) => {
+            positions.insert(position);
+        }
+        None => {
+            index.insert(value.clone(), Some(position).into_iter().collect());
+        }
+    }
+}
+
+/// Removes the specified position from the positions at which the specified value is marked
+/// in an index of marked leaf values, discarding the value once no positions remain.
+fn unindex_mark<H: Hashable + Ord>(
+    index: &mut BTreeMap<H, BTreeSet<Position>>,
+    value: &H,
+    position: Position,
+) {
+    if let Some(positions) = index.get_mut(value) {
+        positions.remove(&position);
+        if positions.is_empty() {
+            if let Some((value, _)) = index.remove_entry(value) {
+                discard(value);
+            }
+        }
+    }
+}
+
 /// Validation errors that can occur during reconstruction of a Merkle frontier from
 /// its constituent parts.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -84,16 +324,62 @@ pub enum WitnessingError {
     BridgeAddressInvalid(Address),
 }
 
+/// Errors that can occur when marking a leaf that is not the most recently appended leaf of a
+/// [`BridgeTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MarkError {
+    /// The position has not yet been appended to the tree.
+    PositionNotAppended(Position),
+    /// No bridge ending at the position has been retained, and so the leaf value at that
+    /// position is not available.
+    BridgeNotFound(Position),
+    /// The value of the node at the given address, which is required to witness the position,
+    /// has not been retained.
+    NodeNotFound(Address),
+}
+
 /// A [`NonEmptyFrontier`] is a reduced representation of a Merkle tree, containing a single leaf
 /// value, along with the vector of hashes produced by the reduction of previously appended leaf
 /// values that will be required when producing a witness for the current leaf.
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Deserialization checks that the number of ommers is consistent with the position of the
+/// frontier, in the same fashion as [`NonEmptyFrontier::from_parts`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct NonEmptyFrontier<H> {
+    // The derived `PartialEq` compares fields in declaration order, so that frontiers at
+    // different positions are distinguished without comparing any hash values.
+    position: Position,
+    leaf: H,
+    ommers: Vec<H>,
+}
+
+/// The unvalidated serialized form of a [`NonEmptyFrontier`].
+#[derive(Deserialize)]
+struct NonEmptyFrontierFields<H> {
     position: Position,
     leaf: H,
     ommers: Vec<H>,
 }
 
+impl<'de, H: Deserialize<'de>> Deserialize<'de> for NonEmptyFrontier<H> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = NonEmptyFrontierFields::<H>::deserialize(deserializer)?;
+        let (position, found) = (fields.position, fields.ommers.len());
+        NonEmptyFrontier::from_parts(position, fields.leaf, fields.ommers).map_err(|e| {
+            let expected = match e {
+                FrontierError::PositionMismatch { expected_ommers } => expected_ommers,
+                FrontierError::MaxDepthExceeded { .. } => unreachable!(
+                    "NonEmptyFrontier::from_parts does not check the depth of the frontier"
+                ),
+            };
+            serde::de::Error::custom(format!(
+                "a frontier at {:?} requires {} ommers, found {}",
+                position, expected, found
+            ))
+        })
+    }
+}
+
 impl<H> NonEmptyFrontier<H> {
     /// Constructs a new frontier with the specified value at position 0.
     pub fn new(leaf: H) -> Self {
@@ -118,6 +404,14 @@ impl<H> NonEmptyFrontier<H> {
         }
     }
 
+    /// Returns whether the number of ommers in the frontier is consistent with its position, as
+    /// is required by [`NonEmptyFrontier::from_parts`] and checked on deserialization. This
+    /// holds for every frontier constructed by this crate; [`BridgeTree::from_parts`] checks it
+    /// for the frontier of each bridge.
+    pub fn is_structurally_valid(&self) -> bool {
+        self.ommers.len() == self.position.past_ommer_count()
+    }
+
     /// Returns the position of the most recently appended leaf.
     pub fn position(&self) -> Position {
         self.position
@@ -133,60 +427,79 @@ impl<H> NonEmptyFrontier<H> {
     pub fn ommers(&self) -> &[H] {
         &self.ommers
     }
+
+    /// Returns the ommer at the specified level, or `None` if the witness for the most
+    /// recently appended leaf does not include a past hash at that level.
+    ///
+    /// An ommer exists at level `l` exactly when bit `l` of the frontier's position is set.
+    pub fn ommer_at(&self, level: Level) -> Option<&H> {
+        let level = u8::from(level) as u32;
+        let position = usize::from(self.position);
+        if level < usize::BITS && (position >> level) & 0x1 == 1 {
+            // ommers are stored in order of increasing level, so the index of the ommer is
+            // the number of ommers that exist at lower levels.
+            let lower_mask = (1 << level) - 1;
+            self.ommers
+                .get((position & lower_mask).count_ones() as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the rightmost ommer of the frontier, which is the ommer at the lowest level,
+    /// or `None` if the frontier has no ommers.
+    pub fn rightmost_ommer(&self) -> Option<&H> {
+        self.ommers.first()
+    }
 }
 
 impl<H: Hashable + Clone> NonEmptyFrontier<H> {
+    /// Drops the frontier, first scrubbing its values if the `zeroize` feature is enabled.
+    fn discard(self) {
+        discard(self.leaf);
+        self.ommers.into_iter().for_each(discard);
+    }
+
     /// Append a new leaf to the frontier, and recompute recompute ommers by hashing together full
     /// subtrees until an empty ommer slot is found.
+    ///
+    /// All hashing is performed before the frontier is modified, so if [`Hashable::combine`]
+    /// panics, the frontier is left unchanged.
     pub fn append(&mut self, leaf: H) {
         let prior_position = self.position;
-        let prior_leaf = self.leaf.clone();
-        self.position += 1;
-        self.leaf = leaf;
-        if self.position.is_odd() {
+        let position = prior_position + 1;
+        if position.is_odd() {
             // if the new position is odd, the current leaf will directly become
             // an ommer at level 0, and there is no other mutation made to the tree.
+            let prior_leaf = core::mem::replace(&mut self.leaf, leaf);
             self.ommers.insert(0, prior_leaf);
         } else {
             // if the new position is even, then the current leaf will be hashed
             // with the first ommer, and so forth up the tree.
-            let new_root_level = self.position.root_level();
-
-            let mut carry = Some((prior_leaf, 0.into()));
-            let mut new_ommers = Vec::with_capacity(self.position.past_ommer_count());
-            for (addr, source) in prior_position.witness_addrs(new_root_level) {
-                if let Source::Past(i) = source {
-                    if let Some((carry_ommer, carry_lvl)) = carry.as_ref() {
-                        if *carry_lvl == addr.level() {
-                            carry = Some((
-                                H::combine(addr.level(), &self.ommers[i], carry_ommer),
-                                addr.level() + 1,
-                            ))
-                        } else {
-                            // insert the carry at the first empty slot; then the rest of the
-                            // ommers will remain unchanged
-                            new_ommers.push(carry_ommer.clone());
-                            new_ommers.push(self.ommers[i].clone());
-                            carry = None;
-                        }
-                    } else {
-                        // when there's no carry, just push on the ommer value
-                        new_ommers.push(self.ommers[i].clone());
-                    }
-                }
-            }
-
-            // we carried value out, so we need to push on one more ommer.
-            if let Some((carry_ommer, _)) = carry {
-                new_ommers.push(carry_ommer);
-            }
-
-            self.ommers = new_ommers;
+            let new_root_level = position.root_level();
+
+            let ommer_levels =
+                prior_position
+                    .witness_addrs(new_root_level)
+                    .filter_map(|(addr, source)| match source {
+                        Source::Past(_) => Some(addr.level()),
+                        Source::Future => None,
+                    });
+            let plan = CarryPlan::new(&self.ommers, &self.leaf, 0.into(), ommer_levels);
+            let prior_leaf = core::mem::replace(&mut self.leaf, leaf);
+            plan.apply(&mut self.ommers, prior_leaf);
         }
+        self.position = position;
     }
 
     /// Generate the root of the Merkle tree by hashing against empty subtree roots.
     pub fn root(&self, root_level: Option<Level>) -> H {
+        self.root_with(root_level, combine)
+    }
+
+    // Computes the root in the same fashion as `root`, using the specified function to combine
+    // nodes so that the internal consistency checks of the tree can avoid counting their hashes.
+    fn root_with(&self, root_level: Option<Level>, combine: fn(Level, &H, &H) -> H) -> H {
         let max_level = root_level.unwrap_or_else(|| self.position.root_level());
         self.position
             .witness_addrs(max_level)
@@ -197,12 +510,12 @@ impl<H: Hashable + Clone> NonEmptyFrontier<H> {
                     // complete_lvl == addr.level() this is just the complete digest to this point
                     let digest = complete_lvl
                         .iter_to(addr.level())
-                        .fold(digest, |d, l| H::combine(l, &d, &H::empty_root(l)));
+                        .fold(digest, |d, l| combine(l, &d, &H::empty_root(l)));
 
                     let res_digest = match source {
-                        Source::Past(i) => H::combine(addr.level(), &self.ommers[i], &digest),
+                        Source::Past(i) => combine(addr.level(), &self.ommers[i], &digest),
                         Source::Future => {
-                            H::combine(addr.level(), &digest, &H::empty_root(addr.level()))
+                            combine(addr.level(), &digest, &H::empty_root(addr.level()))
                         }
                     };
 
@@ -232,17 +545,70 @@ impl<H: Hashable + Clone> NonEmptyFrontier<H> {
     }
 }
 
+/// Zeroizes the leaf and ommers of the frontier. The frontier's position is retained, and so
+/// the frontier must not be used after it has been zeroized.
+#[cfg(feature = "zeroize")]
+impl<H: Zeroize> Zeroize for NonEmptyFrontier<H> {
+    fn zeroize(&mut self) {
+        self.leaf.zeroize();
+        self.ommers.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H: ZeroizeOnDrop> ZeroizeOnDrop for NonEmptyFrontier<H> {}
+
 /// A possibly-empty Merkle frontier.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// The depth of the frontier is included in its serialized form, and deserialization will fail
+/// if the serialized depth does not match `DEPTH`, or if the frontier could not have been
+/// constructed by [`Frontier::from_parts`].
+///
+/// Frontiers serialized before the depth was included in their serialized form are deserialized
+/// as having depth `DEPTH`, but only from self-describing formats such as JSON, in which the
+/// absence of the depth can be detected. Formats that encode fields by position, such as
+/// `bincode`, read the first byte of such data as the depth, and so fail to deserialize it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Frontier<H, const DEPTH: u8> {
+    depth: ConstDepth<DEPTH>,
+    frontier: Option<NonEmptyFrontier<H>>,
+}
+
+/// The unvalidated serialized form of a [`Frontier`].
+#[derive(Deserialize)]
+struct FrontierFields<H, const DEPTH: u8> {
+    // validated against `DEPTH` when it is deserialized, and otherwise unused
+    #[serde(default)]
+    #[allow(dead_code)]
+    depth: ConstDepth<DEPTH>,
     frontier: Option<NonEmptyFrontier<H>>,
 }
 
+impl<'de, H: Deserialize<'de>, const DEPTH: u8> Deserialize<'de> for Frontier<H, DEPTH> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match FrontierFields::<H, DEPTH>::deserialize(deserializer)?.frontier {
+            None => Ok(Frontier::empty()),
+            Some(f) => {
+                let position = f.position();
+                Frontier::try_from(f).map_err(|_| {
+                    serde::de::Error::custom(format!(
+                        "a frontier at {:?} exceeds the capacity of a tree of depth {}",
+                        position, DEPTH
+                    ))
+                })
+            }
+        }
+    }
+}
+
 impl<H, const DEPTH: u8> TryFrom<NonEmptyFrontier<H>> for Frontier<H, DEPTH> {
     type Error = FrontierError;
     fn try_from(f: NonEmptyFrontier<H>) -> Result<Self, FrontierError> {
         if f.position.root_level() <= Level::from(DEPTH) {
-            Ok(Frontier { frontier: Some(f) })
+            Ok(Frontier {
+                depth: ConstDepth,
+                frontier: Some(f),
+            })
         } else {
             Err(FrontierError::MaxDepthExceeded {
                 depth: f.position.root_level().into(),
@@ -251,10 +617,24 @@ impl<H, const DEPTH: u8> TryFrom<NonEmptyFrontier<H>> for Frontier<H, DEPTH> {
     }
 }
 
+/// Zeroizes the values of the frontier, leaving it empty.
+#[cfg(feature = "zeroize")]
+impl<H: Zeroize, const DEPTH: u8> Zeroize for Frontier<H, DEPTH> {
+    fn zeroize(&mut self) {
+        self.frontier.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H: ZeroizeOnDrop, const DEPTH: u8> ZeroizeOnDrop for Frontier<H, DEPTH> {}
+
 impl<H, const DEPTH: u8> Frontier<H, DEPTH> {
     /// Constructs a new empty frontier.
     pub fn empty() -> Self {
-        Self { frontier: None }
+        Self {
+            depth: ConstDepth,
+            frontier: None,
+        }
     }
 
     /// Constructs a new frontier from its constituent parts.
@@ -286,19 +666,56 @@ impl<H: Hashable + Clone, const DEPTH: u8> Frontier<H, DEPTH> {
     /// Returns true if successful and false if the frontier would exceed
     /// the maximum allowed depth.
     pub fn append(&mut self, value: &H) -> bool {
+        self.append_owned(value.clone())
+    }
+
+    /// Appends a new value to the frontier at the next available slot, taking ownership of the
+    /// value so that it need not be cloned. Returns true if successful and false if the frontier
+    /// would exceed the maximum allowed depth.
+    pub fn append_owned(&mut self, value: H) -> bool {
         if let Some(frontier) = self.frontier.as_mut() {
             if frontier.position().is_complete_subtree(DEPTH.into()) {
                 false
             } else {
-                frontier.append(value.clone());
+                frontier.append(value);
                 true
             }
         } else {
-            self.frontier = Some(NonEmptyFrontier::new(value.clone()));
+            self.frontier = Some(NonEmptyFrontier::new(value));
             true
         }
     }
 
+    /// Appends each of the leaves appended by the specified batch of operations, in order, so
+    /// that a frontier may track the root of a [`BridgeTree`] to which the same batch is applied
+    /// with [`BridgeTree::apply_batch`]. A frontier has no marks or checkpoints, so leaves
+    /// appended by [`BatchOp::AppendMarked`] are appended without being marked, and the other
+    /// operations are ignored.
+    ///
+    /// If the batch would append more leaves than the frontier has room for, an error is
+    /// returned and the frontier is left unmodified.
+    pub fn apply_batch<M>(&mut self, ops: &[BatchOp<H, M>]) -> Result<(), BatchError> {
+        let values = ops.iter().filter_map(|op| match op {
+            BatchOp::Append(value) | BatchOp::AppendMarked(value) => Some(value),
+            BatchOp::RemoveMark(_) | BatchOp::Checkpoint(_) => None,
+        });
+
+        let required = values.clone().count();
+        let available =
+            remaining_capacity(DEPTH.into(), self.frontier.as_ref().map(|f| f.position()));
+        if required > available {
+            return Err(BatchError::InsufficientCapacity {
+                required,
+                available,
+            });
+        }
+
+        for value in values {
+            assert!(self.append(value), "the frontier's capacity was verified");
+        }
+        Ok(())
+    }
+
     /// Obtains the current root of this Merkle frontier by hashing
     /// against empty nodes up to the maximum height of the pruned
     /// tree that the frontier represents.
@@ -311,6 +728,84 @@ impl<H: Hashable + Clone, const DEPTH: u8> Frontier<H, DEPTH> {
     }
 }
 
+#[cfg(feature = "rayon")]
+impl<H: Hashable + Clone + Send + Sync, const DEPTH: u8> Frontier<H, DEPTH> {
+    /// Constructs the frontier of a tree containing the specified leaves, in order. The leaves
+    /// are divided into at most `threads` chunks, each of which is a complete subtree of the
+    /// tree (except possibly the last), and the roots of these subtrees are computed in
+    /// parallel. The result is identical to the frontier obtained by appending each leaf to an
+    /// empty frontier in sequence. Nodes of the complete subtrees are computed a level at a time
+    /// using [`Hashable::combine_batch`].
+    ///
+    /// Returns an error if the leaves would exceed the capacity of a tree of depth `DEPTH`.
+    pub fn build_parallel(values: &[H], threads: usize) -> Result<Self, FrontierError> {
+        use rayon::prelude::*;
+
+        let (last_chunk, full_chunks, chunk_level) = match values.len() {
+            0 => return Ok(Self::empty()),
+            len => {
+                let chunk_size = ((len + threads.max(1) - 1) / threads.max(1)).next_power_of_two();
+                let full_len = (len - 1) / chunk_size * chunk_size;
+                (
+                    &values[full_len..],
+                    &values[..full_len],
+                    chunk_size.trailing_zeros() as usize,
+                )
+            }
+        };
+
+        let chunk_roots: Vec<H> = full_chunks
+            .par_chunks(1 << chunk_level)
+            .map(|chunk| complete_subtree_root(chunk.to_vec(), Level::from(0)))
+            .collect();
+
+        // Because each chunk is aligned to a complete subtree, the ommers of the final chunk's
+        // frontier are exactly the ommers of the full frontier below the chunk level.
+        let mut tail = NonEmptyFrontier::new(last_chunk[0].clone());
+        for value in &last_chunk[1..] {
+            tail.append(value.clone());
+        }
+
+        let position = Position::from(values.len() - 1);
+        let mut ommers = tail.ommers;
+        let chunk_index = usize::from(position) >> chunk_level;
+        for level in chunk_level..(u8::from(position.root_level()) as usize) {
+            let level_index = chunk_index >> (level - chunk_level);
+            if level_index & 0x1 == 1 {
+                // the ommer at this level is the root of the complete subtree to the left of the
+                // subtree containing the final leaf
+                let start = (level_index - 1) << (level - chunk_level);
+                let end = level_index << (level - chunk_level);
+                ommers.push(complete_subtree_root(
+                    chunk_roots[start..end].to_vec(),
+                    Level::from(chunk_level as u8),
+                ));
+            }
+        }
+
+        Self::from_parts(position, tail.leaf, ommers)
+    }
+}
+
+/// Computes the root of the complete subtree having the specified nodes at the given level. The
+/// number of nodes must be a power of two.
+#[cfg(feature = "rayon")]
+fn complete_subtree_root<H: Hashable>(mut nodes: Vec<H>, mut level: Level) -> H {
+    while nodes.len() > 1 {
+        let mut pairs = Vec::with_capacity(nodes.len() / 2);
+        let mut iter = nodes.into_iter();
+        while let (Some(left), Some(right)) = (iter.next(), iter.next()) {
+            pairs.push((left, right));
+        }
+        count!("bridgetree_combines", pairs.len());
+        nodes = H::combine_batch(level, &pairs);
+        level = level + 1;
+    }
+    nodes
+        .pop()
+        .expect("a complete subtree has at least one node")
+}
+
 /// The information required to "update" witnesses from one state of a Merkle tree to another.
 ///
 /// The witness for a particular leaf of a Merkle tree consists of the siblings of that leaf, plus
@@ -434,6 +929,43 @@ impl<H> MerkleBridge<H> {
             end: self.position() + 1,
         }
     }
+
+    /// Returns the approximate amount of memory dynamically allocated for the frontier,
+    /// tracked addresses and ommers of this bridge.
+    fn dynamic_memory_usage(&self) -> usize {
+        self.frontier.ommers.capacity() * size_of::<H>()
+            + self.tracking.len() * size_of::<Address>()
+            + self.ommers.len() * (size_of::<Address>() + size_of::<H>())
+    }
+
+    /// Releases any excess capacity allocated for the frontier of this bridge.
+    fn shrink_to_fit(&mut self) {
+        self.frontier.ommers.shrink_to_fit();
+    }
+}
+
+/// Zeroizes the frontier and ommer values of the bridge. As with [`NonEmptyFrontier`], the
+/// bridge must not be used after it has been zeroized.
+#[cfg(feature = "zeroize")]
+impl<H: Zeroize> Zeroize for MerkleBridge<H> {
+    fn zeroize(&mut self) {
+        self.prior_position = None;
+        self.tracking.clear();
+        self.ommers.values_mut().for_each(Zeroize::zeroize);
+        self.ommers.clear();
+        self.frontier.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H: ZeroizeOnDrop> ZeroizeOnDrop for MerkleBridge<H> {}
+
+impl<H> From<NonEmptyFrontier<H>> for MerkleBridge<H> {
+    /// Constructs a new bridge with no predecessor having the specified frontier as its leading
+    /// edge.
+    fn from(frontier: NonEmptyFrontier<H>) -> Self {
+        Self::from_parts(None, BTreeSet::new(), BTreeMap::new(), frontier)
+    }
 }
 
 impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
@@ -456,6 +988,17 @@ impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
         result
     }
 
+    /// Returns the root of the subtree with its root at the specified level that contains the
+    /// most recently appended leaf, if that leaf completes the subtree. Returns `None` if the
+    /// subtree is incomplete.
+    pub fn root_at_level(&self, level: Level) -> Option<H> {
+        if self.position().is_complete_subtree(level) {
+            Some(self.frontier.root(Some(level)))
+        } else {
+            None
+        }
+    }
+
     fn track_current_leaf(&mut self) {
         self.tracking
             .insert(Address::from(self.frontier.position()).current_incomplete());
@@ -463,6 +1006,11 @@ impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
 
     /// Advances this bridge's frontier by appending the specified node,
     /// and updates any auth path ommers being tracked if necessary.
+    ///
+    /// The tracked addresses of marked leaves converge as their authentication paths are
+    /// completed, so at most one address is tracked at each level of the tree; the cost of
+    /// this operation is therefore bounded by the depth of the tree rather than by the number
+    /// of marked leaves.
     pub fn append(&mut self, value: H) {
         self.frontier.append(value);
 
@@ -500,44 +1048,73 @@ impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
     /// to this bridge. The resulting Bridge will have the same state as though
     /// `self` had had every leaf used to construct `next` appended to it
     /// directly.
-    fn fuse(&self, next: &Self) -> Result<Self, ContinuityError> {
-        self.check_continuity(next)?;
+    ///
+    /// Both bridges are consumed, so that their values are moved rather than cloned into the
+    /// result.
+    fn fuse(mut self, next: Self) -> Result<Self, ContinuityError> {
+        self.check_continuity(&next)?;
 
+        self.ommers.extend(next.ommers);
+        self.frontier.discard();
         Ok(Self {
             prior_position: self.prior_position,
-            tracking: next.tracking.clone(),
-            ommers: self
-                .ommers
-                .iter()
-                .chain(next.ommers.iter())
-                .map(|(k, v)| (*k, v.clone()))
-                .collect(),
-            frontier: next.frontier.clone(),
+            tracking: next.tracking,
+            ommers: self.ommers,
+            frontier: next.frontier,
         })
     }
 
+    /// Drops the bridge, first scrubbing its values if the `zeroize` feature is enabled.
+    fn discard(self) {
+        self.ommers
+            .into_iter()
+            .for_each(|(_, value)| discard(value));
+        self.frontier.discard();
+    }
+
     /// Returns a single MerkleBridge that contains the aggregate information
     /// of all the provided bridges (discarding internal frontiers) or None
     /// if the provided iterator is empty. Returns a continuity error if
     /// any of the bridges are not valid successors to one another.
+    ///
+    /// Each ommer of the provided bridges is cloned once, and only the frontier of the last
+    /// bridge is cloned, rather than cloning the accumulated result for each bridge fused.
     fn fuse_all<T: Iterator<Item = &'a Self>>(
         mut iter: T,
     ) -> Result<Option<Self>, ContinuityError> {
-        let mut fused = iter.next().cloned();
+        let first = match iter.next() {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+
+        let mut ommers = first.ommers.clone();
+        let mut last = first;
         for next in iter {
-            fused = Some(fused.unwrap().fuse(next)?);
+            last.check_continuity(next)?;
+            ommers.extend(next.ommers.iter().map(|(k, v)| (*k, v.clone())));
+            last = next;
         }
-        Ok(fused)
+
+        Ok(Some(Self {
+            prior_position: first.prior_position,
+            tracking: last.tracking.clone(),
+            ommers,
+            frontier: last.frontier.clone(),
+        }))
     }
 
     /// If this bridge contains sufficient auth fragment information, construct an authentication
     /// path for the specified position by interleaving with values from the prior frontier. This
     /// method will panic if the position of the prior frontier does not match this bridge's prior
     /// position.
+    ///
+    /// Nodes are combined using the specified function, which is [`Hashable::combine`] itself
+    /// for the internal consistency checks of the tree so that their hashes are not counted.
     fn witness(
         &self,
         depth: u8,
         prior_frontier: &NonEmptyFrontier<H>,
+        combine: fn(Level, &H, &H) -> H,
     ) -> Result<Vec<H>, WitnessingError> {
         assert!(Some(prior_frontier.position()) == self.prior_position);
 
@@ -546,7 +1123,7 @@ impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
             if self.frontier.position() < r.start {
                 Some(H::empty_root(addr.level()))
             } else if r.contains(&self.frontier.position()) {
-                Some(self.frontier.root(Some(addr.level())))
+                Some(self.frontier.root_with(Some(addr.level()), combine))
             } else {
                 // the frontier's position is after the end of the requested
                 // range, so the requested value should exist in a stored
@@ -556,11 +1133,123 @@ impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
         })
     }
 
+    /// Returns the value of the node at the specified address, if it is complete as of the
+    /// position of this bridge and can be obtained from the bridge's frontier or ommers.
+    fn node_value(&self, addr: Address) -> Option<H> {
+        let position = usize::from(self.frontier.position());
+        let level = u8::from(addr.level());
+        if let Some(value) = self.ommers.get(&addr) {
+            Some(value.clone())
+        } else if position + 1 == usize::from(addr.position_range().end) {
+            Some(self.frontier.root(Some(addr.level())))
+        } else if (position >> level) == addr.index() + 1 {
+            self.frontier.ommer_at(addr.level()).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Prunes away any tracked addresses and ommers that are not required to construct a
+    /// witness for one of the specified marked positions.
+    fn retain_marked<V>(&mut self, marked: &BTreeMap<Position, V>) {
+        let is_needed = |addr: &Address| marked.range(addr.position_range()).next().is_some();
+        self.tracking.retain(|addr| is_needed(addr));
+        self.ommers.retain(|addr, value| {
+            is_needed(&addr.sibling()) || {
+                scrub(value);
+                false
+            }
+        });
+    }
+
     fn retain(&mut self, ommer_addrs: &BTreeSet<Address>) {
         // Prune away any ommers & tracking addresses we don't need
         self.tracking
             .retain(|addr| ommer_addrs.contains(&addr.sibling()));
-        self.ommers.retain(|addr, _| ommer_addrs.contains(addr));
+        self.ommers.retain(|addr, value| {
+            ommer_addrs.contains(addr) || {
+                scrub(value);
+                false
+            }
+        });
+    }
+}
+
+/// A witness to a single leaf that is maintained independently of any tree.
+///
+/// The bundle consists of the frontier of the tree as of the witnessed leaf, and a bridge from
+/// that leaf to the tip of the tree whose ommers are the completed subtree roots to the right of
+/// the witnessed leaf. Appending to the bundle advances the witness as the tree grows, in the
+/// same fashion as the bridges of a [`BridgeTree`] advance the witnesses of its marked leaves,
+/// but without retaining any other part of the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WitnessBundle<H> {
+    frontier: NonEmptyFrontier<H>,
+    bridge: MerkleBridge<H>,
+}
+
+impl<H: Hashable + Ord + Clone> WitnessBundle<H> {
+    /// Constructs a bundle witnessing the most recently appended leaf of the given frontier.
+    pub fn new(frontier: NonEmptyFrontier<H>) -> Self {
+        let mut prior = MerkleBridge::from(frontier.clone());
+        prior.track_current_leaf();
+        WitnessBundle {
+            frontier,
+            bridge: prior.successor(true),
+        }
+    }
+
+    /// Constructs a bundle from the frontier of the tree as of the witnessed leaf and a bridge
+    /// from that leaf to the tip of the tree. Returns `None` if the bridge does not begin at
+    /// the position of the frontier.
+    pub fn from_parts(frontier: NonEmptyFrontier<H>, bridge: MerkleBridge<H>) -> Option<Self> {
+        if bridge.prior_position() == Some(frontier.position()) {
+            Some(WitnessBundle { frontier, bridge })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the position of the witnessed leaf.
+    pub fn position(&self) -> Position {
+        self.frontier.position()
+    }
+
+    /// Returns the witnessed leaf.
+    pub fn leaf(&self) -> &H {
+        self.frontier.leaf()
+    }
+
+    /// Returns the frontier of the tree as of the witnessed leaf.
+    pub fn frontier(&self) -> &NonEmptyFrontier<H> {
+        &self.frontier
+    }
+
+    /// Returns the bridge from the witnessed leaf to the tip of the tree.
+    pub fn bridge(&self) -> &MerkleBridge<H> {
+        &self.bridge
+    }
+
+    /// Returns the frontier of the tree as of its most recently appended leaf.
+    pub fn tip(&self) -> &NonEmptyFrontier<H> {
+        self.bridge.frontier()
+    }
+
+    /// Appends a leaf to the tree, advancing the witness.
+    pub fn append(&mut self, value: H) {
+        self.bridge.append(value);
+    }
+
+    /// Returns the root of the tree of the specified depth as of its most recently appended
+    /// leaf.
+    pub fn root(&self, depth: u8) -> H {
+        self.tip().root(Some(Level::from(depth)))
+    }
+
+    /// Returns the witness to the witnessed leaf in a tree of the specified depth, as of the
+    /// tree's most recently appended leaf.
+    pub fn path(&self, depth: u8) -> Result<Vec<H>, WitnessingError> {
+        self.bridge.witness(depth, &self.frontier, combine)
     }
 }
 
@@ -571,8 +1260,14 @@ impl<'a, H: Hashable + Ord + Clone + 'a> MerkleBridge<H> {
 /// crosses [`MerkleBridge`] boundaries, and so it is not sufficient to just truncate the list of
 /// bridges; instead, we use [`Checkpoint`] values to be able to rapidly restore the cache to its
 /// previous state.
+///
+/// Each checkpoint carries a metadata value of type `M`, which may be used to associate
+/// caller-defined data (for example, a block height and hash) with the checkpointed tree state.
+/// Metadata is discarded along with its checkpoint, and is returned on rewind. Metadata is
+/// optional in the serialized form of a checkpoint, so that checkpoints serialized without
+/// metadata can be deserialized using the default metadata value.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct Checkpoint {
+pub struct Checkpoint<M = ()> {
     /// The number of bridges that will be retained in a rewind.
     bridges_len: usize,
     /// A flag indicating whether or not the current state of the tree
@@ -587,31 +1282,37 @@ pub struct Checkpoint {
     /// checkpoint, we don't need to remember when we forget it because both the mark
     /// creation and removal will be reverted in the rollback.
     forgotten: BTreeMap<Position, usize>,
+    /// The caller-provided metadata associated with this checkpoint.
+    #[serde(default)]
+    meta: M,
 }
 
-impl Checkpoint {
+impl<M> Checkpoint<M> {
     /// Creates a new checkpoint from its constituent parts.
     pub fn from_parts(
         bridges_len: usize,
         is_marked: bool,
         marked: BTreeSet<Position>,
         forgotten: BTreeMap<Position, usize>,
+        meta: M,
     ) -> Self {
         Self {
             bridges_len,
             is_marked,
             marked,
             forgotten,
+            meta,
         }
     }
 
     /// Creates a new empty checkpoint for the specified [`BridgeTree`] state.
-    pub fn at_length(bridges_len: usize, is_marked: bool) -> Self {
+    pub fn at_length(bridges_len: usize, is_marked: bool, meta: M) -> Self {
         Checkpoint {
             bridges_len,
             is_marked,
             marked: BTreeSet::new(),
             forgotten: BTreeMap::new(),
+            meta,
         }
     }
 
@@ -645,6 +1346,11 @@ impl Checkpoint {
         &self.forgotten
     }
 
+    /// Returns the metadata associated with this checkpoint.
+    pub fn meta(&self) -> &M {
+        &self.meta
+    }
+
     // A private convenience method that returns the root of the bridge corresponding to
     // this checkpoint at a specified depth, given the slice of bridges from which this checkpoint
     // was derived.
@@ -659,9 +1365,10 @@ impl Checkpoint {
         }
     }
 
-    // A private convenience method that returns the position of the bridge corresponding
-    // to this checkpoint, if the checkpoint is not for the empty bridge.
-    fn position<H: Ord>(&self, bridges: &[MerkleBridge<H>]) -> Option<Position> {
+    /// Returns the position of the most recently appended leaf as of this checkpoint, given
+    /// the [`BridgeTree::prior_bridges`] of the tree to which this checkpoint belongs. Returns
+    /// `None` if the checkpoint was created when the tree was empty.
+    pub fn position<H>(&self, bridges: &[MerkleBridge<H>]) -> Option<Position> {
         if self.bridges_len == 0 {
             None
         } else {
@@ -669,43 +1376,231 @@ impl Checkpoint {
         }
     }
 
-    // A private method that rewrites the indices of each forgotten marked record
-    // using the specified rewrite function. Used during garbage collection.
+    // A private method that records the removal of the mark at the specified position
+    // during the period that this checkpoint is the current checkpoint, unless that mark
+    // was also created during this period (in which case both the creation and removal of
+    // the mark will be reverted on rewind.) Returns whether the mark will be restored by a
+    // rewind to this checkpoint.
+    fn forget(&mut self, position: Position, bridge_idx: usize) -> bool {
+        if !self.marked.contains(&position) {
+            self.forgotten.insert(position, bridge_idx);
+        }
+        self.restores(&position)
+    }
+
+    // A private method that merges the records of the checkpoint immediately following this one
+    // into this checkpoint, such that a rewind to this checkpoint after `next` has been removed
+    // restores the same marks as rewinding to `next` and then to this checkpoint would have.
+    fn absorb(&mut self, next: Checkpoint<M>) {
+        self.marked.extend(next.marked);
+        for (position, idx) in next.forgotten {
+            self.forgotten.entry(position).or_insert(idx);
+        }
+    }
+
+    // A private method that returns whether a rewind to this checkpoint will restore a mark
+    // at the specified position that has been removed since the checkpoint was created.
+    fn restores(&self, position: &Position) -> bool {
+        self.forgotten
+            .get(position)
+            .map_or(false, |idx| *idx < self.bridges_len)
+    }
+
+    // A private method that rewrites the index of the checkpointed bridge and the indices of
+    // each forgotten marked record using the specified function, which maps the original index
+    // of a bridge to its new index. Used during garbage collection.
     fn rewrite_indices<F: Fn(usize) -> usize>(&mut self, f: F) {
-        self.bridges_len = f(self.bridges_len);
+        if self.bridges_len > 0 {
+            self.bridges_len = f(self.bridges_len - 1) + 1;
+        }
         for v in self.forgotten.values_mut() {
             *v = f(*v)
         }
     }
 }
 
-/// A sparse representation of a Merkle tree with linear appending of leaves that contains enough
-/// information to produce a witness for any `mark`ed leaf.
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct BridgeTree<H, const DEPTH: u8> {
-    /// The ordered list of Merkle bridges representing the history
-    /// of the tree. There will be one bridge for each saved leaf.
-    prior_bridges: Vec<MerkleBridge<H>>,
-    /// The current (mutable) bridge at the tip of the tree.
-    current_bridge: Option<MerkleBridge<H>>,
-    /// A map from positions for which we wish to be able to compute a
-    /// witness to index in the bridges vector.
-    saved: BTreeMap<Position, usize>,
-    /// A stack of bridge indices to which it's possible to rewind directly.
-    checkpoints: Vec<Checkpoint>,
-    /// The maximum number of checkpoints to retain. If this number is
-    /// exceeded, the oldest checkpoint will be dropped when creating
-    /// a new checkpoint.
-    max_checkpoints: usize,
+/// A source of the depth of a [`DepthBridgeTree`].
+///
+/// The depth of a tree may either be fixed at compile time using [`ConstDepth`], or be supplied
+/// at runtime by using a [`Level`] value directly.
+pub trait Depth: Copy {
+    /// Returns the level of the root of the tree.
+    fn level(&self) -> Level;
+
+    /// Returns the depth that is determined by the type itself, if any. This is the depth
+    /// assumed when deserializing a tree that was serialized before the depth of the tree was
+    /// included in its serialized form.
+    fn implied() -> Option<Self> {
+        None
+    }
 }
 
-impl<H: Hashable + Ord + Debug, const DEPTH: u8> Debug for BridgeTree<H, DEPTH> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "BridgeTree {{\n  depth: {:?},\n  prior_bridges: {:?},\n  current_bridge: {:?},\n  saved: {:?},\n  checkpoints: {:?},\n  max_checkpoints: {:?}\n}}",
-            DEPTH, self.prior_bridges, self.current_bridge, self.saved, self.checkpoints, self.max_checkpoints
-        )
+/// A zero-sized [`Depth`] determined by a const generic parameter.
+///
+/// The depth is included in the serialized form of this type, and deserialization will fail if
+/// the serialized depth does not match `DEPTH`. When a [`Frontier`] or [`BridgeTree`] serialized
+/// without its depth is deserialized, the depth is taken to be `DEPTH`; this is possible only in
+/// self-describing formats such as JSON, as a format that encodes fields by position, such as
+/// `bincode`, instead reads the first byte of the data as the depth.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConstDepth<const DEPTH: u8>;
+
+impl<const DEPTH: u8> Depth for ConstDepth<DEPTH> {
+    fn level(&self) -> Level {
+        Level::from(DEPTH)
+    }
+
+    fn implied() -> Option<Self> {
+        Some(ConstDepth)
+    }
+}
+
+impl<const DEPTH: u8> Serialize for ConstDepth<DEPTH> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(DEPTH)
+    }
+}
+
+impl<'de, const DEPTH: u8> Deserialize<'de> for ConstDepth<DEPTH> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let depth = u8::deserialize(deserializer)?;
+        if depth == DEPTH {
+            Ok(ConstDepth)
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "tree depth mismatch: expected {}, found {}",
+                DEPTH, depth
+            )))
+        }
+    }
+}
+
+impl Depth for Level {
+    fn level(&self) -> Level {
+        *self
+    }
+}
+
+/// A sparse representation of a Merkle tree with linear appending of leaves that contains enough
+/// information to produce a witness for any `mark`ed leaf.
+///
+/// This type is generic over the source of the tree's depth; most users will want to use either
+/// the [`BridgeTree`] alias, for trees whose depth is known at compile time, or the
+/// [`DynBridgeTree`] alias, for trees whose depth is a runtime parameter.
+///
+/// The tree is also generic over the type `M` of metadata associated with each checkpoint. The
+/// [`BridgeTree`] alias always uses `()` as its checkpoint metadata type; trees with a depth that
+/// is known at compile time and other checkpoint metadata can be named as
+/// `DepthBridgeTree<H, ConstDepth<DEPTH>, M>`.
+///
+/// Similarly, the tree is generic over the type `W` of metadata that may be associated with each
+/// marked position using [`BridgeTree::mark_with`].
+///
+/// Deserialization checks the internal consistency of the tree in the same fashion as
+/// [`BridgeTree::from_parts`], and fails with a description of the first violated invariant.
+/// A tree serialized before its depth was included in its serialized form may be deserialized
+/// only if the depth is determined by its type, as for [`BridgeTree`], and only from a
+/// self-describing format such as JSON; see [`ConstDepth`].
+#[derive(Clone, PartialEq, Eq, Serialize)]
+pub struct DepthBridgeTree<H, D, M = (), W = ()> {
+    /// The depth of the tree.
+    depth: D,
+    /// The ordered list of Merkle bridges representing the history
+    /// of the tree. There will be one bridge for each saved leaf.
+    prior_bridges: Vec<MerkleBridge<H>>,
+    /// The current (mutable) bridge at the tip of the tree.
+    current_bridge: Option<MerkleBridge<H>>,
+    /// A map from positions for which we wish to be able to compute a
+    /// witness to index in the bridges vector.
+    saved: BTreeMap<Position, usize>,
+    /// A map from marked positions to the metadata associated with those marks. This may also
+    /// contain the metadata for marks that have been removed since the oldest checkpoint, so
+    /// that the metadata can be restored along with the mark on rewind.
+    mark_meta: BTreeMap<Position, W>,
+    /// A stack of bridge indices to which it's possible to rewind directly.
+    checkpoints: Vec<Checkpoint<M>>,
+    /// The maximum number of checkpoints to retain. If this number is
+    /// exceeded, the oldest checkpoint will be dropped when creating
+    /// a new checkpoint. If this number is zero, checkpointing is disabled.
+    max_checkpoints: usize,
+    /// An index from the leaf values at marked positions to those positions. This is derived
+    /// from `saved` and the bridges of the tree, and so is not serialized. Each key is a clone
+    /// of a marked leaf, so every marked leaf value is held twice: once in the frontier of the
+    /// bridge that ends at its position, and once here.
+    #[serde(skip)]
+    marked_values: BTreeMap<H, BTreeSet<Position>>,
+}
+
+/// The unvalidated serialized form of a [`DepthBridgeTree`].
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "H: Deserialize<'de>, \
+                             D: Deserialize<'de>, \
+                             M: Deserialize<'de> + Default, \
+                             W: Deserialize<'de>"))]
+struct BridgeTreeFields<H, D, M, W> {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    depth: Option<D>,
+    prior_bridges: Vec<MerkleBridge<H>>,
+    current_bridge: Option<MerkleBridge<H>>,
+    saved: BTreeMap<Position, usize>,
+    #[serde(default)]
+    mark_meta: BTreeMap<Position, W>,
+    checkpoints: Vec<Checkpoint<M>>,
+    max_checkpoints: usize,
+}
+
+fn deserialize_some<'de, T: Deserialize<'de>, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+impl<'de, H, D, M, W> Deserialize<'de> for DepthBridgeTree<H, D, M, W>
+where
+    H: Deserialize<'de> + Hashable + Ord + Clone,
+    D: Deserialize<'de> + Depth,
+    M: Deserialize<'de> + Default,
+    W: Deserialize<'de>,
+{
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let fields = BridgeTreeFields::<H, D, M, W>::deserialize(deserializer)?;
+        let depth = fields
+            .depth
+            .or_else(D::implied)
+            .ok_or_else(|| serde::de::Error::missing_field("depth"))?;
+        Self::from_parts_internal(
+            depth,
+            fields.prior_bridges,
+            fields.current_bridge,
+            fields.saved,
+            fields.mark_meta,
+            fields.checkpoints,
+            fields.max_checkpoints,
+        )
+        .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A [`DepthBridgeTree`] whose depth is fixed at compile time.
+///
+/// A tree of depth `DEPTH` holds `2^DEPTH` leaves. All depths are supported, including a depth
+/// of zero, in which case the tree holds a single leaf that is also its root.
+pub type BridgeTree<H, const DEPTH: u8> = DepthBridgeTree<H, ConstDepth<DEPTH>>;
+
+/// A [`DepthBridgeTree`] whose depth is supplied at runtime.
+pub type DynBridgeTree<H, M = (), W = ()> = DepthBridgeTree<H, Level, M, W>;
+
+impl<H: Debug, D: Depth, M: Debug, W: Debug> Debug for DepthBridgeTree<H, D, M, W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        f.debug_struct("BridgeTree")
+            .field("depth", &u8::from(self.depth.level()))
+            .field("prior_bridges", &self.prior_bridges)
+            .field("current_bridge", &self.current_bridge)
+            .field("saved", &self.saved)
+            .field("mark_meta", &self.mark_meta)
+            .field("checkpoints", &self.checkpoints)
+            .field("max_checkpoints", &self.max_checkpoints)
+            .finish()
     }
 }
 
@@ -715,85 +1610,701 @@ impl<H: Hashable + Ord + Debug, const DEPTH: u8> Debug for BridgeTree<H, DEPTH>
 pub enum BridgeTreeError {
     IncorrectIncompleteIndex,
     InvalidMarkIndex(usize),
-    PositionMismatch { expected: Position, found: Position },
+    PositionMismatch {
+        expected: Position,
+        found: Position,
+    },
     InvalidSavePoints,
     Discontinuity(ContinuityError),
     CheckpointMismatch,
+    DepthMismatch {
+        expected: Level,
+        found: Level,
+    },
+    MarkMetaMismatch(Position),
+    /// The checkpoint at the specified index retains fewer bridges than the checkpoint that
+    /// precedes it.
+    CheckpointsOutOfOrder(usize),
+    /// The bridge at the specified index, where an index equal to the number of prior bridges
+    /// denotes the current bridge, holds an ommer at an address whose subtree was not completed
+    /// by a leaf appended to that bridge.
+    InvalidOmmer {
+        bridge: usize,
+        address: Address,
+    },
+    /// A leaf has been appended at the specified position, which exceeds the capacity of the
+    /// tree.
+    DepthExceeded(Position),
+    /// The frontier of the bridge at the specified index, where an index equal to the number of
+    /// prior bridges denotes the current bridge, holds a number of ommers that is inconsistent
+    /// with its position.
+    InvalidFrontier(usize),
+}
+
+impl core::fmt::Display for BridgeTreeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BridgeTreeError::IncorrectIncompleteIndex => write!(f, "incorrect incomplete index"),
+            BridgeTreeError::InvalidMarkIndex(i) => {
+                write!(f, "a mark refers to bridge {}, which does not exist", i)
+            }
+            BridgeTreeError::PositionMismatch { expected, found } => write!(
+                f,
+                "the mark at {:?} refers to a bridge ending at {:?}",
+                expected, found
+            ),
+            BridgeTreeError::InvalidSavePoints => write!(f, "invalid save points"),
+            BridgeTreeError::Discontinuity(e) => write!(f, "bridges are discontinuous: {:?}", e),
+            BridgeTreeError::CheckpointMismatch => write!(
+                f,
+                "checkpoints exceed the maximum number of checkpoints or refer to bridges that \
+                 do not exist"
+            ),
+            BridgeTreeError::DepthMismatch { expected, found } => write!(
+                f,
+                "expected a tree of depth {}, found depth {}",
+                u8::from(*expected),
+                u8::from(*found)
+            ),
+            BridgeTreeError::MarkMetaMismatch(pos) => write!(
+                f,
+                "mark metadata exists for {:?}, which is not marked and cannot be restored",
+                pos
+            ),
+            BridgeTreeError::CheckpointsOutOfOrder(i) => write!(
+                f,
+                "checkpoint {} retains fewer bridges than the checkpoint before it",
+                i
+            ),
+            BridgeTreeError::InvalidOmmer { bridge, address } => write!(
+                f,
+                "bridge {} holds an ommer at {:?} that it did not complete",
+                bridge, address
+            ),
+            BridgeTreeError::DepthExceeded(pos) => {
+                write!(f, "the leaf at {:?} exceeds the capacity of the tree", pos)
+            }
+            BridgeTreeError::InvalidFrontier(i) => write!(
+                f,
+                "the frontier of bridge {} has the wrong number of ommers for its position",
+                i
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BridgeTreeError {}
+
+/// The result of marking the most recently appended leaf of a [`BridgeTree`], as returned by
+/// [`BridgeTree::mark_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkStatus {
+    /// The leaf at the specified position was not previously marked, and has been marked.
+    New(Position),
+    /// The leaf at the specified position was already marked.
+    AlreadyMarked(Position),
+    /// The tree is empty, and so there is no leaf to mark.
+    EmptyTree,
+}
+
+impl MarkStatus {
+    /// Returns the position of the marked leaf, or `None` if the tree is empty.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            MarkStatus::New(pos) | MarkStatus::AlreadyMarked(pos) => Some(*pos),
+            MarkStatus::EmptyTree => None,
+        }
+    }
+}
+
+/// Errors that can occur when appending a leaf to a [`BridgeTree`] with
+/// [`BridgeTree::append_info`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppendError {
+    /// The tree is full, and so no further leaves may be appended.
+    TreeFull,
+}
+
+/// A description of the changes made to a [`BridgeTree`] by a successful append, as returned by
+/// [`BridgeTree::append_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppendInfo {
+    position: Position,
+    new_bridge: bool,
+    is_full: bool,
+}
+
+impl AppendInfo {
+    /// Returns the position at which the leaf was appended.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Returns whether a new bridge was created to hold the leaf, which is the case only for the
+    /// first leaf appended to the tree; bridges are otherwise created by marking and
+    /// checkpointing rather than by appending.
+    pub fn new_bridge(&self) -> bool {
+        self.new_bridge
+    }
+
+    /// Returns whether the tree became full as a result of the append, such that no further
+    /// leaves may be appended.
+    pub fn is_full(&self) -> bool {
+        self.is_full
+    }
+}
+
+/// Errors that can occur when attempting to rewind a [`BridgeTree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewindError {
+    /// The tree has no checkpoints to which it could be rewound.
+    NoCheckpoints,
+    /// None of the tree's checkpoints has the requested root.
+    RootNotFound,
+}
+
+/// Errors that can occur when replaying leaves onto a [`BridgeTree`] with
+/// [`BridgeTree::replay_from`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The tree has no checkpoint at the specified position to which it could be rewound.
+    CheckpointNotFound(Position),
+    /// The replayed leaves would exceed the capacity of the tree.
+    InsufficientCapacity { required: usize, available: usize },
+}
+
+/// A description of the changes made to the state of a [`BridgeTree`] by a successful rewind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewindResult<M = ()> {
+    new_position: Option<Position>,
+    leaves_dropped: u64,
+    marks_dropped: Vec<Position>,
+    meta: M,
+}
+
+impl<M> RewindResult<M> {
+    /// Returns the position of the most recently appended leaf after the rewind, or `None` if
+    /// the tree is empty after the rewind.
+    pub fn new_position(&self) -> Option<Position> {
+        self.new_position
+    }
+
+    /// Returns the number of leaves that were removed from the tree by the rewind.
+    pub fn leaves_dropped(&self) -> u64 {
+        self.leaves_dropped
+    }
+
+    /// Returns the positions, in ascending order, of marks that were removed from the tree
+    /// because they were created after the checkpoint to which the tree was rewound.
+    pub fn marks_dropped(&self) -> &[Position] {
+        &self.marks_dropped
+    }
+
+    /// Returns the metadata of the checkpoint that was removed by the rewind.
+    pub fn meta(&self) -> &M {
+        &self.meta
+    }
+
+    /// Consumes this result, returning the metadata of the checkpoint that was removed by the
+    /// rewind.
+    pub fn into_meta(self) -> M {
+        self.meta
+    }
+}
+
+/// An operation to be applied to a [`BridgeTree`] as part of a batch by
+/// [`BridgeTree::apply_batch`].
+///
+/// Batches of operations may be serialized, so that a batch observed by one process can be
+/// replayed onto a tree maintained by another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BatchOp<H, M = ()> {
+    /// Appends the leaf to the tree.
+    Append(H),
+    /// Appends the leaf to the tree and marks it.
+    AppendMarked(H),
+    /// Removes the mark at the specified position, which must either have been marked before the
+    /// batch was applied or have been appended and marked earlier in the batch.
+    RemoveMark(Position),
+    /// Creates a checkpoint with the specified metadata.
+    Checkpoint(M),
+}
+
+/// Errors that can occur when validating a batch of operations to be applied to a
+/// [`BridgeTree`] by [`BridgeTree::apply_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchError {
+    /// The batch appends more leaves than the tree has room for.
+    InsufficientCapacity { required: usize, available: usize },
+    /// The batch creates a checkpoint, but checkpointing is disabled for the tree.
+    CheckpointingDisabled,
+    /// The batch removes the mark at the specified position, but the position is not marked
+    /// at that point in the batch.
+    NotMarked(Position),
+    /// The root of the tree once the batch had been applied would not be the expected root.
+    RootMismatch,
+    /// The batch was produced against a tree whose most recently appended leaf was at the
+    /// `expected` position, but the most recently appended leaf of this tree is at the `found`
+    /// position; `None` denotes an empty tree.
+    PositionMismatch {
+        expected: Option<Position>,
+        found: Option<Position>,
+    },
+}
+
+/// A description of the changes made to a [`BridgeTree`] by a successful call to
+/// [`BridgeTree::apply_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchReceipt {
+    appended: Vec<Position>,
+    marked: Vec<Position>,
+    checkpoints: usize,
+    start_position: Option<Position>,
+    position: Option<Position>,
+}
+
+impl BatchReceipt {
+    /// Returns the positions of the leaves appended by the batch, in the order in which they
+    /// were appended.
+    pub fn appended(&self) -> &[Position] {
+        &self.appended
+    }
+
+    /// Returns the positions of the leaves that were appended and marked by the batch.
+    pub fn marked(&self) -> &[Position] {
+        &self.marked
+    }
+
+    /// Returns the number of checkpoints created by the batch.
+    pub fn checkpoints(&self) -> usize {
+        self.checkpoints
+    }
+
+    /// Returns the position of the most recently appended leaf of the tree once the batch had
+    /// been applied, or `None` if the tree is empty. Together with the root of the tree, this may
+    /// be used to check that replaying a batch produced the same tree state as the original.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// Returns the position of the most recently appended leaf of the tree before the batch was
+    /// applied, or `None` if the tree was empty. A tree that follows the tree to which the batch
+    /// was applied may pass this to [`BridgeTree::apply_batch_at`] to apply the same batch.
+    pub fn start_position(&self) -> Option<Position> {
+        self.start_position
+    }
+
+    /// Returns whether the batch appended no leaves, in which case the start position and the
+    /// position of the tree once the batch had been applied are the same.
+    pub fn is_empty(&self) -> bool {
+        self.appended.is_empty()
+    }
+}
+
+/// The outcome of a successful call to [`BridgeTree::apply_batch_at`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchStatus {
+    /// The batch was applied to the tree, making the described changes.
+    Applied(BatchReceipt),
+    /// The leaves appended by the batch had already been appended to the tree from the same
+    /// position, and so the tree was left unmodified.
+    AlreadyApplied,
+}
+
+/// An estimate of the state that would be discarded by [`BridgeTree::garbage_collect`], as
+/// returned by [`BridgeTree::gc_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    bridges: usize,
+    bytes: usize,
+    index_bytes: usize,
+}
+
+impl GcStats {
+    /// Returns the number of bridges that would be fused away by garbage collection.
+    pub fn bridges(&self) -> usize {
+        self.bridges
+    }
+
+    /// Returns the approximate amount of memory, in bytes, occupied by the bridges that would be
+    /// fused away by garbage collection.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the approximate amount of memory, in bytes, occupied by the index of marked leaf
+    /// values used by [`BridgeTree::positions_of`]. The index holds a copy of each marked leaf,
+    /// and is not reduced by garbage collection.
+    pub fn index_bytes(&self) -> usize {
+        self.index_bytes
+    }
+}
+
+/// The state of the tree at a checkpoint that has been removed from a [`BridgeTree`] by
+/// [`BridgeTree::drain_checkpoints`] or evicted by [`BridgeTree::checkpoint_with_archive`],
+/// which may be archived to detect reorganizations that are deeper than the tree can rewind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedCheckpoint<H, M = ()> {
+    position: Option<Position>,
+    root: H,
+    meta: M,
+}
+
+impl<H, M> ArchivedCheckpoint<H, M> {
+    /// Returns the position of the most recently appended leaf as of the checkpoint, or `None`
+    /// if the tree was empty when the checkpoint was created.
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
+    /// Returns the root of the tree as of the checkpoint.
+    pub fn root(&self) -> &H {
+        &self.root
+    }
+
+    /// Returns the metadata associated with the checkpoint.
+    pub fn meta(&self) -> &M {
+        &self.meta
+    }
+}
+
+/// The constituent parts of a [`BridgeTree`], as returned by [`BridgeTree::into_parts`].
+///
+/// These correspond to the arguments of [`BridgeTree::from_parts`], which may be used to
+/// reconstruct the tree after the parts have been persisted separately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BridgeTreeParts<H, M = (), W = ()> {
+    /// The bridges prior to the current bridge; see [`BridgeTree::prior_bridges`].
+    pub prior_bridges: Vec<MerkleBridge<H>>,
+    /// The bridge at the tip of the tree; see [`BridgeTree::current_bridge`].
+    pub current_bridge: Option<MerkleBridge<H>>,
+    /// The indices of the bridges for marked positions; see [`BridgeTree::marked_indices`].
+    pub saved: BTreeMap<Position, usize>,
+    /// The metadata associated with marks; see [`BridgeTree::marked_meta`].
+    pub mark_meta: BTreeMap<Position, W>,
+    /// The checkpoints of the tree; see [`BridgeTree::checkpoints`].
+    pub checkpoints: Vec<Checkpoint<M>>,
+    /// The maximum number of checkpoints to retain; see [`BridgeTree::max_checkpoints`].
+    pub max_checkpoints: usize,
 }
 
-impl<H, const DEPTH: u8> BridgeTree<H, DEPTH> {
+impl<H, M, W, const DEPTH: u8> DepthBridgeTree<H, ConstDepth<DEPTH>, M, W> {
     /// Construct an empty BridgeTree value with the specified maximum number of checkpoints.
+    ///
+    /// A `max_checkpoints` value of zero disables checkpointing: [`BridgeTree::checkpoint`] will
+    /// return `false` without modifying the tree, and the tree can never be rewound.
     pub fn new(max_checkpoints: usize) -> Self {
+        Self::empty(ConstDepth, max_checkpoints)
+    }
+
+    /// Construct an empty BridgeTree value with the specified maximum number of checkpoints,
+    /// with space preallocated for the specified number of bridges.
+    ///
+    /// A bridge is added to the tree each time a leaf is marked or a checkpoint is created at
+    /// a new position, so `bridges_capacity` should be an estimate of the number of marks and
+    /// checkpoints the tree will accumulate; this avoids repeated reallocation of the tree's
+    /// internal storage during an initial sync.
+    pub fn with_capacity(max_checkpoints: usize, bridges_capacity: usize) -> Self {
+        Self::empty_with_capacity(ConstDepth, max_checkpoints, bridges_capacity)
+    }
+}
+
+impl<H, M, W> DynBridgeTree<H, M, W> {
+    /// Construct an empty tree of the specified depth, with the specified maximum number of
+    /// checkpoints.
+    pub fn new(depth: Level, max_checkpoints: usize) -> Self {
+        Self::empty(depth, max_checkpoints)
+    }
+
+    /// Construct an empty tree of the specified depth, with the specified maximum number of
+    /// checkpoints and with space preallocated for the specified number of bridges. See
+    /// [`BridgeTree::with_capacity`] for details.
+    pub fn with_capacity(depth: Level, max_checkpoints: usize, bridges_capacity: usize) -> Self {
+        Self::empty_with_capacity(depth, max_checkpoints, bridges_capacity)
+    }
+}
+
+/// Zeroizes the leaf and node values of the tree, leaving the tree empty and without marks or
+/// checkpoints. The tree's depth and maximum number of checkpoints are retained.
+#[cfg(feature = "zeroize")]
+impl<H: Zeroize, D, M, W> Zeroize for DepthBridgeTree<H, D, M, W> {
+    fn zeroize(&mut self) {
+        self.prior_bridges.zeroize();
+        self.current_bridge.zeroize();
+        self.saved.clear();
+        self.mark_meta.clear();
+        self.checkpoints.clear();
+        for (mut value, _) in core::mem::take(&mut self.marked_values) {
+            value.zeroize();
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<H: ZeroizeOnDrop, D, M, W> ZeroizeOnDrop for DepthBridgeTree<H, D, M, W> {}
+
+impl<H, M, W, const DEPTH: u8> From<DepthBridgeTree<H, ConstDepth<DEPTH>, M, W>>
+    for DynBridgeTree<H, M, W>
+{
+    fn from(tree: DepthBridgeTree<H, ConstDepth<DEPTH>, M, W>) -> Self {
+        tree.with_depth(Level::from(DEPTH))
+    }
+}
+
+impl<H, M, W, const DEPTH: u8> TryFrom<DynBridgeTree<H, M, W>>
+    for DepthBridgeTree<H, ConstDepth<DEPTH>, M, W>
+{
+    type Error = BridgeTreeError;
+
+    fn try_from(tree: DynBridgeTree<H, M, W>) -> Result<Self, BridgeTreeError> {
+        if tree.depth == Level::from(DEPTH) {
+            Ok(tree.with_depth(ConstDepth))
+        } else {
+            Err(BridgeTreeError::DepthMismatch {
+                expected: Level::from(DEPTH),
+                found: tree.depth,
+            })
+        }
+    }
+}
+
+impl<H, D: Depth, M, W> DepthBridgeTree<H, D, M, W> {
+    fn empty(depth: D, max_checkpoints: usize) -> Self {
+        Self::empty_with_capacity(depth, max_checkpoints, 0)
+    }
+
+    fn empty_with_capacity(depth: D, max_checkpoints: usize, bridges_capacity: usize) -> Self {
         Self {
-            prior_bridges: vec![],
+            depth,
+            prior_bridges: Vec::with_capacity(bridges_capacity),
             current_bridge: None,
             saved: BTreeMap::new(),
+            mark_meta: BTreeMap::new(),
             checkpoints: vec![],
             max_checkpoints,
+            marked_values: BTreeMap::new(),
+        }
+    }
+
+    fn with_depth<E: Depth>(self, depth: E) -> DepthBridgeTree<H, E, M, W> {
+        DepthBridgeTree {
+            depth,
+            prior_bridges: self.prior_bridges,
+            current_bridge: self.current_bridge,
+            saved: self.saved,
+            mark_meta: self.mark_meta,
+            checkpoints: self.checkpoints,
+            max_checkpoints: self.max_checkpoints,
+            marked_values: self.marked_values,
         }
     }
 
+    /// Returns the level of the root of this tree.
+    pub fn depth(&self) -> Level {
+        self.depth.level()
+    }
+
     /// Removes the oldest checkpoint. Returns true if successful and false if
     /// there are no checkpoints.
     fn drop_oldest_checkpoint(&mut self) -> bool {
         if self.checkpoints.is_empty() {
             false
         } else {
-            self.checkpoints.remove(0);
+            let c = self.checkpoints.remove(0);
+            if !c.forgotten.is_empty() {
+                self.retain_restorable_mark_meta();
+            }
+            count!("bridgetree_checkpoints_dropped");
             true
         }
     }
 
-    /// Returns the prior bridges that make up this tree
+    // Returns whether the specified position is currently marked, or may have its mark restored
+    // by a rewind to one of the specified checkpoints.
+    fn is_mark_restorable(
+        saved: &BTreeMap<Position, usize>,
+        checkpoints: &[Checkpoint<M>],
+        position: &Position,
+    ) -> bool {
+        saved.contains_key(position) || checkpoints.iter().any(|c| c.restores(position))
+    }
+
+    // Discards the metadata for marks that have been removed and can no longer be restored by
+    // a rewind.
+    fn retain_restorable_mark_meta(&mut self) {
+        let saved = &self.saved;
+        let checkpoints = &self.checkpoints;
+        self.mark_meta
+            .retain(|pos, _| Self::is_mark_restorable(saved, checkpoints, pos));
+    }
+
+    /// Returns the prior bridges that make up this tree.
+    ///
+    /// Together with [`current_bridge`], [`marked_indices`], [`checkpoints`] and
+    /// [`max_checkpoints`], this provides a read-only view of the complete state of the tree;
+    /// a tree equal to this one can be reconstructed from these values using `from_parts`.
+    /// Bridge indices stored in the marked indices and checkpoints refer to positions in this
+    /// slice. New bridges are only ever appended to the end of this slice, except that
+    /// [`rewind`] may truncate it and [`garbage_collect`] and [`prune_to_marks`] may merge
+    /// bridges and renumber the stored indices, so persistence layers that store bridges
+    /// incrementally must account for these operations.
+    ///
+    /// [`current_bridge`]: DepthBridgeTree::current_bridge
+    /// [`marked_indices`]: DepthBridgeTree::marked_indices
+    /// [`checkpoints`]: DepthBridgeTree::checkpoints
+    /// [`max_checkpoints`]: DepthBridgeTree::max_checkpoints
+    /// [`rewind`]: DepthBridgeTree::rewind
+    /// [`garbage_collect`]: DepthBridgeTree::garbage_collect
+    /// [`prune_to_marks`]: DepthBridgeTree::prune_to_marks
     pub fn prior_bridges(&self) -> &[MerkleBridge<H>] {
         &self.prior_bridges
     }
 
-    /// Returns the current bridge at the tip of this tree
+    /// Returns the current bridge at the tip of this tree.
+    ///
+    /// Leaves may be appended to a copy of this bridge elsewhere, for example on a worker
+    /// thread, and the extended bridge substituted for it by way of
+    /// [`BridgeTree::into_parts`] and [`BridgeTree::from_parts`]; the latter verifies that the
+    /// substituted bridge is continuous with the tree's prior bridges.
     pub fn current_bridge(&self) -> &Option<MerkleBridge<H>> {
         &self.current_bridge
     }
 
     /// Returns the map from leaf positions that have been marked to the index of
-    /// the bridge whose tip is at that position in this tree's list of bridges.
+    /// the bridge whose tip is at that position in this tree's list of prior bridges.
     pub fn marked_indices(&self) -> &BTreeMap<Position, usize> {
         &self.saved
     }
 
+    /// Returns the map from marked positions to the metadata associated with those marks by
+    /// [`BridgeTree::mark_with`]. In addition to the metadata for current marks, this contains
+    /// the metadata for marks that have been removed but may be restored by a rewind.
+    pub fn marked_meta(&self) -> &BTreeMap<Position, W> {
+        &self.mark_meta
+    }
+
+    /// Returns the metadata associated with the mark at the specified position, or `None` if
+    /// the position is not marked or if no metadata was provided when the mark was created.
+    pub fn mark_meta(&self, position: Position) -> Option<&W> {
+        if self.saved.contains_key(&position) {
+            self.mark_meta.get(&position)
+        } else {
+            None
+        }
+    }
+
     /// Returns the checkpoints to which this tree may be rewound.
-    pub fn checkpoints(&self) -> &[Checkpoint] {
+    pub fn checkpoints(&self) -> &[Checkpoint<M>] {
         &self.checkpoints
     }
 
     /// Returns the maximum number of checkpoints that will be maintained
     /// by the data structure. When this number of checkpoints is exceeded,
     /// the oldest checkpoints are discarded when creating new checkpoints.
+    /// A value of zero indicates that checkpointing is disabled.
     pub fn max_checkpoints(&self) -> usize {
         self.max_checkpoints
     }
 
-    /// Returns the bridge's frontier.
+    /// Returns a reference to the frontier at the tip of the tree, or `None` if the tree is
+    /// empty.
+    ///
+    /// The frontier is borrowed from the current bridge, so this may be used to cheaply compute
+    /// the root of the tree or to construct a witness for the most recently appended leaf
+    /// without cloning any of the tree's state.
     pub fn frontier(&self) -> Option<&NonEmptyFrontier<H>> {
         self.current_bridge.as_ref().map(|b| b.frontier())
     }
+
+    /// Returns the approximate amount of memory dynamically allocated for the tree's bridges,
+    /// marks and checkpoints, including any excess capacity that has been allocated but is not
+    /// in use, and including the index of marked leaf values used by
+    /// [`BridgeTree::positions_of`]. Memory allocated for mark and checkpoint metadata is not
+    /// included.
+    pub fn dynamic_memory_usage(&self) -> usize {
+        let bridges = self.prior_bridges.capacity() * size_of::<MerkleBridge<H>>()
+            + self
+                .prior_bridges
+                .iter()
+                .chain(&self.current_bridge)
+                .map(|b| b.dynamic_memory_usage())
+                .sum::<usize>();
+        let marks = (self.saved.len() + self.mark_meta.len())
+            * (size_of::<Position>() + size_of::<usize>())
+            + self.marked_values_memory_usage();
+        let checkpoints = self.checkpoints.capacity() * size_of::<Checkpoint<M>>()
+            + self
+                .checkpoints
+                .iter()
+                .map(|c| {
+                    c.marked.len() * size_of::<Position>()
+                        + c.forgotten.len() * (size_of::<Position>() + size_of::<usize>())
+                })
+                .sum::<usize>();
+        bridges + marks + checkpoints
+    }
+
+    /// Returns the approximate amount of memory occupied by the index of marked leaf values.
+    fn marked_values_memory_usage(&self) -> usize {
+        self.marked_values
+            .values()
+            .map(|positions| size_of::<H>() + positions.len() * size_of::<Position>())
+            .sum()
+    }
+
+    /// Reserves capacity for at least `additional` more bridges to be added to the tree without
+    /// reallocation of the tree's internal storage.
+    ///
+    /// A bridge is added to the tree each time a leaf is marked or a checkpoint is created at a
+    /// new position; as with [`BridgeTree::with_capacity`], reserving space for the expected
+    /// number of such bridges before marking a large number of leaves avoids repeated
+    /// reallocation and copying of the existing bridges. Marked positions are stored in an
+    /// ordered map, for which no space can be reserved.
+    pub fn reserve(&mut self, additional: usize) {
+        self.prior_bridges.reserve(additional);
+    }
+
+    /// Releases any excess capacity allocated for the tree's bridges and checkpoints, such as
+    /// that which remains after a rewind or garbage collection has discarded a large number of
+    /// bridges.
+    pub fn shrink_to_fit(&mut self) {
+        self.prior_bridges.shrink_to_fit();
+        for b in self
+            .prior_bridges
+            .iter_mut()
+            .chain(&mut self.current_bridge)
+        {
+            b.shrink_to_fit();
+        }
+        self.checkpoints.shrink_to_fit();
+    }
+
+    /// Decomposes the tree into its constituent parts, so that they may be persisted
+    /// separately. The depth of the tree is not included.
+    pub fn into_parts(self) -> BridgeTreeParts<H, M, W> {
+        BridgeTreeParts {
+            prior_bridges: self.prior_bridges,
+            current_bridge: self.current_bridge,
+            saved: self.saved,
+            mark_meta: self.mark_meta,
+            checkpoints: self.checkpoints,
+            max_checkpoints: self.max_checkpoints,
+        }
+    }
 }
 
-impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
+impl<H: Hashable + Ord + Clone, M, W, const DEPTH: u8> DepthBridgeTree<H, ConstDepth<DEPTH>, M, W> {
     /// Construct a new BridgeTree that will start recording changes from the state of
     /// the specified frontier.
     pub fn from_frontier(max_checkpoints: usize, frontier: NonEmptyFrontier<H>) -> Self {
-        Self {
-            prior_bridges: vec![],
-            current_bridge: Some(MerkleBridge::from_parts(
-                None,
-                BTreeSet::new(),
-                BTreeMap::new(),
-                frontier,
-            )),
-            saved: BTreeMap::new(),
-            checkpoints: vec![],
-            max_checkpoints,
-        }
+        Self::from_frontier_internal(ConstDepth, max_checkpoints, frontier)
+    }
+
+    /// Construct a new BridgeTree that will start recording changes from the tip of the
+    /// specified witness bundle, with the bundle's witnessed leaf marked.
+    pub fn from_witness_bundle(max_checkpoints: usize, bundle: WitnessBundle<H>) -> Self {
+        Self::from_witness_bundle_internal(ConstDepth, max_checkpoints, bundle)
     }
 
     /// Construct a new BridgeTree from its constituent parts, checking for internal
@@ -802,44 +2313,185 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
         prior_bridges: Vec<MerkleBridge<H>>,
         current_bridge: Option<MerkleBridge<H>>,
         saved: BTreeMap<Position, usize>,
-        checkpoints: Vec<Checkpoint>,
+        mark_meta: BTreeMap<Position, W>,
+        checkpoints: Vec<Checkpoint<M>>,
         max_checkpoints: usize,
     ) -> Result<Self, BridgeTreeError> {
-        Self::check_consistency_internal(
-            &prior_bridges,
-            &current_bridge,
-            &saved,
-            &checkpoints,
-            max_checkpoints,
-        )?;
-        Ok(BridgeTree {
+        Self::from_parts_internal(
+            ConstDepth,
             prior_bridges,
             current_bridge,
             saved,
+            mark_meta,
             checkpoints,
             max_checkpoints,
-        })
+        )
     }
+}
 
-    fn check_consistency(&self) -> Result<(), BridgeTreeError> {
-        Self::check_consistency_internal(
-            &self.prior_bridges,
-            &self.current_bridge,
-            &self.saved,
-            &self.checkpoints,
-            self.max_checkpoints,
-        )
+impl<H: Hashable + Ord + Clone, M, W> DynBridgeTree<H, M, W> {
+    /// Construct a new tree of the specified depth that will start recording changes from the
+    /// state of the specified frontier.
+    pub fn from_frontier(
+        depth: Level,
+        max_checkpoints: usize,
+        frontier: NonEmptyFrontier<H>,
+    ) -> Self {
+        Self::from_frontier_internal(depth, max_checkpoints, frontier)
     }
 
-    fn check_consistency_internal(
-        prior_bridges: &[MerkleBridge<H>],
-        current_bridge: &Option<MerkleBridge<H>>,
-        saved: &BTreeMap<Position, usize>,
-        checkpoints: &[Checkpoint],
+    /// Construct a new tree of the specified depth that will start recording changes from the
+    /// tip of the specified witness bundle, with the bundle's witnessed leaf marked.
+    pub fn from_witness_bundle(
+        depth: Level,
         max_checkpoints: usize,
-    ) -> Result<(), BridgeTreeError> {
-        // check that saved values correspond to bridges
-        for (pos, i) in saved {
+        bundle: WitnessBundle<H>,
+    ) -> Self {
+        Self::from_witness_bundle_internal(depth, max_checkpoints, bundle)
+    }
+
+    /// Construct a new tree of the specified depth from its constituent parts, checking for
+    /// internal consistency.
+    pub fn from_parts(
+        depth: Level,
+        prior_bridges: Vec<MerkleBridge<H>>,
+        current_bridge: Option<MerkleBridge<H>>,
+        saved: BTreeMap<Position, usize>,
+        mark_meta: BTreeMap<Position, W>,
+        checkpoints: Vec<Checkpoint<M>>,
+        max_checkpoints: usize,
+    ) -> Result<Self, BridgeTreeError> {
+        Self::from_parts_internal(
+            depth,
+            prior_bridges,
+            current_bridge,
+            saved,
+            mark_meta,
+            checkpoints,
+            max_checkpoints,
+        )
+    }
+}
+
+/// The tree state as of which a witness is to be constructed.
+#[derive(Debug)]
+enum AuthBase<'a, M> {
+    Current,
+    Checkpoint(usize, &'a Checkpoint<M>),
+    NotFound,
+}
+
+impl<H: Hashable + Ord + Clone, D: Depth, M, W> DepthBridgeTree<H, D, M, W> {
+    fn from_frontier_internal(
+        depth: D,
+        max_checkpoints: usize,
+        frontier: NonEmptyFrontier<H>,
+    ) -> Self {
+        Self {
+            depth,
+            prior_bridges: vec![],
+            current_bridge: Some(MerkleBridge::from(frontier)),
+            saved: BTreeMap::new(),
+            mark_meta: BTreeMap::new(),
+            checkpoints: vec![],
+            max_checkpoints,
+            marked_values: BTreeMap::new(),
+        }
+    }
+
+    fn from_witness_bundle_internal(
+        depth: D,
+        max_checkpoints: usize,
+        bundle: WitnessBundle<H>,
+    ) -> Self {
+        let position = bundle.position();
+        let mut marked = MerkleBridge::from(bundle.frontier);
+        marked.track_current_leaf();
+        let mut marked_values = BTreeMap::new();
+        index_mark(&mut marked_values, marked.current_leaf(), position);
+        Self {
+            depth,
+            prior_bridges: vec![marked],
+            current_bridge: Some(bundle.bridge),
+            saved: vec![(position, 0)].into_iter().collect(),
+            mark_meta: BTreeMap::new(),
+            checkpoints: vec![],
+            max_checkpoints,
+            marked_values,
+        }
+    }
+
+    fn from_parts_internal(
+        depth: D,
+        prior_bridges: Vec<MerkleBridge<H>>,
+        current_bridge: Option<MerkleBridge<H>>,
+        saved: BTreeMap<Position, usize>,
+        mark_meta: BTreeMap<Position, W>,
+        checkpoints: Vec<Checkpoint<M>>,
+        max_checkpoints: usize,
+    ) -> Result<Self, BridgeTreeError> {
+        Self::check_consistency_internal(
+            depth.level(),
+            &prior_bridges,
+            &current_bridge,
+            &saved,
+            &mark_meta,
+            &checkpoints,
+            max_checkpoints,
+        )?;
+        let mut marked_values = BTreeMap::new();
+        for (position, idx) in &saved {
+            index_mark(
+                &mut marked_values,
+                prior_bridges[*idx].current_leaf(),
+                *position,
+            );
+        }
+        Ok(DepthBridgeTree {
+            depth,
+            prior_bridges,
+            current_bridge,
+            saved,
+            mark_meta,
+            checkpoints,
+            max_checkpoints,
+            marked_values,
+        })
+    }
+
+    fn check_consistency(&self) -> Result<(), BridgeTreeError> {
+        Self::check_consistency_internal(
+            self.depth.level(),
+            &self.prior_bridges,
+            &self.current_bridge,
+            &self.saved,
+            &self.mark_meta,
+            &self.checkpoints,
+            self.max_checkpoints,
+        )
+    }
+
+    fn check_consistency_internal(
+        depth: Level,
+        prior_bridges: &[MerkleBridge<H>],
+        current_bridge: &Option<MerkleBridge<H>>,
+        saved: &BTreeMap<Position, usize>,
+        mark_meta: &BTreeMap<Position, W>,
+        checkpoints: &[Checkpoint<M>],
+        max_checkpoints: usize,
+    ) -> Result<(), BridgeTreeError> {
+        // check that each frontier has the ommers required by its position, as every other
+        // operation on a bridge relies upon this
+        if let Some(i) = prior_bridges
+            .iter()
+            .chain(current_bridge.iter())
+            .position(|b| !b.frontier.is_structurally_valid())
+        {
+            return Err(BridgeTreeError::InvalidFrontier(i));
+        }
+
+        // check that saved values correspond to bridges
+        for (pos, i) in saved {
             if i >= &prior_bridges.len() {
                 return Err(BridgeTreeError::InvalidMarkIndex(*i));
             }
@@ -860,6 +2512,21 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
             return Err(BridgeTreeError::CheckpointMismatch);
         }
 
+        // check that checkpoints are ordered from oldest to newest
+        if let Some(i) = (1..checkpoints.len())
+            .find(|i| checkpoints[*i].bridges_len < checkpoints[i - 1].bridges_len)
+        {
+            return Err(BridgeTreeError::CheckpointsOutOfOrder(i));
+        }
+
+        // check that metadata exists only for marks that exist or may be restored on rewind
+        if let Some(pos) = mark_meta
+            .keys()
+            .find(|pos| !Self::is_mark_restorable(saved, checkpoints, pos))
+        {
+            return Err(BridgeTreeError::MarkMetaMismatch(*pos));
+        }
+
         for (prev, next) in prior_bridges.iter().zip(prior_bridges.iter().skip(1)) {
             prev.check_continuity(next)
                 .map_err(BridgeTreeError::Discontinuity)?;
@@ -870,6 +2537,44 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
                 .map_err(BridgeTreeError::Discontinuity)?;
         }
 
+        // check that each ommer is the root of a subtree completed by a leaf in its bridge's range
+        for (i, bridge) in prior_bridges
+            .iter()
+            .chain(current_bridge.iter())
+            .enumerate()
+        {
+            let range = bridge.position_range();
+            if let Some(address) = bridge.ommers.keys().find(|addr| {
+                // the position of the last leaf of the subtree, computed without overflow
+                let last = 1usize
+                    .checked_shl(u8::from(addr.level()).into())
+                    .and_then(|width| addr.index().checked_add(1)?.checked_mul(width))
+                    .map(|end| Position::from(end - 1));
+                !addr.is_complete_node()
+                    || last.map_or(true, |last| {
+                        last < range.start
+                            || last >= range.end
+                            || (bridge.prior_position.is_some() && last == range.start)
+                    })
+            }) {
+                return Err(BridgeTreeError::InvalidOmmer {
+                    bridge: i,
+                    address: *address,
+                });
+            }
+        }
+
+        // check that the most recently appended leaf is within the capacity of the tree
+        if let Some(position) = current_bridge
+            .as_ref()
+            .or_else(|| prior_bridges.last())
+            .map(|b| b.position())
+        {
+            if u8::from(depth) < 64 && u64::from(position) >> u8::from(depth) != 0 {
+                return Err(BridgeTreeError::DepthExceeded(position));
+            }
+        }
+
         Ok(())
     }
 
@@ -877,29 +2582,279 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
     /// Returns true if successful and false if the tree would exceed
     /// the maximum allowed depth.
     pub fn append(&mut self, value: &H) -> bool {
+        self.append_owned(value.clone())
+    }
+
+    /// Appends a new value to the tree at the next available slot, taking ownership of the value
+    /// so that it need not be cloned. Returns true if successful and false if the tree would
+    /// exceed the maximum allowed depth.
+    pub fn append_owned(&mut self, value: H) -> bool {
         if let Some(bridge) = self.current_bridge.as_mut() {
             if bridge
                 .frontier
                 .position()
-                .is_complete_subtree(Level::from(DEPTH))
+                .is_complete_subtree(self.depth.level())
             {
                 false
             } else {
-                bridge.append(value.clone());
+                bridge.append(value);
+                count!("bridgetree_appends");
                 true
             }
         } else {
-            self.current_bridge = Some(MerkleBridge::new(value.clone()));
+            self.current_bridge = Some(MerkleBridge::new(value));
+            count!("bridgetree_appends");
             true
         }
     }
 
+    /// Appends a new value to the tree at the next available slot in the same fashion as
+    /// [`BridgeTree::append`], and describes the effect of the append on the tree.
+    pub fn append_info(&mut self, value: &H) -> Result<AppendInfo, AppendError> {
+        let new_bridge = self.current_bridge.is_none();
+        if !self.append(value) {
+            return Err(AppendError::TreeFull);
+        }
+
+        let position = self
+            .current_position()
+            .expect("a leaf has been appended to the tree");
+        Ok(AppendInfo {
+            position,
+            new_bridge,
+            is_full: position.is_complete_subtree(self.depth.level()),
+        })
+    }
+
+    /// Appends each of the specified values to the tree, in order.
+    ///
+    /// The capacity of the tree is checked once for the whole slice rather than once per value;
+    /// if the values would not all fit in the tree, an error is returned and the tree is left
+    /// unmodified.
+    pub fn append_all(&mut self, values: &[H]) -> Result<(), BatchError> {
+        let available = remaining_capacity(self.depth.level(), self.current_position());
+        if values.len() > available {
+            return Err(BatchError::InsufficientCapacity {
+                required: values.len(),
+                available,
+            });
+        }
+
+        count!("bridgetree_appends", values.len());
+        let mut values = values.iter().cloned();
+        if self.current_bridge.is_none() {
+            self.current_bridge = values.next().map(MerkleBridge::new);
+        }
+        if let Some(bridge) = self.current_bridge.as_mut() {
+            values.for_each(|value| bridge.append(value));
+        }
+
+        Ok(())
+    }
+
+    /// Applies each of the specified operations to the tree, in order, and returns the positions
+    /// of the leaves that were appended.
+    ///
+    /// The batch is validated in its entirety before any operation is applied, so that if the
+    /// batch would append more leaves than the tree has room for, would create a checkpoint
+    /// when checkpointing is disabled, or would remove a mark that does not exist, an error is
+    /// returned and the tree is left unmodified.
+    pub fn apply_batch<I: IntoIterator<Item = BatchOp<H, M>>>(
+        &mut self,
+        ops: I,
+    ) -> Result<BatchReceipt, BatchError> {
+        let ops = ops.into_iter().collect::<Vec<_>>();
+        let required = self.validate_batch(&ops)?;
+        Ok(self.apply_validated_batch(ops, required))
+    }
+
+    /// Applies each of the specified operations to the tree in the same fashion as
+    /// [`BridgeTree::apply_batch`], but only if the root of the tree once the batch has been
+    /// applied would be `expected_root`.
+    ///
+    /// The resulting root is computed from a copy of the tree's frontier before any operation is
+    /// applied, so that a batch obtained from an untrusted source whose leaves do not produce the
+    /// expected root is rejected with [`BatchError::RootMismatch`] and the tree is left
+    /// unmodified.
+    pub fn apply_batch_checked<I: IntoIterator<Item = BatchOp<H, M>>>(
+        &mut self,
+        ops: I,
+        expected_root: &H,
+    ) -> Result<BatchReceipt, BatchError> {
+        let ops = ops.into_iter().collect::<Vec<_>>();
+        let required = self.validate_batch(&ops)?;
+
+        let mut frontier = self.frontier().cloned();
+        for op in &ops {
+            if let BatchOp::Append(value) | BatchOp::AppendMarked(value) = op {
+                match frontier.as_mut() {
+                    Some(f) => f.append(value.clone()),
+                    None => frontier = Some(NonEmptyFrontier::new(value.clone())),
+                }
+            }
+        }
+        let root_level = self.depth.level();
+        let root = frontier.map_or(H::empty_root(root_level), |f| f.root(Some(root_level)));
+        if &root != expected_root {
+            return Err(BatchError::RootMismatch);
+        }
+
+        Ok(self.apply_validated_batch(ops, required))
+    }
+
+    /// Applies each of the specified operations to the tree in the same fashion as
+    /// [`BridgeTree::apply_batch`], but only if the most recently appended leaf of the tree is at
+    /// `position`, or the tree is empty and `position` is `None`.
+    ///
+    /// This is intended for following a batch that was produced by applying it to another tree,
+    /// where `position` is the position of that tree before the batch was applied: a batch that
+    /// would not extend this tree from the same position is rejected with
+    /// [`BatchError::PositionMismatch`], identifying both positions, and the tree is left
+    /// unmodified.
+    ///
+    /// A batch that has already been applied to this tree, such as a batch delivered twice or
+    /// produced by two workers for the same range of leaves, is instead recognized and reported
+    /// as [`BatchStatus::AlreadyApplied`], leaving the tree unmodified. The batch is recognized
+    /// by its content: the root obtained by appending its leaves to the frontier of this tree at
+    /// `position` must be the root of this tree at the position of its last leaf. This requires
+    /// that the tree retain bridges ending at both positions, as it does for the current
+    /// position and for marked or checkpointed leaves. Only the leaves of the batch are
+    /// compared; its marks and checkpoints are not.
+    pub fn apply_batch_at<I: IntoIterator<Item = BatchOp<H, M>>>(
+        &mut self,
+        position: Option<Position>,
+        ops: I,
+    ) -> Result<BatchStatus, BatchError> {
+        let found = self.current_position();
+        if found != position {
+            let ops = ops.into_iter().collect::<Vec<_>>();
+            return if self.is_batch_applied(position, &ops) {
+                Ok(BatchStatus::AlreadyApplied)
+            } else {
+                Err(BatchError::PositionMismatch {
+                    expected: position,
+                    found,
+                })
+            };
+        }
+
+        self.apply_batch(ops).map(BatchStatus::Applied)
+    }
+
+    // Returns whether the leaves of the batch have already been appended to the tree starting
+    // after the specified position, by comparing the root that appending them would produce
+    // against the root of the tree at the position of the last leaf of the batch.
+    fn is_batch_applied(&self, position: Option<Position>, ops: &[BatchOp<H, M>]) -> bool {
+        let mut frontier = match position {
+            Some(p) => match self.frontier_at(p) {
+                Some(f) => Some(f.clone()),
+                None => return false,
+            },
+            None => None,
+        };
+        for op in ops {
+            if let BatchOp::Append(value) | BatchOp::AppendMarked(value) = op {
+                match frontier.as_mut() {
+                    Some(f) => f.append(value.clone()),
+                    None => frontier = Some(NonEmptyFrontier::new(value.clone())),
+                }
+            }
+        }
+
+        match frontier {
+            Some(f) if Some(f.position()) != position => {
+                let root_level = self.depth.level();
+                self.frontier_at(f.position()).map_or(false, |applied| {
+                    applied.root(Some(root_level)) == f.root(Some(root_level))
+                })
+            }
+            _ => false,
+        }
+    }
+
+    // Checks that each of the operations of the batch can be applied to the tree, and returns
+    // the number of leaves that the batch appends.
+    fn validate_batch(&self, ops: &[BatchOp<H, M>]) -> Result<usize, BatchError> {
+        let required = ops
+            .iter()
+            .filter(|op| matches!(op, BatchOp::Append(_) | BatchOp::AppendMarked(_)))
+            .count();
+        let available = remaining_capacity(self.depth.level(), self.current_position());
+        if required > available {
+            return Err(BatchError::InsufficientCapacity {
+                required,
+                available,
+            });
+        }
+        if self.max_checkpoints == 0 && ops.iter().any(|op| matches!(op, BatchOp::Checkpoint(_))) {
+            return Err(BatchError::CheckpointingDisabled);
+        }
+
+        // Each mark to be removed must exist at the point in the batch at which it is removed.
+        let mut next_position = self.current_position().map_or(0, |p| usize::from(p) + 1);
+        let mut added = BTreeSet::new();
+        let mut removed = BTreeSet::new();
+        for op in ops {
+            match op {
+                BatchOp::Append(_) => next_position += 1,
+                BatchOp::AppendMarked(_) => {
+                    added.insert(Position::from(next_position));
+                    next_position += 1;
+                }
+                BatchOp::RemoveMark(pos) => {
+                    if !(self.saved.contains_key(pos) || added.contains(pos))
+                        || !removed.insert(*pos)
+                    {
+                        return Err(BatchError::NotMarked(*pos));
+                    }
+                }
+                BatchOp::Checkpoint(_) => {}
+            }
+        }
+
+        Ok(required)
+    }
+
+    fn apply_validated_batch(&mut self, ops: Vec<BatchOp<H, M>>, required: usize) -> BatchReceipt {
+        let mut receipt = BatchReceipt {
+            appended: Vec::with_capacity(required),
+            marked: vec![],
+            checkpoints: 0,
+            start_position: self.current_position(),
+            position: None,
+        };
+        for op in ops {
+            match op {
+                BatchOp::Append(value) => {
+                    assert!(self.append_owned(value), "the tree's capacity was verified");
+                    receipt.appended.extend(self.current_position());
+                }
+                BatchOp::AppendMarked(value) => {
+                    assert!(self.append_owned(value), "the tree's capacity was verified");
+                    let position = self.mark().expect("a leaf was appended");
+                    receipt.appended.push(position);
+                    receipt.marked.push(position);
+                }
+                BatchOp::RemoveMark(pos) => {
+                    assert!(self.remove_mark(pos), "the mark was verified");
+                }
+                BatchOp::Checkpoint(meta) => {
+                    assert!(self.checkpoint_with(meta), "checkpointing was verified");
+                    receipt.checkpoints += 1;
+                }
+            }
+        }
+
+        receipt.position = self.current_position();
+        receipt
+    }
+
     /// Obtains the root of the Merkle tree at the specified checkpoint depth
     /// by hashing against empty nodes up to the maximum height of the tree.
     /// Returns `None` if there are not enough checkpoints available to reach the
     /// requested checkpoint depth.
     pub fn root(&self, checkpoint_depth: usize) -> Option<H> {
-        let root_level = Level::from(DEPTH);
+        let root_level = self.depth.level();
         if checkpoint_depth == 0 {
             Some(
                 self.current_bridge
@@ -918,6 +2873,69 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
         }
     }
 
+    /// Obtains the current root of the Merkle tree, or `None` if no leaves have
+    /// been appended. Unlike `root(0)`, this does not report the empty root for
+    /// an empty tree.
+    pub fn root_if_nonempty(&self) -> Option<H> {
+        let root_level = self.depth.level();
+        self.current_bridge
+            .as_ref()
+            .map(|bridge| bridge.frontier().root(Some(root_level)))
+    }
+
+    /// Obtains the root of the Merkle tree as of the point at which the leaf at the specified
+    /// position was the most recently appended leaf, by hashing against empty nodes up to the
+    /// maximum height of the tree.
+    ///
+    /// This is possible only if the tree retains a bridge that ends at the specified position,
+    /// such as a bridge for a marked or checkpointed leaf, or the current bridge. Returns `None`
+    /// if no such bridge exists, including when the bridge has been fused with its successor by
+    /// [`BridgeTree::garbage_collect`].
+    pub fn root_at_position(&self, position: Position) -> Option<H> {
+        self.frontier_at(position)
+            .map(|f| f.root(Some(self.depth.level())))
+    }
+
+    /// Returns the frontier of the tree as of the point at which the leaf at the specified
+    /// position was the most recently appended leaf.
+    ///
+    /// As with [`BridgeTree::root_at_position`], this is possible only if the tree retains a
+    /// bridge that ends at the specified position; otherwise, this returns `None`.
+    pub fn frontier_at(&self, position: Position) -> Option<&NonEmptyFrontier<H>> {
+        self.current_bridge
+            .as_ref()
+            .filter(|b| b.position() == position)
+            .or_else(|| {
+                self.prior_bridges
+                    .binary_search_by_key(&position, |b| b.position())
+                    .ok()
+                    .map(|i| &self.prior_bridges[i])
+            })
+            .map(|b| b.frontier())
+    }
+
+    /// Returns the metadata associated with the checkpoint at the specified checkpoint depth,
+    /// where a depth of 1 refers to the most recent checkpoint, consistent with the checkpoint
+    /// depths used by [`BridgeTree::root`]. Returns `None` if not enough checkpoints are
+    /// available to reach the requested checkpoint depth.
+    pub fn checkpoint_meta(&self, checkpoint_depth: usize) -> Option<&M> {
+        checkpoint_depth
+            .checked_sub(1)
+            .and_then(|d| self.checkpoints.iter().rev().nth(d))
+            .map(|c| c.meta())
+    }
+
+    /// Returns the position of the most recently appended leaf as of the checkpoint at the
+    /// specified depth, where a depth of 1 refers to the most recent checkpoint. Returns `None`
+    /// if not enough checkpoints are available to reach the requested checkpoint depth, or if
+    /// the tree was empty when the checkpoint was created.
+    pub fn checkpoint_position(&self, checkpoint_depth: usize) -> Option<Position> {
+        checkpoint_depth
+            .checked_sub(1)
+            .and_then(|d| self.checkpoints.iter().rev().nth(d))
+            .and_then(|c| c.position(&self.prior_bridges))
+    }
+
     /// Returns the most recently appended leaf value.
     pub fn current_position(&self) -> Option<Position> {
         self.current_bridge.as_ref().map(|b| b.position())
@@ -928,11 +2946,48 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
         self.current_bridge.as_ref().map(|b| b.current_leaf())
     }
 
+    /// Marks the current leaf as one for which we're interested in producing a witness, and
+    /// associates the specified metadata with the mark, replacing any metadata previously
+    /// associated with it. Returns the current position if successful, or None if the tree is
+    /// empty.
+    ///
+    /// Metadata is removed along with its mark, and is restored if the mark is restored by a
+    /// rewind. Replacing the metadata of an existing mark is not reverted by a rewind.
+    pub fn mark_with(&mut self, meta: W) -> Option<Position> {
+        let position = self.mark()?;
+        self.mark_meta.insert(position, meta);
+        Some(position)
+    }
+
     /// Marks the current leaf as one for which we're interested in producing
     /// a witness. Returns an optional value containing the
     /// current position if successful or if the current value was already
     /// marked, or None if the tree is empty.
+    ///
+    /// Marks are identified by position rather than by value: marking always refers to the leaf
+    /// at the tip of the tree, even if the same value was appended, marked and unmarked at an
+    /// earlier position. A mark that has been removed with [`BridgeTree::remove_mark`] is
+    /// restored only by rewinding to a checkpoint that precedes its removal.
     pub fn mark(&mut self) -> Option<Position> {
+        self.mark_status().position()
+    }
+
+    /// Marks the current leaf in the same fashion as [`BridgeTree::mark`], and reports whether
+    /// the leaf was newly marked, so that callers may distinguish redundant marking.
+    pub fn mark_status(&mut self) -> MarkStatus {
+        match self.mark_current() {
+            Some((position, true)) => {
+                count!("bridgetree_marks_added");
+                MarkStatus::New(position)
+            }
+            Some((position, false)) => MarkStatus::AlreadyMarked(position),
+            None => MarkStatus::EmptyTree,
+        }
+    }
+
+    // Marks the current leaf, returning its position along with whether the position was not
+    // already marked.
+    fn mark_current(&mut self) -> Option<(Position, bool)> {
         match self.current_bridge.take() {
             Some(mut cur_b) => {
                 cur_b.track_current_leaf();
@@ -954,9 +3009,19 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
                     self.current_bridge = Some(successor);
                 }
 
-                self.saved
-                    .entry(pos)
-                    .or_insert(self.prior_bridges.len() - 1);
+                let idx = self.prior_bridges.len() - 1;
+                let is_new = match self.saved.entry(pos) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(idx);
+                        index_mark(
+                            &mut self.marked_values,
+                            self.prior_bridges[idx].current_leaf(),
+                            pos,
+                        );
+                        true
+                    }
+                    Entry::Occupied(_) => false,
+                };
 
                 // mark the position as having been marked in the current checkpoint
                 if let Some(c) = self.checkpoints.last_mut() {
@@ -965,12 +3030,106 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
                     }
                 }
 
-                Some(pos)
+                Some((pos, is_new))
             }
             None => None,
         }
     }
 
+    /// Marks the leaf at the specified position, which may precede the most recently appended
+    /// leaf, as one for which we're interested in producing a witness.
+    ///
+    /// This is possible only if the tree retains a bridge that ends at the specified position,
+    /// such as the bridge for a checkpointed or previously marked leaf, and if the values of
+    /// the nodes to the right of the position that are required for its witness, and which
+    /// have been completed by leaves appended since, can be recovered from the bridges that
+    /// follow it. Those bridges retain the roots of the complete subtrees that precede each of
+    /// their own positions, along with the nodes required to witness other marked positions; a
+    /// node that lies strictly between two retained bridges, and that is not required by any
+    /// other mark, will have been discarded. Marking the most recently appended leaf is
+    /// equivalent to calling [`BridgeTree::mark`].
+    ///
+    /// As with [`BridgeTree::mark`], the mark will be removed by a rewind to a checkpoint that
+    /// was created before the marked leaf was appended; a mark at a position prior to the
+    /// checkpointed leaf is retained.
+    pub fn mark_position(&mut self, position: Position) -> Result<(), MarkError> {
+        let tip = self
+            .current_position()
+            .filter(|tip| *tip >= position)
+            .ok_or(MarkError::PositionNotAppended(position))?;
+        if position == tip {
+            self.mark();
+            return Ok(());
+        }
+        if self.saved.contains_key(&position) {
+            return Ok(());
+        }
+        let idx = self
+            .prior_bridges
+            .binary_search_by_key(&position, |b| b.position())
+            .map_err(|_| MarkError::BridgeNotFound(position))?;
+
+        // The bridges that follow the bridge for the marked position, ending with the current
+        // bridge, in order of position.
+        let later = |i: usize| self.prior_bridges.get(i).or(self.current_bridge.as_ref());
+        let last_idx = self.prior_bridges.len();
+
+        // Find the value of each node to the right of the position that is required for its
+        // witness and has already been completed, along with the index of the bridge during
+        // which it was completed, and for each following bridge the address whose sibling it
+        // would have been tracking had the position been marked as it was appended.
+        let mut ommers = vec![];
+        let mut tracking = vec![None; last_idx - idx];
+        for (addr, source) in position.witness_addrs(self.depth.level()) {
+            if source != Source::Future {
+                continue;
+            }
+            let last_leaf = Position::from(usize::from(addr.position_range().end) - 1);
+            for (i, t) in tracking.iter_mut().enumerate() {
+                if t.is_none() && later(idx + 1 + i).unwrap().position() < last_leaf {
+                    *t = Some(addr.sibling());
+                }
+            }
+            if last_leaf <= tip {
+                let owner = (idx + 1..=last_idx)
+                    .find(|i| later(*i).unwrap().position() >= last_leaf)
+                    .unwrap();
+                let value = (owner..=last_idx)
+                    .find_map(|i| later(i).unwrap().node_value(addr))
+                    .ok_or(MarkError::NodeNotFound(addr))?;
+                ommers.push((owner, addr, value));
+            }
+        }
+
+        for (i, bridge) in self
+            .prior_bridges
+            .iter_mut()
+            .chain(self.current_bridge.as_mut())
+            .enumerate()
+            .skip(idx + 1)
+        {
+            for (_, addr, value) in ommers.iter().filter(|(owner, _, _)| *owner == i) {
+                bridge.ommers.insert(*addr, value.clone());
+            }
+            if let Some(addr) = tracking[i - idx - 1] {
+                bridge.tracking.insert(addr);
+            }
+        }
+        self.saved.insert(position, idx);
+        index_mark(
+            &mut self.marked_values,
+            self.prior_bridges[idx].current_leaf(),
+            position,
+        );
+        if let Some(c) = self.checkpoints.last_mut() {
+            if idx + 1 >= c.bridges_len {
+                c.marked.insert(position);
+            }
+        }
+        count!("bridgetree_marks_added");
+        Ok(())
+    }
+
     /// Return a set of all the positions for which we have marked.
     pub fn marked_positions(&self) -> BTreeSet<Position> {
         self.saved.keys().cloned().collect()
@@ -984,56 +3143,341 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
             .and_then(|idx| self.prior_bridges.get(*idx).map(|b| b.current_leaf()))
     }
 
+    /// Returns an iterator over the marked positions at which the specified leaf value was
+    /// appended, in ascending order. The same value may be marked at more than one position.
+    ///
+    /// The lookup uses an index from marked leaf values to their positions, which holds a clone
+    /// of each distinct marked leaf value in addition to the copy held by the bridge for its
+    /// position; the memory that it occupies is reported by [`BridgeTree::gc_stats`] and
+    /// included in [`BridgeTree::dynamic_memory_usage`].
+    pub fn positions_of<'a>(&'a self, value: &H) -> impl Iterator<Item = Position> + 'a {
+        self.marked_values
+            .get(value)
+            .into_iter()
+            .flat_map(|positions| positions.iter().cloned())
+    }
+
+    /// Returns an iterator over the marked positions of the tree, in ascending order, along
+    /// with the leaf value at each position and the metadata associated with its mark, if any.
+    pub fn marked_leaves(&self) -> impl Iterator<Item = (Position, &H, Option<&W>)> + '_ {
+        self.saved.iter().map(move |(pos, idx)| {
+            (
+                *pos,
+                self.prior_bridges[*idx].current_leaf(),
+                self.mark_meta.get(pos),
+            )
+        })
+    }
+
     /// Marks the value at the specified position as a value we're no longer
     /// interested in maintaining a mark for. Returns true if successful and
     /// false if we were already not maintaining a mark at this position.
     pub fn remove_mark(&mut self, position: Position) -> bool {
         if let Some(idx) = self.saved.remove(&position) {
+            unindex_mark(
+                &mut self.marked_values,
+                self.prior_bridges[idx].current_leaf(),
+                position,
+            );
             // If the position is one that has *not* just been marked since the last checkpoint,
             // then add it to the set of those forgotten during the current checkpoint span so that
             // it can be restored on rollback.
-            if let Some(c) = self.checkpoints.last_mut() {
-                if !c.marked.contains(&position) {
-                    c.forgotten.insert(position, idx);
-                }
+            let restorable = self
+                .checkpoints
+                .last_mut()
+                .map_or(false, |c| c.forget(position, idx));
+            // Metadata for the mark is retained only if the mark may be restored.
+            if !restorable {
+                self.mark_meta.remove(&position);
             }
+            self.prune_current_bridge();
+            self.collapse_unmarked_prefix();
+            count!("bridgetree_marks_removed");
             true
         } else {
             false
         }
     }
 
-    /// Creates a new checkpoint for the current tree state. It is valid to
-    /// have multiple checkpoints for the same tree state, and each `rewind`
-    /// call will remove a single checkpoint.
-    pub fn checkpoint(&mut self) {
-        match self.current_bridge.take() {
-            Some(cur_b) => {
-                let is_marked = self.get_marked_leaf(cur_b.position()).is_some();
+    /// Removes the marks at each of the specified positions, in a single pass over the marked
+    /// positions of the tree. Returns the number of marks that were removed; positions that were
+    /// not marked are ignored.
+    ///
+    /// This has the same effect as calling [`BridgeTree::remove_mark`] for each of the
+    /// specified positions.
+    pub fn remove_marks(&mut self, positions: &[Position]) -> usize {
+        let positions = positions.iter().collect::<BTreeSet<_>>();
+        self.retain_marks(|pos, _| !positions.contains(&pos))
+    }
 
-                // Do not create a duplicate bridge
-                if self
-                    .prior_bridges
-                    .last()
-                    .map_or(false, |pb| pb.position() == cur_b.position())
-                {
-                    self.current_bridge = Some(cur_b);
-                } else {
-                    self.current_bridge = Some(cur_b.successor(false));
-                    self.prior_bridges.push(cur_b);
-                }
+    /// Stops tracking information in the current bridge that is no longer required to
+    /// construct a witness for any marked position, so that it is not propagated to the
+    /// bridge's successors. Prior bridges are left untouched, as they may be required to
+    /// restore forgotten marks on rewind; they are pruned by `garbage_collect`.
+    fn prune_current_bridge(&mut self) {
+        if let Some(cur_b) = self.current_bridge.as_mut() {
+            cur_b.retain_marked(&self.saved);
+        }
+    }
 
-                self.checkpoints
-                    .push(Checkpoint::at_length(self.prior_bridges.len(), is_marked));
-            }
-            None => {
-                self.checkpoints.push(Checkpoint::at_length(0, false));
+    /// Fuses together the bridges that precede the oldest checkpoint once no position in the
+    /// tree is marked, so that a tree whose marks have all been removed does not continue to
+    /// retain every historical bridge until it is next garbage collected. Bridges required to
+    /// restore marks that were removed since the oldest checkpoint are retained.
+    fn collapse_unmarked_prefix(&mut self) {
+        if self.saved.is_empty() {
+            let gc_len = self
+                .checkpoints
+                .first()
+                .map_or(self.prior_bridges.len(), |c| c.bridges_len);
+            if gc_len > 1 {
+                self.collect_bridges(gc_len);
             }
         }
+    }
 
-        if self.checkpoints.len() > self.max_checkpoints {
+    /// Retains only the marks for which the specified predicate returns true, given the
+    /// marked position and the leaf value at that position, and removes all other marks in a
+    /// single pass. Returns the number of marks that were removed.
+    ///
+    /// This has the same effect as calling [`BridgeTree::remove_mark`] for each of the
+    /// removed positions; marks removed in this fashion will be restored by a `rewind` to a
+    /// checkpoint at which they existed.
+    pub fn retain_marks<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(Position, &H) -> bool,
+    {
+        let prior_bridges = &self.prior_bridges;
+        let mark_meta = &mut self.mark_meta;
+        let marked_values = &mut self.marked_values;
+        let mut checkpoint = self.checkpoints.last_mut();
+        let mut removed = 0;
+        self.saved.retain(|position, idx| {
+            let value = prior_bridges[*idx].current_leaf();
+            if f(*position, value) {
+                true
+            } else {
+                unindex_mark(marked_values, value, *position);
+                let restorable = checkpoint
+                    .as_mut()
+                    .map_or(false, |c| c.forget(*position, *idx));
+                if !restorable {
+                    mark_meta.remove(position);
+                }
+                removed += 1;
+                false
+            }
+        });
+        if removed > 0 {
+            self.prune_current_bridge();
+            self.collapse_unmarked_prefix();
+            count!("bridgetree_marks_removed", removed);
+        }
+        removed
+    }
+
+    /// Creates a new checkpoint for the current tree state. It is valid to
+    /// have multiple checkpoints for the same tree state, and each `rewind`
+    /// call will remove a single checkpoint. Returns false and leaves the
+    /// tree unmodified if checkpointing is disabled because the tree was
+    /// constructed with a `max_checkpoints` value of zero.
+    ///
+    /// A checkpoint is created even if no leaf has been appended since the most recent
+    /// checkpoint; use [`BridgeTree::checkpoint_if_changed`] to skip such redundant checkpoints.
+    ///
+    /// The checkpoint is created with the default metadata value; use
+    /// [`BridgeTree::checkpoint_with`] to associate other metadata with the checkpoint.
+    pub fn checkpoint(&mut self) -> bool
+    where
+        M: Default,
+    {
+        self.checkpoint_with(M::default())
+    }
+
+    /// Creates a new checkpoint for the current tree state in the same fashion as
+    /// [`BridgeTree::checkpoint`], and associates the specified metadata with it.
+    pub fn checkpoint_with(&mut self, meta: M) -> bool {
+        if !self.push_checkpoint(meta) {
+            return false;
+        }
+
+        if self.checkpoints.len() > self.max_checkpoints {
             self.drop_oldest_checkpoint();
+            self.collapse_unmarked_prefix();
+        }
+
+        true
+    }
+
+    /// Creates a new checkpoint for the current tree state in the same fashion as
+    /// [`BridgeTree::checkpoint_with`]. If this causes the oldest checkpoint to be evicted
+    /// because `max_checkpoints` has been exceeded, the state of the tree at the evicted
+    /// checkpoint is passed to `archive` before any of the data that it refers to is discarded.
+    ///
+    /// This is equivalent to calling `drain_checkpoints(max_checkpoints - 1)` before each call
+    /// to [`BridgeTree::checkpoint_with`], except that no root is computed unless a checkpoint
+    /// is actually evicted.
+    pub fn checkpoint_with_archive(
+        &mut self,
+        meta: M,
+        archive: impl FnOnce(ArchivedCheckpoint<H, M>),
+    ) -> bool {
+        if !self.push_checkpoint(meta) {
+            return false;
+        }
+
+        if self.checkpoints.len() > self.max_checkpoints {
+            if let Some(evicted) = self.drain_checkpoints(self.max_checkpoints).pop() {
+                archive(evicted);
+            }
+        }
+
+        true
+    }
+
+    // Appends a checkpoint for the current tree state without evicting the oldest checkpoint,
+    // returning false if checkpointing is disabled.
+    fn push_checkpoint(&mut self, meta: M) -> bool {
+        if self.max_checkpoints == 0 {
+            return false;
+        }
+
+        match self.current_bridge.take() {
+            Some(cur_b) => {
+                let is_marked = self.get_marked_leaf(cur_b.position()).is_some();
+
+                // Do not create a duplicate bridge
+                if self
+                    .prior_bridges
+                    .last()
+                    .map_or(false, |pb| pb.position() == cur_b.position())
+                {
+                    self.current_bridge = Some(cur_b);
+                } else {
+                    self.current_bridge = Some(cur_b.successor(false));
+                    self.prior_bridges.push(cur_b);
+                }
+
+                self.checkpoints.push(Checkpoint::at_length(
+                    self.prior_bridges.len(),
+                    is_marked,
+                    meta,
+                ));
+            }
+            None => {
+                self.checkpoints.push(Checkpoint::at_length(0, false, meta));
+            }
+        }
+        count!("bridgetree_checkpoints_added");
+        true
+    }
+
+    /// Creates a new checkpoint for the current tree state only if a leaf has been appended to
+    /// the tree since the most recent checkpoint was created, or if no checkpoint exists.
+    /// Returns true if a checkpoint was created.
+    ///
+    /// This is useful for callers that checkpoint once per block, where some blocks may not
+    /// add any leaves to the tree; skipping redundant checkpoints allows the available
+    /// `max_checkpoints` to span a larger number of blocks. Only the tree's position is
+    /// compared; marks created or removed since the last checkpoint do not by themselves
+    /// cause a new checkpoint to be created.
+    pub fn checkpoint_if_changed(&mut self) -> bool
+    where
+        M: Default,
+    {
+        let changed = self.checkpoints.last().map_or(true, |c| {
+            let checkpointed_position = c
+                .bridges_len
+                .checked_sub(1)
+                .map(|idx| self.prior_bridges[idx].position());
+            checkpointed_position != self.current_position()
+        });
+
+        changed && self.checkpoint()
+    }
+
+    /// Removes the oldest checkpoints from the tree until at most `keep` checkpoints remain,
+    /// and returns the state of the tree at each of the removed checkpoints, oldest first.
+    ///
+    /// Checkpoints in excess of `max_checkpoints` are otherwise discarded without notice when a
+    /// new checkpoint is created; a caller that wishes to archive every checkpoint may use
+    /// [`BridgeTree::checkpoint_with_archive`] to create checkpoints instead.
+    pub fn drain_checkpoints(&mut self, keep: usize) -> Vec<ArchivedCheckpoint<H, M>> {
+        let root_level = self.depth.level();
+        let drain_len = self.checkpoints.len().saturating_sub(keep);
+        let archived = self.checkpoints[..drain_len]
+            .iter()
+            .map(|c| {
+                (
+                    c.position(&self.prior_bridges),
+                    c.root(&self.prior_bridges, root_level),
+                )
+            })
+            .collect::<Vec<_>>();
+        let drained = self.checkpoints.drain(..drain_len).collect::<Vec<_>>();
+        if drain_len > 0 {
+            self.retain_restorable_mark_meta();
+            self.collapse_unmarked_prefix();
+            count!("bridgetree_checkpoints_dropped", drain_len);
+        }
+        archived
+            .into_iter()
+            .zip(drained)
+            .map(|((position, root), c)| ArchivedCheckpoint {
+                position,
+                root,
+                meta: c.meta,
+            })
+            .collect()
+    }
+
+    /// Collapses each run of consecutive checkpoints at the same tree position into the most
+    /// recent checkpoint of the run, and returns the number of checkpoints removed.
+    ///
+    /// Checkpoints created when no leaves had been appended since the previous checkpoint (for
+    /// example, for blocks that add no leaves to the tree) count against `max_checkpoints`
+    /// without extending the range of positions to which the tree may be rewound; compacting
+    /// them allows the remaining checkpoints to span more distinct positions. The metadata of
+    /// the most recent checkpoint of each run is retained. Marks added or removed after an
+    /// earlier checkpoint of a run are attributed to the checkpoint preceding the run, so that
+    /// rewinding past the run restores the same set of marks as it would have prior to
+    /// compaction.
+    pub fn compact_checkpoints(&mut self) -> usize {
+        let mut removed = 0;
+        let mut i = 0;
+        while i + 1 < self.checkpoints.len() {
+            if self.checkpoints[i].position(&self.prior_bridges)
+                == self.checkpoints[i + 1].position(&self.prior_bridges)
+            {
+                let c = self.checkpoints.remove(i);
+                if let Some(prev) = i.checked_sub(1) {
+                    self.checkpoints[prev].absorb(c);
+                }
+                removed += 1;
+            } else {
+                i += 1;
+            }
+        }
+
+        if removed > 0 {
+            self.retain_restorable_mark_meta();
+            count!("bridgetree_checkpoints_dropped", removed);
         }
+        debug_assert_eq!(self.check_consistency(), Ok(()));
+        removed
+    }
+
+    /// Returns whether a subsequent call to [`BridgeTree::rewind`] would succeed, without
+    /// modifying the tree.
+    pub fn can_rewind(&self) -> bool {
+        self.can_rewind_n(1)
+    }
+
+    /// Returns whether `checkpoint_depth` successive calls to [`BridgeTree::rewind`] would all
+    /// succeed, without modifying the tree. This is the case whenever at least that many
+    /// checkpoints are available; rewinding to a checkpoint never otherwise fails.
+    pub fn can_rewind_n(&self, checkpoint_depth: usize) -> bool {
+        checkpoint_depth <= self.checkpoints.len()
     }
 
     /// Rewinds the tree state to the previous checkpoint, and then removes
@@ -1042,21 +3486,176 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
     /// at that tree state have been removed using `rewind`. This function
     /// return false and leave the tree unmodified if no checkpoints exist.
     pub fn rewind(&mut self) -> bool {
-        match self.checkpoints.pop() {
-            Some(mut c) => {
-                // drop marked values at and above the checkpoint height;
-                // we will re-mark if necessary.
-                self.saved.append(&mut c.forgotten);
-                self.saved.retain(|_, i| *i + 1 < c.bridges_len);
-                self.prior_bridges.truncate(c.bridges_len);
-                self.current_bridge = self.prior_bridges.last().map(|b| b.successor(c.is_marked));
-                if c.is_marked {
-                    self.mark();
-                }
+        self.rewind_reporting().is_ok()
+    }
+
+    /// Rewinds the tree state to the previous checkpoint in the same fashion as
+    /// [`BridgeTree::rewind`], and returns a description of the resulting change in the tree's
+    /// state. Returns an error and leaves the tree unmodified if no checkpoints exist.
+    pub fn rewind_reporting(&mut self) -> Result<RewindResult<M>, RewindError> {
+        count!("bridgetree_rewinds");
+        let c = match self.checkpoints.pop() {
+            Some(c) => c,
+            None => {
+                count!("bridgetree_rewinds_failed");
+                return Err(RewindError::NoCheckpoints);
+            }
+        };
+        let prior_size = self.current_position().map_or(0, |p| u64::from(p) + 1);
+        let prior_marks = self.marked_positions();
+
+        let meta = self.restore_checkpoint(c);
+
+        let new_position = self.current_position();
+        Ok(RewindResult {
+            new_position,
+            leaves_dropped: prior_size - new_position.map_or(0, |p| u64::from(p) + 1),
+            marks_dropped: prior_marks
+                .into_iter()
+                .filter(|p| !self.saved.contains_key(p))
+                .collect(),
+            meta,
+        })
+    }
+
+    /// Rewinds the tree to the most recent checkpoint having the specified root, and returns
+    /// the number of checkpoints that were removed in the process. The tree's checkpoints are
+    /// searched from newest to oldest; if no checkpoint has the specified root, an error is
+    /// returned and the tree is left unmodified.
+    ///
+    /// This is useful when handling a chain reorganization, where the root of the tree as of
+    /// the last block in common with the new chain is known but the number of checkpoints
+    /// that must be rewound is not.
+    pub fn rewind_to_root(&mut self, root: &H) -> Result<usize, RewindError> {
+        count!("bridgetree_rewinds");
+        if self.checkpoints.is_empty() {
+            count!("bridgetree_rewinds_failed");
+            return Err(RewindError::NoCheckpoints);
+        }
+
+        let depth = match (1..=self.checkpoints.len())
+            .find(|depth| self.root(*depth).as_ref() == Some(root))
+        {
+            Some(depth) => depth,
+            None => {
+                count!("bridgetree_rewinds_failed");
+                return Err(RewindError::RootNotFound);
+            }
+        };
+
+        for _ in 0..depth {
+            let c = self
+                .checkpoints
+                .pop()
+                .expect("checkpoint depth was verified");
+            self.restore_checkpoint(c);
+        }
+
+        Ok(depth)
+    }
+
+    /// Rewinds the tree to the most recent checkpoint at which the leaf at the specified position
+    /// was the most recently appended leaf, removing that checkpoint along with all more recent
+    /// checkpoints, and then appends each of the specified values, the first of which is
+    /// appended at the position following `position`.
+    ///
+    /// This is useful for rebuilding the tip of the tree from a log of appended leaf values.
+    /// If the tree has no such checkpoint, or the values would not fit in the tree once it has
+    /// been rewound, an error is returned and the tree is left unmodified.
+    pub fn replay_from(&mut self, position: Position, values: &[H]) -> Result<(), ReplayError> {
+        let depth = (1..=self.checkpoints.len())
+            .find(|depth| self.checkpoint_position(*depth) == Some(position))
+            .ok_or(ReplayError::CheckpointNotFound(position))?;
+
+        let available = remaining_capacity(self.depth.level(), Some(position));
+        if values.len() > available {
+            return Err(ReplayError::InsufficientCapacity {
+                required: values.len(),
+                available,
+            });
+        }
+
+        for _ in 0..depth {
+            let c = self
+                .checkpoints
+                .pop()
+                .expect("checkpoint depth was verified");
+            self.restore_checkpoint(c);
+        }
+        debug_assert_eq!(self.current_position(), Some(position));
+        for value in values {
+            assert!(self.append(value), "the tree's capacity was verified");
+        }
+
+        Ok(())
+    }
+
+    /// Returns an independent copy of the tree in the state that it had at the specified
+    /// checkpoint depth, where a depth of 1 refers to the most recent checkpoint and a depth of 0
+    /// refers to the current state of the tree, consistent with the checkpoint depths used by
+    /// [`BridgeTree::root`]. The copy retains the checkpoints that precede that state. Returns
+    /// `None` if not enough checkpoints are available to reach the requested checkpoint depth.
+    ///
+    /// The copy is produced by cloning the tree and rewinding the clone, and so the tree itself
+    /// is left unmodified.
+    pub fn as_of_checkpoint(&self, checkpoint_depth: usize) -> Option<Self>
+    where
+        M: Clone,
+        W: Clone,
+    {
+        if checkpoint_depth > self.checkpoints.len() {
+            return None;
+        }
+        let mut result = self.clone();
+        for _ in 0..checkpoint_depth {
+            let c = result
+                .checkpoints
+                .pop()
+                .expect("checkpoint depth was verified");
+            result.restore_checkpoint(c);
+        }
+        Some(result)
+    }
+
+    fn restore_checkpoint(&mut self, mut c: Checkpoint<M>) -> M {
+        // drop marked values at and above the checkpoint height;
+        // we will re-mark if necessary.
+        for (position, idx) in core::mem::take(&mut c.forgotten) {
+            self.saved.insert(position, idx);
+            index_mark(
+                &mut self.marked_values,
+                self.prior_bridges[idx].current_leaf(),
+                position,
+            );
+        }
+        let prior_bridges = &self.prior_bridges;
+        let marked_values = &mut self.marked_values;
+        self.saved.retain(|position, i| {
+            if *i + 1 < c.bridges_len {
                 true
+            } else {
+                unindex_mark(marked_values, prior_bridges[*i].current_leaf(), *position);
+                false
             }
-            None => false,
+        });
+        if self.prior_bridges.len() > c.bridges_len {
+            self.prior_bridges
+                .drain(c.bridges_len..)
+                .for_each(MerkleBridge::discard);
+        }
+        let successor = self.prior_bridges.last().map(|b| b.successor(c.is_marked));
+        if let Some(bridge) = core::mem::replace(&mut self.current_bridge, successor) {
+            bridge.discard();
         }
+        if c.is_marked {
+            self.mark_current();
+        }
+        self.retain_restorable_mark_meta();
+        // Each retained mark refers to a bridge prior to the checkpointed bridge, which is not
+        // modified by the truncation above, so every mark continues to refer to the bridge
+        // for its position.
+        debug_assert_eq!(self.check_consistency(), Ok(()));
+        c.meta
     }
 
     /// Obtains a witness to the value at the specified position,
@@ -1064,19 +3663,203 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
     /// Returns `None` if there is no available witness to that
     /// position or if the root does not correspond to a checkpointed
     /// root of the tree.
+    ///
+    /// In debug builds, the witness is verified against the specified root before it is
+    /// returned, and this method panics if verification fails, as this indicates that the
+    /// internal state of the tree has been corrupted. The panic message names the lowest level
+    /// at which the witness can be seen to diverge from the frontier of the tree.
     pub fn witness(&self, position: Position, as_of_root: &H) -> Option<Vec<H>> {
-        self.witness_inner(position, as_of_root).ok()
+        let (path, leaf) = self.witness_with_leaf(position, as_of_root).ok()?;
+        debug_assert!(
+            &root_from_witness(leaf.clone(), position, &path, H::combine) == as_of_root,
+            "Witness for position {:?} does not produce the root for which it was requested; it \
+            first diverges from the frontier of the tree at {:?}.",
+            position,
+            self.witness_divergence(position, leaf, &path, as_of_root)
+        );
+        Some(path)
+    }
+
+    // Returns the lowest level at which either the witness for the specified leaf or the
+    // ancestor of the leaf computed from that witness disagrees with the frontier of the tree as
+    // of the specified root, or the level of the root if they agree at every lower level. Below
+    // the level just beneath the common ancestor of the leaf and the tip of that frontier, the
+    // witness is determined only by the ommers of the leaf's own bridge, so no comparison is
+    // made at those levels.
+    fn witness_divergence(
+        &self,
+        position: Position,
+        leaf: &H,
+        path: &[H],
+        as_of_root: &H,
+    ) -> Level {
+        let depth = self.depth.level();
+        let frontier = match self.witness_base(position, as_of_root) {
+            Ok((_, successor)) => successor.frontier,
+            Err(_) => return depth,
+        };
+        let tip_path = frontier
+            .witness(depth.into(), |addr| Some(H::empty_root(addr.level())))
+            .unwrap_or_default();
+        let common = position.common_ancestor_level(frontier.position());
+        let mut ancestor = leaf.clone();
+        let mut addr = Address::from(position);
+        for (sibling, tip_sibling) in path.iter().zip(&tip_path) {
+            let level = addr.level();
+            let diverges = if level >= common {
+                // the leaf and the tip share this ancestor and its sibling
+                ancestor != frontier.root_with(Some(level), H::combine) || sibling != tip_sibling
+            } else if level + 1 == common {
+                // the ancestors of the leaf and the tip at this level are siblings
+                sibling != &frontier.root_with(Some(level), H::combine) || &ancestor != tip_sibling
+            } else {
+                false
+            };
+            if diverges {
+                return level;
+            }
+            ancestor = if addr.is_complete_node() {
+                H::combine(level, sibling, &ancestor)
+            } else {
+                H::combine(level, &ancestor, sibling)
+            };
+            addr = addr.parent();
+        }
+        depth
+    }
+
+    /// Obtains a witness to the value at the specified position as of the tree state
+    /// corresponding to the given root, in the same fashion as [`BridgeTree::witness`], and
+    /// additionally checks that hashing the marked leaf together with the witness produces
+    /// `expected_root`. Returns `None` if no witness is available or if the check fails.
+    pub fn witness_verified(&self, position: Position, expected_root: &H) -> Option<Vec<H>> {
+        let (path, leaf) = self.witness_with_leaf(position, expected_root).ok()?;
+        Some(path).filter(|path| {
+            &root_from_witness(leaf.clone(), position, path, combine) == expected_root
+        })
+    }
+
+    /// Returns an iterator over the witnesses for each marked position as of the current root of
+    /// the tree, in ascending order of position.
+    ///
+    /// This produces the same witnesses as calling [`BridgeTree::witness`] for each marked
+    /// position, but fuses the bridges that follow the marked positions in a single pass from the
+    /// tip of the tree, sharing the fused bridge between successive positions instead of fusing
+    /// all of the bridges to the right of each position independently.
+    pub fn witnesses(&self) -> impl Iterator<Item = (Position, Vec<H>)> + '_ {
+        let depth = self.depth.level().into();
+        let mut fuse_end = self.prior_bridges.len();
+        let mut tail = self.current_bridge.clone();
+        let mut witnesses = self
+            .saved
+            .iter()
+            .rev()
+            .filter_map(|(pos, idx)| {
+                let fused = MerkleBridge::fuse_all(
+                    self.prior_bridges[idx + 1..fuse_end].iter().chain(&tail),
+                )
+                .ok()??;
+                fuse_end = idx + 1;
+                tail = Some(fused);
+                let path = tail
+                    .as_ref()?
+                    .witness(depth, &self.prior_bridges[*idx].frontier, combine)
+                    .ok()?;
+                count!("bridgetree_witnesses");
+                Some((*pos, path))
+            })
+            .collect::<Vec<_>>();
+        witnesses.reverse();
+
+        #[cfg(debug_assertions)]
+        {
+            let root = self.uncounted_root();
+            for (pos, path) in &witnesses {
+                debug_assert!(
+                    root_from_witness(
+                        self.prior_bridges[self.saved[pos]].current_leaf().clone(),
+                        *pos,
+                        path,
+                        H::combine,
+                    ) == root,
+                    "Witness for position {:?} does not produce the current root.",
+                    pos
+                );
+            }
+        }
+
+        witnesses.into_iter()
+    }
+
+    /// Returns the position of the most recently appended leaf along with a witness for that
+    /// leaf as of the current root of the tree, or `None` if the tree is empty.
+    ///
+    /// Unlike marking the leaf, obtaining its witness and then removing the mark, this does not
+    /// modify the tree, and in particular does not add a bridge; every node to the right of the
+    /// tip is empty, so the witness is computed directly from the current bridge's frontier.
+    pub fn tip_path(&self) -> Option<(Position, Vec<H>)> {
+        let frontier = self.frontier()?;
+        let path = frontier
+            .witness(self.depth.level().into(), |addr| {
+                Some(H::empty_root(addr.level()))
+            })
+            .ok()?;
+        Some((frontier.position(), path))
     }
 
-    fn witness_inner(&self, position: Position, as_of_root: &H) -> Result<Vec<H>, WitnessingError> {
-        #[derive(Debug)]
-        enum AuthBase<'a> {
-            Current,
-            Checkpoint(usize, &'a Checkpoint),
-            NotFound,
+    /// Obtains an aggregated proof of inclusion for the leaves at each of the specified marked
+    /// positions as of the current root of the tree, in which the nodes shared between the
+    /// witnesses for those positions appear only once. Returns `None` if no positions are
+    /// specified, or if any of the specified positions is not marked.
+    pub fn multiproof(&self, positions: &[Position]) -> Option<MultiProof<H>> {
+        let root = self.root(0)?;
+        let depth = self.depth.level();
+        let positions = positions.iter().cloned().collect::<BTreeSet<_>>();
+        if positions.is_empty() {
+            return None;
+        }
+
+        let mut nodes = BTreeMap::new();
+        for pos in &positions {
+            let path = self.witness(*pos, &root)?;
+            for (level, node) in Level::from(0).iter_to(depth).zip(path) {
+                let addr = Address::from_parts(level, usize::from(*pos) >> u8::from(level));
+                nodes.insert(addr.sibling(), node);
+            }
         }
+        // nodes that can be computed from the proven leaves are omitted
+        nodes.retain(|addr, _| positions.range(addr.position_range()).next().is_none());
+
+        Some(MultiProof {
+            depth,
+            positions: positions.into_iter().collect(),
+            nodes: nodes.into_iter().collect(),
+        })
+    }
+
+    // Returns a witness to the value at the specified position along with the marked leaf
+    // value at that position.
+    fn witness_with_leaf(
+        &self,
+        position: Position,
+        as_of_root: &H,
+    ) -> Result<(Vec<H>, &H), WitnessingError> {
+        let (saved_idx, successor) = self.witness_base(position, as_of_root)?;
+        let prior_frontier = &self.prior_bridges[saved_idx].frontier;
+        let path = successor.witness(self.depth.level().into(), prior_frontier, combine)?;
+        count!("bridgetree_witnesses");
+        Ok((path, prior_frontier.leaf()))
+    }
 
-        let max_alt = Level::from(DEPTH);
+    // Returns the index of the bridge for the specified marked position, along with a bridge
+    // that has all of the data to the right of that position in the tree as of the tree state
+    // corresponding to the given root.
+    fn witness_base(
+        &self,
+        position: Position,
+        as_of_root: &H,
+    ) -> Result<(usize, MerkleBridge<H>), WitnessingError> {
+        let max_alt = self.depth.level();
 
         // Find the earliest checkpoint having a matching root, or the current
         // root if it matches and there is no earlier matching checkpoint.
@@ -1097,6 +3880,44 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
                 }
             });
 
+        self.witness_base_from(position, auth_base)
+    }
+
+    // Returns a witness to the value at the specified position as of the tree state at the
+    // specified checkpoint depth, where a depth of 0 refers to the current state of the tree.
+    // Unlike `witness`, this does not depend upon which of the tree's checkpoints share a root.
+    fn witness_at_checkpoint_depth(
+        &self,
+        position: Position,
+        checkpoint_depth: usize,
+    ) -> Option<Vec<H>> {
+        let auth_base = match checkpoint_depth {
+            0 => AuthBase::Current,
+            depth => {
+                let i = self.checkpoints.len().checked_sub(depth)?;
+                AuthBase::Checkpoint(i, &self.checkpoints[i])
+            }
+        };
+        let (saved_idx, successor) = self.witness_base_from(position, auth_base).ok()?;
+        let path = successor
+            .witness(
+                self.depth.level().into(),
+                &self.prior_bridges[saved_idx].frontier,
+                combine,
+            )
+            .ok()?;
+        count!("bridgetree_witnesses");
+        Some(path)
+    }
+
+    // Returns the index of the bridge for the specified marked position, along with a bridge
+    // that has all of the data to the right of that position in the tree as of the specified
+    // tree state.
+    fn witness_base_from(
+        &self,
+        position: Position,
+        auth_base: AuthBase<'_, M>,
+    ) -> Result<(usize, MerkleBridge<H>), WitnessingError> {
         let saved_idx = self
             .saved
             .get(&position)
@@ -1118,8 +3939,6 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
             })
             .ok_or(WitnessingError::PositionNotMarked(position))?;
 
-        let prior_frontier = &self.prior_bridges[*saved_idx].frontier;
-
         // Fuse the following bridges to obtain a bridge that has all
         // of the data to the right of the selected value in the tree,
         // up to the specified checkpoint depth.
@@ -1163,7 +3982,57 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
             }
         }?;
 
-        successor.witness(DEPTH, prior_frontier)
+        Ok((*saved_idx, successor))
+    }
+
+    /// Obtains a witness to the value at the specified position as of the tree state
+    /// corresponding to the given root, in the same fashion as [`BridgeTree::witness`], along
+    /// with a description of where each element of the witness was obtained. This is intended
+    /// as a diagnostic aid for witnesses that fail to verify.
+    pub fn explain_witness(&self, position: Position, as_of_root: &H) -> Option<WitnessTrace<H>> {
+        let (saved_idx, successor) = self.witness_base(position, as_of_root).ok()?;
+        let prior_frontier = &self.prior_bridges[saved_idx].frontier;
+        let tip = successor.position();
+        let nodes = position
+            .witness_addrs(self.depth.level())
+            .map(|(addr, source)| {
+                let (value, source) = match source {
+                    Source::Past(i) => (
+                        prior_frontier.ommers[i].clone(),
+                        WitnessSource::MarkedFrontier,
+                    ),
+                    Source::Future => {
+                        let range = addr.position_range();
+                        if tip < range.start {
+                            (H::empty_root(addr.level()), WitnessSource::EmptyRoot)
+                        } else if range.contains(&tip) {
+                            (
+                                successor.frontier.root(Some(addr.level())),
+                                WitnessSource::TipFrontier,
+                            )
+                        } else {
+                            let value = successor.ommers.get(&addr)?.clone();
+                            let bridge_idx = self.prior_bridges[saved_idx + 1..]
+                                .iter()
+                                .chain(&self.current_bridge)
+                                .position(|b| b.ommers.contains_key(&addr))?;
+                            (value, WitnessSource::Bridge(saved_idx + 1 + bridge_idx))
+                        }
+                    }
+                };
+                Some(WitnessTraceNode {
+                    address: addr,
+                    value,
+                    source,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(WitnessTrace {
+            position,
+            tip,
+            nodes,
+        })
     }
 
     /// Remove state from the tree that no longer needs to be maintained
@@ -1171,77 +4040,8 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
     /// have been removed from the tree at positions deeper than those
     /// reachable by calls to `rewind`.
     pub fn garbage_collect(&mut self) {
-        // Only garbage collect once we have more bridges than the maximum number of
-        // checkpoints; we cannot remove information that we might need to restore in
-        // a rewind.
-        if self.checkpoints.len() == self.max_checkpoints {
-            let gc_len = self.checkpoints.first().unwrap().bridges_len;
-            // Get a list of the leaf positions that we need to retain. This consists of
-            // all the saved leaves, plus all the leaves that have been forgotten since
-            // the most distant checkpoint to which we could rewind.
-            let remember: BTreeSet<Position> = self
-                .saved
-                .keys()
-                .chain(self.checkpoints.iter().flat_map(|c| c.forgotten.keys()))
-                .cloned()
-                .collect();
-
-            let mut cur: Option<MerkleBridge<H>> = None;
-            let mut merged = 0;
-            let mut ommer_addrs: BTreeSet<Address> = BTreeSet::new();
-            for (i, next_bridge) in std::mem::take(&mut self.prior_bridges)
-                .into_iter()
-                .enumerate()
-            {
-                if let Some(cur_bridge) = cur {
-                    let pos = cur_bridge.position();
-                    let mut new_cur = if remember.contains(&pos) || i > gc_len {
-                        // We need to remember cur_bridge; update its save index & put next_bridge
-                        // on the chopping block
-                        if let Some(idx) = self.saved.get_mut(&pos) {
-                            *idx -= merged;
-                        }
-
-                        // Add the elements of the auth path to the set of addresses we should
-                        // continue to track and retain information for
-                        for (addr, source) in cur_bridge
-                            .frontier
-                            .position()
-                            .witness_addrs(Level::from(DEPTH))
-                        {
-                            if source == Source::Future {
-                                ommer_addrs.insert(addr);
-                            }
-                        }
-
-                        self.prior_bridges.push(cur_bridge);
-                        next_bridge
-                    } else {
-                        // We can fuse these bridges together because we don't need to
-                        // remember next_bridge.
-                        merged += 1;
-                        cur_bridge.fuse(&next_bridge).unwrap()
-                    };
-
-                    new_cur.retain(&ommer_addrs);
-                    cur = Some(new_cur);
-                } else {
-                    // this case will only occur for the first bridge
-                    cur = Some(next_bridge);
-                }
-            }
-
-            // unwrap is safe because we know that prior_bridges was nonempty.
-            if let Some(last_bridge) = cur {
-                if let Some(idx) = self.saved.get_mut(&last_bridge.position()) {
-                    *idx -= merged;
-                }
-                self.prior_bridges.push(last_bridge);
-            }
-
-            for c in self.checkpoints.iter_mut() {
-                c.rewrite_indices(|idx| idx - merged);
-            }
+        if let Some(gc_len) = self.gc_len() {
+            self.collect_bridges(gc_len);
         }
         if let Err(e) = self.check_consistency() {
             panic!(
@@ -1249,71 +4049,432 @@ impl<H: Hashable + Ord + Clone, const DEPTH: u8> BridgeTree<H, DEPTH> {
                 e
             );
         }
+        debug_assert!(
+            self.verify_root_consistency(),
+            "Root consistency check failed after garbage collection."
+        );
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use proptest::prelude::*;
 
-    use super::*;
-    use crate::testing::{apply_operation, arb_operation, tests, Frontier, Tree};
+    /// Returns an estimate of the number of bridges that a call to
+    /// [`BridgeTree::garbage_collect`] would fuse away, and of the memory that they occupy.
+    ///
+    /// Garbage collection also discards ommers from the remaining bridges that are no longer
+    /// required, so the memory reclaimed may be somewhat larger than this estimate.
+    pub fn gc_stats(&self) -> GcStats {
+        let empty = GcStats {
+            index_bytes: self.marked_values_memory_usage(),
+            ..GcStats::default()
+        };
+        let gc_len = match self.gc_len() {
+            Some(gc_len) => gc_len,
+            None => return empty,
+        };
+        let remember = self.retained_positions();
+        // Each bridge prior to the bridge at `gc_len - 1` that does not end at a retained
+        // position is fused with its successor.
+        let collectable = self.prior_bridges[..gc_len.saturating_sub(1)]
+            .iter()
+            .filter(|b| !remember.contains(&b.position()));
+        collectable.fold(empty, |stats, b| GcStats {
+            bridges: stats.bridges + 1,
+            bytes: stats.bytes + size_of::<MerkleBridge<H>>() + b.dynamic_memory_usage(),
+            ..stats
+        })
+    }
 
-    impl<H: Hashable + Clone, const DEPTH: u8> Frontier<H> for super::Frontier<H, DEPTH> {
-        fn append(&mut self, value: &H) -> bool {
-            super::Frontier::append(self, value)
+    /// Returns the number of prior bridges that are eligible for garbage collection, or `None`
+    /// if garbage collection cannot yet be performed.
+    fn gc_len(&self) -> Option<usize> {
+        // Only garbage collect once we have more bridges than the maximum number of
+        // checkpoints; we cannot remove information that we might need to restore in
+        // a rewind.
+        if self.checkpoints.len() == self.max_checkpoints {
+            // If checkpointing is disabled, every bridge is eligible for collection.
+            Some(
+                self.checkpoints
+                    .first()
+                    .map_or(self.prior_bridges.len(), |c| c.bridges_len),
+            )
+        } else {
+            None
         }
+    }
 
-        fn root(&self) -> H {
-            super::Frontier::root(self)
-        }
+    /// Returns the positions whose bridges must be retained by garbage collection: the marked
+    /// positions, along with the positions that have been forgotten since the most distant
+    /// checkpoint to which we could rewind.
+    fn retained_positions(&self) -> BTreeSet<Position> {
+        self.saved
+            .keys()
+            .chain(self.checkpoints.iter().flat_map(|c| c.forgotten.keys()))
+            .cloned()
+            .collect()
     }
 
-    impl<H: Hashable + Ord + Clone, const DEPTH: u8> Tree<H> for BridgeTree<H, DEPTH> {
-        fn append(&mut self, value: &H) -> bool {
-            BridgeTree::append(self, value)
+    /// Removes all checkpoints from the tree and then discards every bridge that
+    /// is not required to produce witnesses for the currently marked positions.
+    ///
+    /// The resulting tree retains the same root, and can produce the same witnesses
+    /// to its marked positions as of that root, but can no longer be rewound. This
+    /// is useful for archiving the minimal state required to continue maintaining
+    /// witnesses for a set of marked leaves.
+    pub fn prune_to_marks(&mut self) {
+        self.checkpoints.clear();
+        self.retain_restorable_mark_meta();
+        self.collect_bridges(self.prior_bridges.len());
+        if let Err(e) = self.check_consistency() {
+            panic!("Consistency check failed after pruning with {:?}", e);
         }
+        debug_assert!(
+            self.verify_root_consistency(),
+            "Root consistency check failed after pruning."
+        );
+    }
 
-        fn current_position(&self) -> Option<Position> {
-            BridgeTree::current_position(self)
-        }
+    /// Fuses together all bridges prior to `gc_len` that are not required to
+    /// produce a witness for a marked position or for a position forgotten since
+    /// the oldest checkpoint, and updates the bridge indices stored in `saved` and
+    /// in the checkpoints accordingly.
+    fn collect_bridges(&mut self, gc_len: usize) {
+        let remember = self.retained_positions();
+
+        let mut cur: Option<MerkleBridge<H>> = None;
+        let mut merged = 0;
+        // The number of bridges fused away prior to each retained bridge, by the bridge's
+        // original index.
+        let mut merged_before: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut ommer_addrs: BTreeSet<Address> = BTreeSet::new();
+        for (i, next_bridge) in core::mem::take(&mut self.prior_bridges)
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(cur_bridge) = cur {
+                let pos = cur_bridge.position();
+                // The bridge at `gc_len - 1` is the state of the tree as of the oldest
+                // checkpoint, and so must not be fused with its successor.
+                let mut new_cur = if remember.contains(&pos) || i >= gc_len {
+                    // We need to remember cur_bridge; update its save index & put next_bridge
+                    // on the chopping block
+                    if let Some(idx) = self.saved.get_mut(&pos) {
+                        *idx -= merged;
+                    }
+                    merged_before.insert(i - 1, merged);
+
+                    // Add the elements of the auth path to the set of addresses we should
+                    // continue to track and retain information for
+                    for (addr, source) in cur_bridge
+                        .frontier
+                        .position()
+                        .witness_addrs(self.depth.level())
+                    {
+                        if source == Source::Future {
+                            ommer_addrs.insert(addr);
+                        }
+                    }
 
-        fn current_leaf(&self) -> Option<&H> {
-            BridgeTree::current_leaf(self)
-        }
-
-        fn get_marked_leaf(&self, position: Position) -> Option<&H> {
-            BridgeTree::get_marked_leaf(self, position)
+                    self.prior_bridges.push(cur_bridge);
+                    next_bridge
+                } else {
+                    // We can fuse these bridges together because we don't need to
+                    // remember next_bridge.
+                    merged += 1;
+                    cur_bridge.fuse(next_bridge).unwrap()
+                };
+
+                new_cur.retain(&ommer_addrs);
+                cur = Some(new_cur);
+            } else {
+                // this case will only occur for the first bridge
+                cur = Some(next_bridge);
+            }
         }
 
-        fn mark(&mut self) -> Option<Position> {
-            BridgeTree::mark(self)
+        // unwrap is safe because we know that prior_bridges was nonempty.
+        if let Some(last_bridge) = cur {
+            if let Some(idx) = self.saved.get_mut(&last_bridge.position()) {
+                *idx -= merged;
+            }
+            merged_before.insert(self.prior_bridges.len() + merged, merged);
+            self.prior_bridges.push(last_bridge);
         }
 
-        fn marked_positions(&self) -> BTreeSet<Position> {
-            BridgeTree::marked_positions(self)
+        // Checkpoints only refer to retained bridges; bridges that were forgotten since the
+        // oldest checkpoint are retained, but may precede bridges that have been fused.
+        for c in self.checkpoints.iter_mut() {
+            c.rewrite_indices(|idx| idx - merged_before.get(&idx).copied().unwrap_or(merged));
         }
+    }
 
-        fn root(&self, checkpoint_depth: usize) -> Option<H> {
-            BridgeTree::root(self, checkpoint_depth)
-        }
+    /// Verifies that the incrementally maintained root of the tree agrees with the roots that
+    /// can be independently recomputed from the tree's retained data.
+    ///
+    /// Leaf values that have not been marked are not retained by the tree, so the root cannot be
+    /// recomputed by replaying every leaf; instead, for each marked position this recomputes the
+    /// root from scratch by hashing the marked leaf together with its witness, and checks that
+    /// the result is equal to the current root. This is intended as a safety net for testing
+    /// and debugging, and is expensive for trees with many marked positions.
+    ///
+    /// The hashes computed by this check are not included in the `bridgetree_combines` metric,
+    /// and the witnesses it computes are not included in the `bridgetree_witnesses` metric.
+    pub fn verify_root_consistency(&self) -> bool {
+        let root = self.uncounted_root();
+        self.saved.iter().all(|(pos, idx)| {
+            let leaf = self.prior_bridges[*idx].current_leaf();
+            match self.uncounted_witness(*pos) {
+                Some(path) => root_from_witness(leaf.clone(), *pos, &path, H::combine) == root,
+                None => false,
+            }
+        })
+    }
+
+    // Returns the current root of the tree, as `root(0)` does, without counting the hashes
+    // computed.
+    fn uncounted_root(&self) -> H {
+        let root_level = self.depth.level();
+        self.current_bridge
+            .as_ref()
+            .map_or(H::empty_root(root_level), |bridge| {
+                bridge.frontier().root_with(Some(root_level), H::combine)
+            })
+    }
 
-        fn witness(&self, position: Position, as_of_root: &H) -> Option<Vec<H>> {
-            BridgeTree::witness(self, position, as_of_root)
+    // Returns a witness to the value at the specified marked position as of the current root of
+    // the tree, without counting either the witness or the hashes computed.
+    fn uncounted_witness(&self, position: Position) -> Option<Vec<H>> {
+        let (saved_idx, successor) = self.witness_base_from(position, AuthBase::Current).ok()?;
+        successor
+            .witness(
+                self.depth.level().into(),
+                &self.prior_bridges[saved_idx].frontier,
+                H::combine,
+            )
+            .ok()
+    }
+}
+
+/// Operations that treat the metadata associated with each mark as the identifier of a group
+/// of marks, such as the account for which a wallet is tracking a note. A mark created with
+/// [`BridgeTree::mark`] belongs to no group.
+impl<H: Hashable + Ord + Clone, D: Depth, M, W: PartialEq> DepthBridgeTree<H, D, M, W> {
+    /// Marks the current leaf as a member of the specified group. If the leaf is already
+    /// marked, it is moved to the specified group. Returns the current position if successful,
+    /// or None if the tree is empty.
+    pub fn mark_in_group(&mut self, group: W) -> Option<Position> {
+        self.mark_with(group)
+    }
+
+    /// Returns an iterator over the marked positions belonging to the specified group, in
+    /// ascending order, along with the leaf value at each position.
+    pub fn marked_leaves_in_group<'a>(
+        &'a self,
+        group: &'a W,
+    ) -> impl Iterator<Item = (Position, &'a H)> + 'a {
+        self.marked_leaves()
+            .filter(move |(_, _, meta)| *meta == Some(group))
+            .map(|(pos, leaf, _)| (pos, leaf))
+    }
+
+    /// Removes every mark belonging to the specified group, leaving the marks of all other
+    /// groups untouched. Returns the number of marks that were removed.
+    ///
+    /// As with [`BridgeTree::remove_mark`], the removed marks will be restored by a `rewind`
+    /// to a checkpoint at which they existed.
+    pub fn remove_group(&mut self, group: &W) -> usize {
+        let members = self
+            .marked_leaves_in_group(group)
+            .map(|(pos, _)| pos)
+            .collect::<BTreeSet<_>>();
+        if members.is_empty() {
+            0
+        } else {
+            self.retain_marks(|pos, _| !members.contains(&pos))
         }
+    }
+
+    /// Obtains witnesses for each of the marked positions belonging to the specified group,
+    /// as of the tree state corresponding to the given root. Positions for which no witness
+    /// can be produced as of that root, such as those marked after the state it identifies,
+    /// are omitted from the result.
+    pub fn witnesses_in_group(&self, group: &W, as_of_root: &H) -> BTreeMap<Position, Vec<H>> {
+        self.marked_leaves_in_group(group)
+            .filter_map(|(pos, _)| self.witness(pos, as_of_root).map(|path| (pos, path)))
+            .collect()
+    }
+}
+
+/// An aggregated proof of inclusion for a set of leaves in a Merkle tree.
+///
+/// The witnesses for nearby leaves share most of their nodes, and the nodes of each witness
+/// that lie on the path from another proven leaf to the root can be computed from that leaf.
+/// A multiproof contains each node required to compute the root from the proven leaves exactly
+/// once, and so is smaller than the witnesses for those leaves taken individually.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProof<H> {
+    depth: Level,
+    positions: Vec<Position>,
+    nodes: Vec<(Address, H)>,
+}
+
+impl<H> MultiProof<H> {
+    /// Returns the level of the root of the tree for which this proof was produced.
+    pub fn depth(&self) -> Level {
+        self.depth
+    }
+
+    /// Returns the positions of the leaves proven by this proof, in ascending order.
+    pub fn positions(&self) -> &[Position] {
+        &self.positions
+    }
+
+    /// Returns the nodes of the tree that are required, in addition to the proven leaves, to
+    /// compute the root of the tree, in ascending order of address.
+    pub fn nodes(&self) -> &[(Address, H)] {
+        &self.nodes
+    }
+}
 
-        fn remove_mark(&mut self, position: Position) -> bool {
-            BridgeTree::remove_mark(self, position)
+impl<H: Hashable + Clone + PartialEq> MultiProof<H> {
+    /// Verifies that the specified leaves, which must be exactly those at the positions
+    /// proven by this proof, are included in the tree having the given root.
+    ///
+    /// Verification fails if any node of the proof is not required to compute the root.
+    pub fn verify(&self, leaves: &[(Position, H)], root: &H) -> bool {
+        let mut current: BTreeMap<Address, H> = leaves
+            .iter()
+            .map(|(pos, leaf)| (Address::from(pos), leaf.clone()))
+            .collect();
+        if current.len() != leaves.len()
+            || !current
+                .keys()
+                .map(|addr| Position::from(addr.index()))
+                .eq(self.positions.iter().cloned())
+        {
+            return false;
         }
 
-        fn checkpoint(&mut self) {
-            BridgeTree::checkpoint(self)
+        let mut nodes: BTreeMap<Address, &H> = self
+            .nodes
+            .iter()
+            .map(|(addr, node)| (*addr, node))
+            .collect();
+        if nodes.len() != self.nodes.len() {
+            return false;
         }
 
-        fn rewind(&mut self) -> bool {
-            BridgeTree::rewind(self)
+        for level in Level::from(0).iter_to(self.depth) {
+            let mut parents = BTreeMap::new();
+            for (addr, digest) in &current {
+                if parents.contains_key(&addr.parent()) {
+                    // both children of the parent are proven, and it has already been computed
+                    continue;
+                }
+                let sibling = match current.get(&addr.sibling()) {
+                    Some(node) => node,
+                    None => match nodes.remove(&addr.sibling()) {
+                        Some(node) => node,
+                        None => return false,
+                    },
+                };
+                let parent = if addr.is_complete_node() {
+                    combine(level, sibling, digest)
+                } else {
+                    combine(level, digest, sibling)
+                };
+                parents.insert(addr.parent(), parent);
+            }
+            current = parents;
         }
+
+        nodes.is_empty()
+            && current.len() == 1
+            && current.get(&Address::from_parts(self.depth, 0)) == Some(root)
+    }
+}
+
+/// The source from which an element of a witness was obtained; see
+/// [`BridgeTree::explain_witness`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WitnessSource {
+    /// An ommer of the frontier of the bridge for the marked position.
+    MarkedFrontier,
+    /// A node that was completed by leaves appended after the marked position, retained by the
+    /// bridge at the given index, where an index equal to the number of prior bridges refers to
+    /// the current bridge.
+    Bridge(usize),
+    /// The root of the incomplete subtree containing the tip of the tree as of the state for
+    /// which the witness was produced.
+    TipFrontier,
+    /// The root of an empty subtree to the right of the tip of the tree.
+    EmptyRoot,
+}
+
+/// An element of a [`WitnessTrace`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessTraceNode<H> {
+    /// The address of the node.
+    pub address: Address,
+    /// The value of the node.
+    pub value: H,
+    /// The source from which the value of the node was obtained.
+    pub source: WitnessSource,
+}
+
+/// A witness for a marked position, along with the source of each of its elements, as
+/// returned by [`BridgeTree::explain_witness`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WitnessTrace<H> {
+    /// The marked position.
+    pub position: Position,
+    /// The position of the tip of the tree as of the state for which the witness was produced.
+    pub tip: Position,
+    /// The elements of the witness, beginning with the sibling of the marked leaf.
+    pub nodes: Vec<WitnessTraceNode<H>>,
+}
+
+impl<H: Clone> WitnessTrace<H> {
+    /// Returns the witness described by this trace.
+    pub fn path(&self) -> Vec<H> {
+        self.nodes.iter().map(|n| n.value.clone()).collect()
+    }
+}
+
+/// Computes the root of a Merkle tree by hashing the specified leaf value together with the
+/// elements of its witness, beginning with the sibling of the leaf, using the specified function
+/// to combine nodes.
+fn root_from_witness<H: Hashable>(
+    leaf: H,
+    position: Position,
+    path: &[H],
+    combine: fn(Level, &H, &H) -> H,
+) -> H {
+    let mut digest = leaf;
+    let mut addr = Address::from(position);
+    for sibling in path {
+        digest = if addr.is_complete_node() {
+            combine(addr.level(), sibling, &digest)
+        } else {
+            combine(addr.level(), &digest, sibling)
+        };
+        addr = addr.parent();
     }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    // the property tests are not run under wasm-bindgen-test, which leaves their helpers unused
+    #![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::{
+        apply_operation, arb_operation, run_tree_conformance, tests, Counting, Frontier, Operation,
+        SipHashable, Tree,
+    };
 
     #[test]
     fn nonempty_frontier_root() {
@@ -1327,6 +4488,169 @@ mod tests {
         assert_eq!(frontier.root(None), "abc_");
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn frontier_build_parallel() {
+        use crate::testing::SipHashable;
+
+        let values: Vec<SipHashable> = (0..4096).map(SipHashable).collect();
+        for len in [0, 1, 2, 3, 7, 64, 1000, 4095, 4096] {
+            let mut expected = super::Frontier::<SipHashable, 12>::empty();
+            for value in &values[..len] {
+                assert!(expected.append(value));
+            }
+            for threads in [0, 1, 3, 4, 8, 5000] {
+                let frontier =
+                    super::Frontier::<SipHashable, 12>::build_parallel(&values[..len], threads)
+                        .unwrap();
+                assert_eq!(frontier, expected);
+                assert_eq!(frontier.root(), expected.root());
+            }
+        }
+
+        let mut values = values;
+        values.push(SipHashable(4096));
+        assert_eq!(
+            super::Frontier::<SipHashable, 12>::build_parallel(&values, 4),
+            Err(FrontierError::MaxDepthExceeded { depth: 13 })
+        );
+    }
+
+    #[test]
+    fn combine_batch() {
+        use crate::testing::SipHashable;
+
+        let pairs: Vec<(SipHashable, SipHashable)> = (0..9)
+            .map(|i| (SipHashable(2 * i), SipHashable(2 * i + 1)))
+            .collect();
+        for level in [0u8, 3, 31] {
+            let level = Level::from(level);
+            assert_eq!(
+                SipHashable::combine_batch(level, &pairs),
+                pairs
+                    .iter()
+                    .map(|(a, b)| SipHashable::combine(level, a, b))
+                    .collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(
+            String::combine_batch(Level::from(0), &[]),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn empty_roots() {
+        use crate::testing::SipHashable;
+
+        assert_eq!(
+            super::empty_roots::<String>(Level::from(0)),
+            vec!["_".to_string()]
+        );
+        assert_eq!(
+            super::empty_roots::<String>(Level::from(3)),
+            vec!["_", "__", "____", "________"]
+        );
+
+        let roots = super::empty_roots::<SipHashable>(Level::from(32));
+        assert_eq!(roots.len(), 33);
+        for (k, root) in roots.iter().enumerate() {
+            assert_eq!(root, &SipHashable::empty_root(Level::from(k as u8)));
+        }
+    }
+
+    #[test]
+    fn nonempty_frontier_eq_compares_position_first() {
+        use std::cell::Cell;
+
+        thread_local! {
+            static EQ_COUNT: Cell<usize> = Cell::new(0);
+        }
+
+        #[derive(Clone, Debug, Eq)]
+        struct Node(String);
+
+        impl PartialEq for Node {
+            fn eq(&self, other: &Self) -> bool {
+                EQ_COUNT.with(|c| c.set(c.get() + 1));
+                self.0 == other.0
+            }
+        }
+
+        let node = |c: char| Node(c.to_string().repeat(1024));
+        let a =
+            NonEmptyFrontier::from_parts(Position::from(1), node('b'), vec![node('a')]).unwrap();
+        let b =
+            NonEmptyFrontier::from_parts(Position::from(2), node('b'), vec![node('a')]).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(EQ_COUNT.with(|c| c.get()), 0);
+
+        assert_eq!(a, a.clone());
+        assert_eq!(EQ_COUNT.with(|c| c.get()), 2);
+    }
+
+    #[test]
+    fn nonempty_frontier_is_structurally_valid() {
+        let mut frontier = NonEmptyFrontier::new("a".to_string());
+        assert!(frontier.is_structurally_valid());
+        for c in 'b'..='k' {
+            frontier.append(c.to_string());
+            assert!(frontier.is_structurally_valid());
+        }
+
+        let mut corrupted = frontier.clone();
+        corrupted.ommers.pop();
+        assert!(!corrupted.is_structurally_valid());
+
+        // deserialization rejects a frontier with the wrong number of ommers
+        let mut value = serde_json::to_value(&frontier).unwrap();
+        assert_eq!(
+            serde_json::from_value::<NonEmptyFrontier<String>>(value.clone()).unwrap(),
+            frontier
+        );
+        value["ommers"].as_array_mut().unwrap().pop();
+        assert_eq!(
+            serde_json::from_value::<NonEmptyFrontier<String>>(value)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "a frontier at {:?} requires 2 ommers, found 1",
+                frontier.position()
+            )
+        );
+        assert_eq!(
+            NonEmptyFrontier::from_parts(
+                corrupted.position(),
+                corrupted.leaf().clone(),
+                corrupted.ommers().to_vec()
+            ),
+            Err(FrontierError::PositionMismatch { expected_ommers: 2 })
+        );
+    }
+
+    #[test]
+    fn frontier_ommer_at() {
+        let mut frontier = NonEmptyFrontier::new("a".to_string());
+        assert_eq!(frontier.rightmost_ommer(), None);
+        assert_eq!(frontier.ommer_at(Level::from(0)), None);
+
+        for c in 'b'..='k' {
+            frontier.append(c.to_string());
+        }
+        // position 10 = 0b1010, so there are ommers at levels 1 and 3
+        assert_eq!(frontier.position(), Position::from(10));
+        assert_eq!(frontier.ommer_at(Level::from(0)), None);
+        assert_eq!(frontier.ommer_at(Level::from(1)), Some(&"ij".to_string()));
+        assert_eq!(frontier.ommer_at(Level::from(2)), None);
+        assert_eq!(
+            frontier.ommer_at(Level::from(3)),
+            Some(&"abcdefgh".to_string())
+        );
+        assert_eq!(frontier.ommer_at(Level::from(4)), None);
+        assert_eq!(frontier.ommer_at(Level::from(255)), None);
+        assert_eq!(frontier.rightmost_ommer(), Some(&"ij".to_string()));
+    }
+
     #[test]
     fn frontier_from_parts() {
         assert!(super::Frontier::<(), 1>::from_parts(0.into(), (), vec![]).is_ok());
@@ -1350,6 +4674,266 @@ mod tests {
         assert_eq!(frontier.root(), "abc_____________");
     }
 
+    #[test]
+    fn depth_zero_root() {
+        // a tree of depth zero holds a single leaf, which is its root
+        let mut frontier: super::Frontier<String, 0> = super::Frontier::empty();
+        assert_eq!(frontier.root(), "_");
+        assert!(frontier.append(&"a".to_string()));
+        assert_eq!(frontier.root(), "a");
+        assert!(!frontier.append(&"b".to_string()));
+        assert_eq!(frontier.root(), "a");
+
+        let mut t = BridgeTree::<String, 0>::new(10);
+        assert_eq!(t.root(0), Some("_".to_string()));
+        t.checkpoint();
+        assert!(t.append(&"a".to_string()));
+        let pos = t.mark().unwrap();
+        t.checkpoint();
+        assert!(!t.append(&"b".to_string()));
+        assert_eq!(t.root(0), Some("a".to_string()));
+        assert_eq!(t.root(1), Some("a".to_string()));
+        assert_eq!(t.root(2), Some("_".to_string()));
+        assert_eq!(t.witness(pos, &"a".to_string()), Some(vec![]));
+        assert!(t.rewind());
+        assert!(t.rewind());
+        assert_eq!(t.root(0), Some("_".to_string()));
+    }
+
+    #[test]
+    fn depth_one_and_two() {
+        // a tree of depth one is full after its second append
+        let mut t = BridgeTree::<String, 1>::new(10);
+        assert_eq!(t.root(0), Some("__".to_string()));
+        assert!(t.append(&"a".to_string()));
+        assert_eq!(t.mark(), Some(0.into()));
+        t.checkpoint();
+        assert!(t.append(&"b".to_string()));
+        assert!(!t.append(&"c".to_string()));
+        assert_eq!(t.current_position(), Some(1.into()));
+        assert_eq!(t.mark(), Some(1.into()));
+        assert_eq!(t.root(0), Some("ab".to_string()));
+        assert_eq!(t.root(1), Some("a_".to_string()));
+        assert_eq!(
+            t.witness(0.into(), &"ab".to_string()),
+            Some(vec!["b".to_string()])
+        );
+        assert_eq!(
+            t.witness(0.into(), &"a_".to_string()),
+            Some(vec!["_".to_string()])
+        );
+        assert_eq!(
+            t.witness(1.into(), &"ab".to_string()),
+            Some(vec!["a".to_string()])
+        );
+        assert_eq!(t.tip_path(), Some((1.into(), vec!["a".to_string()])));
+        assert!(t.rewind());
+        assert_eq!(t.root(0), Some("a_".to_string()));
+        assert_eq!(t.marked_positions(), BTreeSet::from([0.into()]));
+        assert!(t.append(&"c".to_string()));
+        assert_eq!(t.root(0), Some("ac".to_string()));
+
+        // a tree of depth two is full after its fourth append
+        let mut t = BridgeTree::<String, 2>::new(10);
+        for c in 'a'..='d' {
+            assert!(t.append(&c.to_string()));
+            if c == 'b' {
+                t.mark();
+                t.checkpoint();
+            }
+        }
+        assert!(!t.append(&"e".to_string()));
+        assert_eq!(t.root(0), Some("abcd".to_string()));
+        assert_eq!(t.root(1), Some("ab__".to_string()));
+        assert_eq!(
+            t.witness(1.into(), &"abcd".to_string()),
+            Some(vec!["a".to_string(), "cd".to_string()])
+        );
+        assert_eq!(
+            t.witness(1.into(), &"ab__".to_string()),
+            Some(vec!["a".to_string(), "__".to_string()])
+        );
+        assert_eq!(
+            t.tip_path(),
+            Some((3.into(), vec!["c".to_string(), "ab".to_string()]))
+        );
+        assert!(t.rewind());
+        assert_eq!(t.root(0), Some("ab__".to_string()));
+        assert!(t.append(&"e".to_string()));
+        assert_eq!(t.root(0), Some("abe_".to_string()));
+    }
+
+    #[test]
+    fn fold_carry() {
+        fn fold(ommers: &[(u8, &str)], carry: &str, level: u8) -> Vec<String> {
+            let mut values = ommers.iter().map(|(_, v)| v.to_string()).collect();
+            let levels = ommers.iter().map(|(l, _)| Level::from(*l));
+            super::fold_carry(&mut values, carry.to_string(), level.into(), levels);
+            values
+        }
+
+        assert_eq!(fold(&[], "a", 0), vec!["a"]);
+        assert_eq!(fold(&[(0, "a")], "b", 0), vec!["ab"]);
+        assert_eq!(fold(&[(1, "ab")], "c", 0), vec!["c", "ab"]);
+        assert_eq!(fold(&[(0, "c"), (1, "ab")], "d", 0), vec!["abcd"]);
+        assert_eq!(fold(&[(0, "c"), (2, "abcd")], "d", 0), vec!["cd", "abcd"]);
+
+        // a carry above level 0 leaves lower ommers in place
+        assert_eq!(fold(&[(0, "e")], "cd", 1), vec!["e", "cd"]);
+        assert_eq!(
+            fold(&[(0, "m"), (2, "ijkl"), (3, "abcdefgh")], "mnop", 2),
+            vec!["m", "abcdefghijklmnop"]
+        );
+        assert_eq!(
+            fold(&[(2, "ijkl"), (4, "abcdefghijklmnop")], "mnop", 2),
+            vec!["ijklmnop", "abcdefghijklmnop"]
+        );
+    }
+
+    // panics abort on wasm32-unknown-unknown, so they cannot be caught
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn frontier_append_panic_safety() {
+        let mut frontier = NonEmptyFrontier::new(Counting("a".to_string()));
+        for c in 'b'..='h' {
+            frontier.append(Counting(c.to_string()));
+        }
+
+        // appending the ninth leaf carries through three levels, and a panic in any of the
+        // resulting calls to `combine` leaves the frontier unchanged
+        let expected = frontier.clone();
+        for n in 1..=3 {
+            Counting::reset_counts();
+            Counting::panic_on_combine(n);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                frontier.append(Counting("i".to_string()))
+            }));
+            assert!(result.is_err());
+            assert_eq!(frontier, expected);
+        }
+
+        Counting::reset_counts();
+        frontier.append(Counting("i".to_string()));
+        assert_eq!(Counting::combine_count(), 3);
+        assert_eq!(frontier.position(), Position::from(8));
+        assert_eq!(frontier.ommers(), &[Counting("abcdefgh".to_string())]);
+    }
+
+    #[test]
+    fn frontier_append_owned() {
+        let mut frontier: super::Frontier<Counting, 4> = super::Frontier::empty();
+        for c in 'a'..'h' {
+            Counting::reset_counts();
+            assert!(frontier.append_owned(Counting(c.to_string())));
+            // the prior leaf moves directly into the ommers, or is folded into the carry along
+            // with the prior ommers, without being cloned
+            assert_eq!(Counting::clone_count(), 0);
+        }
+        assert_eq!(frontier.root().0, "abcdefg_________");
+
+        // appending by reference requires exactly one clone of the appended value
+        Counting::reset_counts();
+        assert!(frontier.append(&Counting("h".to_string())));
+        assert_eq!(Counting::clone_count(), 1);
+    }
+
+    #[test]
+    fn tree_append_owned() {
+        let mut tree = BridgeTree::<Counting, 4>::new(100);
+        Counting::reset_counts();
+        for c in 'a'..'e' {
+            assert!(tree.append_owned(Counting(c.to_string())));
+        }
+        assert_eq!(Counting::clone_count(), 0);
+        assert_eq!(tree.root(0).unwrap().0, "abcd____________");
+    }
+
+    #[test]
+    fn fuse_clone_count() {
+        let build = || {
+            let mut tree = BridgeTree::<Counting, 6>::new(1);
+            for c in 'a'..='z' {
+                tree.append_owned(Counting(c.to_string()));
+                tree.mark();
+            }
+            tree
+        };
+
+        // witnessing the first leaf fuses each of the subsequent bridges
+        let tree = build();
+        let root = tree.root(0).unwrap();
+        Counting::reset_counts();
+        assert!(tree.witness(Position::from(0), &root).is_some());
+        assert_eq!(Counting::clone_count(), 34);
+
+        // garbage collection fuses the bridges for the removed marks
+        let mut tree = build();
+        for pos in 1..25 {
+            assert!(tree.remove_mark(Position::from(pos)));
+        }
+        tree.checkpoint();
+        Counting::reset_counts();
+        tree.garbage_collect();
+        assert_eq!(Counting::clone_count(), 23);
+        assert_eq!(tree.prior_bridges().len(), 2);
+        assert_eq!(
+            tree.witness(Position::from(0), &root).map(|p| p.len()),
+            Some(6)
+        );
+    }
+
+    #[test]
+    fn trait_append_owned() {
+        fn check<T: Tree<Counting>>(mut tree: T) {
+            Counting::reset_counts();
+            for c in 'a'..'e' {
+                assert!(tree.append_owned(Counting(c.to_string())));
+            }
+            assert_eq!(Counting::clone_count(), 0);
+            assert_eq!(tree.root(0).unwrap().0, "abcd____________");
+        }
+        check(BridgeTree::<Counting, 4>::new(100));
+        check(DynBridgeTree::<Counting>::new(Level::from(4), 100));
+
+        let mut frontier = super::Frontier::<Counting, 4>::empty();
+        Counting::reset_counts();
+        for c in 'a'..'e' {
+            assert!(Frontier::append_owned(
+                &mut frontier,
+                Counting(c.to_string())
+            ));
+        }
+        assert_eq!(Counting::clone_count(), 0);
+        assert_eq!(Frontier::root(&frontier).0, "abcd____________");
+    }
+
+    #[test]
+    fn append_cost_bounded_by_depth() {
+        // Returns the number of hashes performed while appending 256 leaves to a tree in which
+        // the first `marked` of the 256 leaves previously appended were marked.
+        fn append_cost(marked: usize) -> usize {
+            let mut tree = BridgeTree::<Counting, 16>::new(100);
+            for i in 0..256 {
+                tree.append_owned(Counting(format!("{},", i)));
+                if i < marked {
+                    tree.mark();
+                }
+            }
+            Counting::reset_counts();
+            for i in 256..512 {
+                tree.append_owned(Counting(format!("{},", i)));
+            }
+            Counting::combine_count()
+        }
+
+        // Once the paths of the marked leaves have been completed up to a common ancestor,
+        // their tracked addresses coincide, and so the cost of appending no longer depends
+        // upon the number of marked leaves.
+        let cost = append_cost(1);
+        assert_eq!(append_cost(16), cost);
+        assert_eq!(append_cost(256), cost);
+    }
+
     #[test]
     fn frontier_witness() {
         let mut frontier = NonEmptyFrontier::<String>::new("a".to_string());
@@ -1370,6 +4954,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bridge_from_frontier() {
+        let mut frontier = NonEmptyFrontier::new("a".to_string());
+        for c in 'b'..'f' {
+            frontier.append(c.to_string());
+        }
+
+        let bridge = MerkleBridge::from(frontier.clone());
+        assert_eq!(bridge.prior_position(), None);
+        assert!(bridge.tracking().is_empty());
+        assert!(bridge.ommers().is_empty());
+        assert_eq!(bridge.position(), frontier.position());
+        assert_eq!(bridge.frontier().root(None), frontier.root(None));
+        assert_eq!(
+            bridge.frontier().root(Some(4.into())),
+            frontier.root(Some(4.into()))
+        );
+    }
+
+    #[test]
+    fn bridge_root_at_level() {
+        let mut bridge = MerkleBridge::new("a".to_string());
+        assert_eq!(bridge.root_at_level(0.into()), Some("a".to_string()));
+        assert_eq!(bridge.root_at_level(1.into()), None);
+
+        for c in 'b'..='d' {
+            bridge.append(c.to_string());
+        }
+        assert_eq!(bridge.root_at_level(0.into()), Some("d".to_string()));
+        assert_eq!(bridge.root_at_level(1.into()), Some("cd".to_string()));
+        assert_eq!(bridge.root_at_level(2.into()), Some("abcd".to_string()));
+        assert_eq!(bridge.root_at_level(3.into()), None);
+
+        bridge.append("e".to_string());
+        bridge.append("f".to_string());
+        assert_eq!(bridge.root_at_level(1.into()), Some("ef".to_string()));
+        assert_eq!(bridge.root_at_level(2.into()), None);
+    }
+
     #[test]
     fn tree_depth() {
         let mut tree = BridgeTree::<String, 3>::new(100);
@@ -1379,62 +5002,2847 @@ mod tests {
         assert!(!tree.append(&'i'.to_string()));
     }
 
-    fn arb_bridgetree<G: Strategy + Clone>(
-        item_gen: G,
-        max_count: usize,
-    ) -> impl Strategy<Value = BridgeTree<G::Value, 8>>
-    where
-        G::Value: Hashable + Ord + Clone + Debug + 'static,
-    {
-        proptest::collection::vec(arb_operation(item_gen, 0..max_count), 0..max_count).prop_map(
-            |ops| {
-                let mut tree: BridgeTree<G::Value, 8> = BridgeTree::new(10);
-                for op in ops {
-                    apply_operation(&mut tree, op);
+    #[test]
+    fn debug_output() {
+        let mut tree = BridgeTree::<String, 3>::new(100);
+        tree.append(&"a".to_string());
+        tree.mark();
+        tree.checkpoint();
+        tree.append(&"b".to_string());
+
+        let out = format!("{:?}", tree);
+        assert!(out.starts_with("BridgeTree { depth: 3, prior_bridges: ["));
+        for field in ["current_bridge", "saved", "mark_meta", "checkpoints"] {
+            assert!(out.contains(field), "{} missing from {}", field, out);
+        }
+        assert!(out.contains("\"a\"") && out.contains("\"b\""));
+        assert!(out.ends_with("max_checkpoints: 100 }"));
+
+        // the alternate form is produced by the standard struct formatter
+        assert!(format!("{:#?}", tree).contains("\n    depth: 3,\n"));
+    }
+
+    #[test]
+    fn tree_with_capacity() {
+        let mut t0 = BridgeTree::<String, 6>::new(100);
+        let mut t1 = BridgeTree::<String, 6>::with_capacity(100, 32);
+        assert!(t1.prior_bridges.capacity() >= 32);
+        for c in 'a'..'q' {
+            for t in [&mut t0, &mut t1] {
+                t.append(&c.to_string());
+                if c as u8 % 3 == 0 {
+                    t.mark();
                 }
-                tree
-            },
-        )
+                if c as u8 % 5 == 0 {
+                    t.checkpoint();
+                }
+            }
+        }
+        assert_eq!(t0, t1);
+        assert!(t1.prior_bridges.capacity() >= 32);
+
+        let t2 = DynBridgeTree::<String>::with_capacity(Level::from(6), 100, 32);
+        assert!(t2.prior_bridges.capacity() >= 32);
+        assert_eq!(t2.root(0), BridgeTree::<String, 6>::new(100).root(0));
     }
 
+    #[test]
+    fn reserve() {
+        let mut t = BridgeTree::<String, 8>::new(100);
+        t.append(&"a".to_string());
+        t.mark();
+        t.reserve(100);
+        let capacity = t.prior_bridges.capacity();
+        let ptr = t.prior_bridges.as_ptr();
+        assert!(capacity >= 101);
+
+        for i in 0..100 {
+            t.append(&i.to_string());
+            t.mark();
+        }
+        assert_eq!(t.prior_bridges.len(), 101);
+        assert_eq!(t.prior_bridges.capacity(), capacity);
+        assert_eq!(t.prior_bridges.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn witnesses() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        assert_eq!(t.witnesses().next(), None);
+
+        for (i, c) in ('a'..='y').enumerate() {
+            t.append(&c.to_string());
+            if i % 3 == 0 || i == 10 {
+                t.mark();
+            }
+            if i % 5 == 0 {
+                t.checkpoint();
+            }
+            if i == 12 {
+                t.remove_mark(3.into());
+            }
+        }
+
+        let root = t.root(0).unwrap();
+        let expected = t
+            .marked_positions()
+            .into_iter()
+            .map(|pos| (pos, t.witness(pos, &root).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 9);
+        assert_eq!(t.witnesses().collect::<Vec<_>>(), expected);
+
+        // the witness for a mark at the tip of the tree is also produced
+        t.mark();
+        let root = t.root(0).unwrap();
+        let tip = t.current_position().unwrap();
+        assert_eq!(
+            t.witnesses().last(),
+            Some((tip, t.witness(tip, &root).unwrap()))
+        );
+    }
+
+    #[test]
+    fn apply_batch() {
+        let mut t = BridgeTree::<String, 3>::new(10);
+        let receipt = t
+            .apply_batch(vec![
+                BatchOp::Append("a".to_string()),
+                BatchOp::AppendMarked("b".to_string()),
+                BatchOp::Checkpoint(()),
+                BatchOp::Append("c".to_string()),
+                BatchOp::AppendMarked("d".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(
+            receipt.appended(),
+            &[0.into(), 1.into(), 2.into(), 3.into()]
+        );
+        assert_eq!(receipt.marked(), &[1.into(), 3.into()]);
+        assert_eq!(receipt.checkpoints(), 1);
+        assert_eq!(receipt.position(), Some(3.into()));
+        assert_eq!(t.root(0), Some("abcd____".to_string()));
+        assert_eq!(t.root(1), Some("ab______".to_string()));
+        assert_eq!(t.marked_positions(), BTreeSet::from([1.into(), 3.into()]));
+
+        // a batch that would overflow the tree fails partway through, leaving the tree
+        // unmodified
+        let snapshot = t.clone();
+        assert_eq!(
+            t.apply_batch(vec![
+                BatchOp::AppendMarked("e".to_string()),
+                BatchOp::Checkpoint(()),
+                BatchOp::Append("f".to_string()),
+                BatchOp::Append("g".to_string()),
+                BatchOp::Append("h".to_string()),
+                BatchOp::Append("i".to_string()),
+            ]),
+            Err(BatchError::InsufficientCapacity {
+                required: 5,
+                available: 4
+            })
+        );
+        assert_eq!(t, snapshot);
+
+        // the tree may be filled exactly
+        let receipt = t
+            .apply_batch(('e'..='h').map(|c| BatchOp::Append(c.to_string())))
+            .unwrap();
+        assert_eq!(receipt.appended().len(), 4);
+        assert_eq!(receipt.position(), Some(7.into()));
+        assert_eq!(t.root(0), Some("abcdefgh".to_string()));
+
+        // an empty batch reports the current position of the tree
+        let receipt = t.apply_batch(vec![]).unwrap();
+        assert!(receipt.is_empty());
+        assert_eq!(receipt.start_position(), Some(7.into()));
+        assert_eq!(receipt.position(), Some(7.into()));
+
+        // a batch that creates a checkpoint fails if checkpointing is disabled
+        let mut t = BridgeTree::<String, 3>::new(0);
+        t.append(&"a".to_string());
+        let snapshot = t.clone();
+        assert_eq!(
+            t.apply_batch(vec![
+                BatchOp::Append("b".to_string()),
+                BatchOp::Checkpoint(()),
+            ]),
+            Err(BatchError::CheckpointingDisabled)
+        );
+        assert_eq!(t, snapshot);
+    }
+
+    #[test]
+    fn batch_replay() {
+        let batch = vec![
+            BatchOp::AppendMarked("a".to_string()),
+            BatchOp::AppendMarked("b".to_string()),
+            BatchOp::Checkpoint(7u32),
+            BatchOp::Append("c".to_string()),
+            BatchOp::RemoveMark(0.into()),
+            BatchOp::AppendMarked("d".to_string()),
+            BatchOp::Checkpoint(8u32),
+            BatchOp::RemoveMark(3.into()),
+        ];
+        let bytes = serde_json::to_vec(&batch).unwrap();
+        let decoded = serde_json::from_slice::<Vec<BatchOp<String, u32>>>(&bytes).unwrap();
+        assert_eq!(decoded, batch);
+
+        // replaying the deserialized batch produces the same tree as applying the original
+        let mut producer = DepthBridgeTree::<String, ConstDepth<4>, u32>::new(10);
+        let mut consumer = producer.clone();
+        let receipt = producer.apply_batch(batch).unwrap();
+        assert_eq!(consumer.apply_batch(decoded), Ok(receipt));
+        assert_eq!(consumer.root(0), Some("abcd____________".to_string()));
+        assert_eq!(consumer.checkpoint_meta(2), Some(&7));
+        assert_eq!(consumer.marked_positions(), BTreeSet::from([1.into()]));
+        assert_eq!(consumer, producer);
+
+        // marks removed by the batch are restored by rewinding
+        assert!(consumer.rewind());
+        assert_eq!(
+            consumer.marked_positions(),
+            BTreeSet::from([1.into(), 3.into()])
+        );
+        assert!(consumer.rewind());
+        assert_eq!(
+            consumer.marked_positions(),
+            BTreeSet::from([0.into(), 1.into()])
+        );
+    }
+
+    #[test]
+    fn batch_replay_onto_prefix() {
+        // the producer and consumer share a prefix of leaves, but have different bridge
+        // structures because of the marks and checkpoints each has made
+        let mut producer = BridgeTree::<String, 5>::new(10);
+        let mut consumer = BridgeTree::<String, 5>::new(10);
+        for c in 'a'..='f' {
+            producer.append(&c.to_string());
+            consumer.append(&c.to_string());
+            if c == 'b' {
+                producer.mark();
+            }
+            if c == 'c' || c == 'e' {
+                consumer.checkpoint();
+                consumer.mark();
+            }
+        }
+        assert_ne!(producer.prior_bridges(), consumer.prior_bridges());
+
+        // a batch that spans multiple new bridges replays onto the consumer's tree
+        let batch = vec![
+            BatchOp::Append("g".to_string()),
+            BatchOp::AppendMarked("h".to_string()),
+            BatchOp::Append("i".to_string()),
+            BatchOp::Checkpoint(()),
+            BatchOp::AppendMarked("j".to_string()),
+            BatchOp::Append("k".to_string()),
+        ];
+        let receipt = producer.apply_batch(batch.clone()).unwrap();
+        assert_eq!(consumer.apply_batch(batch), Ok(receipt.clone()));
+
+        let root = producer.root(0).unwrap();
+        assert_eq!(consumer.root(0), Some(root.clone()));
+        assert_eq!(consumer.root(1), producer.root(1));
+        for pos in receipt.marked() {
+            assert_eq!(consumer.witness(*pos, &root), producer.witness(*pos, &root));
+        }
+    }
+
+    #[test]
+    fn replay_from() {
+        let mut t = BridgeTree::<String, 3>::new(10);
+        for c in 'a'..='f' {
+            t.append(&c.to_string());
+            if c == 'b' || c == 'd' {
+                t.checkpoint();
+            }
+            if c == 'c' {
+                t.mark();
+            }
+        }
+        let log = ["c", "d", "e", "f"].map(String::from);
+        let root = t.root(0);
+
+        // the tree is left unmodified if there is no checkpoint at the position, or if the
+        // replayed values would not fit
+        let snapshot = t.clone();
+        assert_eq!(
+            t.replay_from(2.into(), &log[1..]),
+            Err(ReplayError::CheckpointNotFound(2.into()))
+        );
+        let overflow = ["c", "d", "e", "f", "g", "h", "i"].map(String::from);
+        assert_eq!(
+            t.replay_from(1.into(), &overflow),
+            Err(ReplayError::InsufficientCapacity {
+                required: 7,
+                available: 6
+            })
+        );
+        assert_eq!(t, snapshot);
+
+        // replaying from the most recent checkpoint reproduces the same root
+        assert_eq!(t.replay_from(3.into(), &log[2..]), Ok(()));
+        assert_eq!(t.root(0), root);
+        assert_eq!(t.checkpoints().len(), 1);
+
+        // as does replaying from an earlier checkpoint, which removes the later marks
+        assert_eq!(t.replay_from(1.into(), &log), Ok(()));
+        assert_eq!(t.root(0), root);
+        assert!(t.checkpoints().is_empty());
+        assert!(t.marked_positions().is_empty());
+    }
+
+    #[test]
+    fn frontier_apply_batch() {
+        let mut tree = BridgeTree::<String, 3>::new(10);
+        let mut frontier = super::Frontier::<String, 3>::empty();
+        let batches = vec![
+            vec![
+                BatchOp::Append("a".to_string()),
+                BatchOp::AppendMarked("b".to_string()),
+                BatchOp::Checkpoint(()),
+            ],
+            vec![
+                BatchOp::Append("c".to_string()),
+                BatchOp::RemoveMark(1.into()),
+                BatchOp::AppendMarked("d".to_string()),
+                BatchOp::Append("e".to_string()),
+            ],
+        ];
+        for batch in batches {
+            assert!(frontier.apply_batch(&batch).is_ok());
+            assert!(tree.apply_batch(batch).is_ok());
+            assert_eq!(tree.root(0), Some(frontier.root()));
+        }
+        assert_eq!(frontier.root(), "abcde___");
+
+        // a batch that would overflow the frontier leaves it unmodified
+        let snapshot = frontier.clone();
+        let overflow = ('f'..='i')
+            .map(|c| BatchOp::Append(c.to_string()))
+            .collect::<Vec<BatchOp<_>>>();
+        assert_eq!(
+            frontier.apply_batch(&overflow),
+            Err(BatchError::InsufficientCapacity {
+                required: 4,
+                available: 3
+            })
+        );
+        assert_eq!(frontier, snapshot);
+    }
+
+    #[test]
+    fn worker_bridge() {
+        let mut direct = BridgeTree::<String, 6>::new(10);
+        for c in 'a'..='e' {
+            direct.append(&c.to_string());
+            if c == 'c' {
+                direct.mark();
+                direct.checkpoint();
+            }
+        }
+        let mut tree = direct.clone();
+
+        // the worker extends a copy of the tree's current bridge
+        let mut worker = tree.current_bridge().clone().unwrap();
+        for c in 'f'..='k' {
+            direct.append(&c.to_string());
+            worker.append(c.to_string());
+        }
+
+        let mut parts = tree.into_parts();
+        parts.current_bridge = Some(worker.clone());
+        tree = BridgeTree::from_parts(
+            parts.prior_bridges,
+            parts.current_bridge,
+            parts.saved,
+            parts.mark_meta,
+            parts.checkpoints,
+            parts.max_checkpoints,
+        )
+        .unwrap();
+        assert_eq!(tree, direct);
+        let root = tree.root(0).unwrap();
+        assert_eq!(
+            tree.witness(Position::from(2), &root),
+            direct.witness(Position::from(2), &root)
+        );
+
+        // a bridge that does not follow from the tree's prior bridges is rejected
+        let parts = tree.into_parts();
+        let detached = MerkleBridge::new("x".to_string());
+        assert!(matches!(
+            BridgeTree::<String, 6>::from_parts(
+                parts.prior_bridges,
+                Some(detached),
+                parts.saved,
+                parts.mark_meta,
+                parts.checkpoints,
+                parts.max_checkpoints,
+            ),
+            Err(BridgeTreeError::Discontinuity(_))
+        ));
+    }
+
+    #[test]
+    fn append_info() {
+        let mut tree = BridgeTree::<String, 2>::new(10);
+        let info = tree.append_info(&"a".to_string()).unwrap();
+        assert_eq!(info.position(), Position::from(0));
+        assert!(info.new_bridge());
+        assert!(!info.is_full());
+
+        tree.mark();
+        tree.append(&"b".to_string());
+        tree.append(&"c".to_string());
+        let info = tree.append_info(&"d".to_string()).unwrap();
+        assert_eq!(info.position(), Position::from(3));
+        assert!(!info.new_bridge());
+        assert!(info.is_full());
+
+        assert_eq!(
+            tree.append_info(&"e".to_string()),
+            Err(AppendError::TreeFull)
+        );
+        assert_eq!(tree.current_position(), Some(Position::from(3)));
+    }
+
+    #[test]
+    fn append_all() {
+        let values = ('a'..='n').map(|c| c.to_string()).collect::<Vec<_>>();
+        for split in [0, 1, 5, 14] {
+            let mut looped = BridgeTree::<String, 4>::new(10);
+            let mut batched = BridgeTree::<String, 4>::new(10);
+            for t in [&mut looped, &mut batched] {
+                for value in &values[..split] {
+                    assert!(t.append(value));
+                }
+                t.mark();
+                t.checkpoint();
+            }
+
+            for value in &values[split..] {
+                assert!(looped.append(value));
+            }
+            assert_eq!(batched.append_all(&values[split..]), Ok(()));
+            assert_eq!(batched, looped);
+
+            let root = batched.root(0).unwrap();
+            for pos in batched.marked_positions() {
+                assert_eq!(batched.witness(pos, &root), looped.witness(pos, &root));
+            }
+        }
+
+        // a batch that does not fit leaves the tree unmodified, as does a single append to a
+        // full tree
+        let mut tree = BridgeTree::<String, 4>::new(10);
+        assert_eq!(tree.append_all(&values), Ok(()));
+        let expected = tree.clone();
+        assert_eq!(
+            tree.append_all(&values[..3]),
+            Err(BatchError::InsufficientCapacity {
+                required: 3,
+                available: 2
+            })
+        );
+        assert_eq!(tree, expected);
+        assert_eq!(tree.append_all(&values[..2]), Ok(()));
+        assert!(!tree.append(&values[0]));
+        assert_eq!(
+            tree.append_all(&values[..1]),
+            Err(BatchError::InsufficientCapacity {
+                required: 1,
+                available: 0
+            })
+        );
+        assert_eq!(tree.append_all(&[]), Ok(()));
+    }
+
+    #[test]
+    fn batch_receipt_start_position() {
+        let mut producer = BridgeTree::<String, 4>::new(10);
+        let mut follower = producer.clone();
+        let batches = vec![
+            vec![],
+            vec![BatchOp::Checkpoint(())],
+            vec![BatchOp::Append("a".to_string())],
+            vec![
+                BatchOp::AppendMarked("b".to_string()),
+                BatchOp::Checkpoint(()),
+                BatchOp::Append("c".to_string()),
+            ],
+            vec![BatchOp::RemoveMark(Position::from(1))],
+        ];
+
+        let mut prior_position = None;
+        for batch in batches {
+            let receipt = producer.apply_batch(batch.clone()).unwrap();
+            assert_eq!(receipt.start_position(), prior_position);
+            assert_eq!(
+                receipt.is_empty(),
+                receipt.start_position() == receipt.position()
+            );
+            prior_position = receipt.position();
+
+            assert_eq!(
+                follower.apply_batch_at(receipt.start_position(), batch),
+                Ok(BatchStatus::Applied(receipt))
+            );
+        }
+        assert_eq!(follower, producer);
+    }
+
+    #[test]
+    fn apply_batch_at() {
+        let mut producer = BridgeTree::<String, 4>::new(10);
+        let mut consumer = BridgeTree::<String, 4>::new(10);
+
+        // an empty tree is identified by a position of `None`
+        let batch = vec![
+            BatchOp::Append("a".to_string()),
+            BatchOp::AppendMarked("b".to_string()),
+        ];
+        let start = producer.current_position();
+        let receipt = producer.apply_batch(batch.clone()).unwrap();
+        assert_eq!(
+            consumer.apply_batch_at(start, batch),
+            Ok(BatchStatus::Applied(receipt))
+        );
+
+        // a different batch from the same position is rejected, as the consumer has moved on
+        assert_eq!(
+            consumer.apply_batch_at(start, vec![BatchOp::Append("x".to_string())]),
+            Err(BatchError::PositionMismatch {
+                expected: None,
+                found: Some(Position::from(1)),
+            })
+        );
+
+        // a batch from a producer that is ahead of the consumer is rejected
+        producer.append(&"c".to_string());
+        let batch = vec![BatchOp::Append("d".to_string())];
+        let start = producer.current_position();
+        producer.apply_batch(batch.clone()).unwrap();
+        let expected = consumer.clone();
+        assert_eq!(
+            consumer.apply_batch_at(start, batch),
+            Err(BatchError::PositionMismatch {
+                expected: Some(Position::from(2)),
+                found: Some(Position::from(1)),
+            })
+        );
+        assert_eq!(consumer, expected);
+
+        // once the consumer has caught up, following batches apply
+        consumer.append(&"c".to_string());
+        consumer.append(&"d".to_string());
+        let batch = vec![BatchOp::Append("e".to_string())];
+        let start = producer.current_position();
+        let receipt = producer.apply_batch(batch.clone()).unwrap();
+        assert_eq!(
+            consumer.apply_batch_at(start, batch),
+            Ok(BatchStatus::Applied(receipt))
+        );
+        assert_eq!(consumer.root(0), producer.root(0));
+    }
+
+    #[test]
+    fn apply_batch_at_already_applied() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        let first = vec![
+            BatchOp::Append("a".to_string()),
+            BatchOp::AppendMarked("b".to_string()),
+            BatchOp::Checkpoint(()),
+        ];
+        assert!(matches!(
+            t.apply_batch_at(None, first.clone()),
+            Ok(BatchStatus::Applied(_))
+        ));
+
+        // applying the same batch a second time is a recognized no-op
+        let expected = t.clone();
+        assert_eq!(
+            t.apply_batch_at(None, first.clone()),
+            Ok(BatchStatus::AlreadyApplied)
+        );
+        assert_eq!(t, expected);
+
+        let second = vec![
+            BatchOp::Append("c".to_string()),
+            BatchOp::Append("d".to_string()),
+        ];
+        let start = t.current_position();
+        assert!(matches!(
+            t.apply_batch_at(start, second.clone()),
+            Ok(BatchStatus::Applied(_))
+        ));
+        let expected = t.clone();
+        assert_eq!(
+            t.apply_batch_at(start, second),
+            Ok(BatchStatus::AlreadyApplied)
+        );
+        assert_eq!(t, expected);
+
+        // the first batch is still recognized once the tree has moved on, because the tree
+        // retains a bridge at the end of that batch
+        assert_eq!(
+            t.apply_batch_at(None, first),
+            Ok(BatchStatus::AlreadyApplied)
+        );
+
+        // a batch with different leaves from the same position is not recognized
+        let conflicting = vec![
+            BatchOp::Append("c".to_string()),
+            BatchOp::Append("x".to_string()),
+        ];
+        assert_eq!(
+            t.apply_batch_at(start, conflicting),
+            Err(BatchError::PositionMismatch {
+                expected: start,
+                found: Some(Position::from(3)),
+            })
+        );
+        assert_eq!(t, expected);
+
+        // nor is one whose leaves cannot be compared, because the tree has not retained a
+        // bridge at its start
+        assert_eq!(
+            t.apply_batch_at(
+                Some(Position::from(2)),
+                vec![BatchOp::Append("d".to_string())]
+            ),
+            Err(BatchError::PositionMismatch {
+                expected: Some(Position::from(2)),
+                found: Some(Position::from(3)),
+            })
+        );
+    }
+
+    #[test]
+    fn apply_batch_checked() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.mark();
+
+        let batch = |leaf: &str| {
+            vec![
+                BatchOp::Append("b".to_string()),
+                BatchOp::Checkpoint(()),
+                BatchOp::AppendMarked(leaf.to_string()),
+                BatchOp::Append("d".to_string()),
+            ]
+        };
+        let expected_root = "abcd____________".to_string();
+
+        // a batch in which a single leaf has been altered is rejected
+        let snapshot = t.clone();
+        assert_eq!(
+            t.apply_batch_checked(batch("x"), &expected_root),
+            Err(BatchError::RootMismatch)
+        );
+        assert_eq!(t, snapshot);
+
+        // validation of the batch's operations precedes the check of its root
+        let mut invalid = batch("c");
+        invalid.push(BatchOp::RemoveMark(5.into()));
+        assert_eq!(
+            t.apply_batch_checked(invalid, &expected_root),
+            Err(BatchError::NotMarked(5.into()))
+        );
+        assert_eq!(t, snapshot);
+
+        let receipt = t.apply_batch_checked(batch("c"), &expected_root).unwrap();
+        assert_eq!(receipt.marked(), &[2.into()]);
+        assert_eq!(t.root(0), Some(expected_root));
+        assert_eq!(t.root(1), Some("ab______________".to_string()));
+
+        // the root of an empty tree is checked
+        let mut t = BridgeTree::<String, 4>::new(10);
+        assert_eq!(
+            t.apply_batch_checked(vec![], &"a_______________".to_string()),
+            Err(BatchError::RootMismatch)
+        );
+        assert!(t
+            .apply_batch_checked(vec![], &"________________".to_string())
+            .is_ok());
+    }
+
+    #[test]
+    fn apply_batch_remove_mark() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.mark();
+        let snapshot = t.clone();
+
+        // a mark may only be removed once, and must exist at the point of its removal
+        for (batch, pos) in [
+            (vec![BatchOp::RemoveMark(1.into())], 1),
+            (
+                vec![BatchOp::RemoveMark(0.into()), BatchOp::RemoveMark(0.into())],
+                0,
+            ),
+            (
+                vec![
+                    BatchOp::Append("b".to_string()),
+                    BatchOp::RemoveMark(1.into()),
+                ],
+                1,
+            ),
+            (
+                vec![
+                    BatchOp::RemoveMark(1.into()),
+                    BatchOp::AppendMarked("b".to_string()),
+                ],
+                1,
+            ),
+        ] {
+            assert_eq!(
+                t.apply_batch(batch),
+                Err(BatchError::NotMarked(Position::from(pos)))
+            );
+            assert_eq!(t, snapshot);
+        }
+
+        let receipt = t
+            .apply_batch(vec![
+                BatchOp::AppendMarked("b".to_string()),
+                BatchOp::RemoveMark(1.into()),
+                BatchOp::RemoveMark(0.into()),
+            ])
+            .unwrap();
+        assert_eq!(receipt.marked(), &[1.into()]);
+        assert!(t.marked_positions().is_empty());
+    }
+
+    #[test]
+    fn tip_path() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        assert_eq!(t.tip_path(), None);
+
+        for c in 'a'..'l' {
+            t.append(&c.to_string());
+            if c == 'c' || c == 'f' {
+                t.mark();
+            }
+            if c == 'g' {
+                t.checkpoint();
+            }
+
+            let bridges_len = t.prior_bridges().len();
+            let (position, path) = t.tip_path().unwrap();
+            assert_eq!(t.prior_bridges().len(), bridges_len);
+            assert_eq!(Some(position), t.current_position());
+
+            let mut u = t.clone();
+            let root = u.root(0).unwrap();
+            let marked = u.mark_status();
+            assert_eq!(u.witness(position, &root), Some(path));
+            if let MarkStatus::New(pos) = marked {
+                u.remove_mark(pos);
+            }
+        }
+    }
+
+    fn arb_bridgetree<G: Strategy + Clone>(
+        item_gen: G,
+        max_count: usize,
+    ) -> impl Strategy<Value = BridgeTree<G::Value, 8>>
+    where
+        G::Value: Hashable + Ord + Clone + Debug + 'static,
+    {
+        proptest::collection::vec(arb_operation(item_gen, 0..max_count), 0..max_count).prop_map(
+            |ops| {
+                let mut tree: BridgeTree<G::Value, 8> = BridgeTree::new(10);
+                for op in ops {
+                    apply_operation(&mut tree, op);
+                }
+                tree
+            },
+        )
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
     proptest! {
         #[test]
         fn bridgetree_from_parts(
             tree in arb_bridgetree((97u8..123).prop_map(|c| char::from(c).to_string()), 100)
         ) {
             assert_eq!(
-                BridgeTree::from_parts(
-                    tree.prior_bridges.clone(),
-                    tree.current_bridge.clone(),
-                    tree.saved.clone(),
-                    tree.checkpoints.clone(),
-                    tree.max_checkpoints
-                ),
-                Ok(tree),
+                BridgeTree::from_parts(
+                    tree.prior_bridges().to_vec(),
+                    tree.current_bridge().clone(),
+                    tree.marked_indices().clone(),
+                    tree.marked_meta().clone(),
+                    tree.checkpoints().to_vec(),
+                    tree.max_checkpoints()
+                ),
+                Ok(tree),
+            );
+        }
+
+        #[test]
+        fn prop_root_consistency(
+            tree in arb_bridgetree((97u8..123).prop_map(|c| char::from(c).to_string()), 100)
+        ) {
+            assert!(tree.verify_root_consistency());
+
+            let mut tree_mut = tree.clone();
+            tree_mut.garbage_collect();
+            assert!(tree_mut.verify_root_consistency());
+            while tree_mut.rewind() {
+                assert!(tree_mut.verify_root_consistency());
+            }
+        }
+
+        #[test]
+        fn prop_can_rewind(
+            ops in proptest::collection::vec(
+                arb_operation((97u8..123).prop_map(|c| char::from(c).to_string()), 0usize..100),
+                0..100
+            )
+        ) {
+            let mut tree: BridgeTree<String, 8> = BridgeTree::new(10);
+            for op in ops {
+                if let Operation::Rewind = op {
+                    let predicted = tree.can_rewind();
+                    assert_eq!(tree.rewind(), predicted);
+                } else {
+                    apply_operation(&mut tree, op);
+                }
+                check_marked_values(&tree);
+            }
+
+            let depth = tree.checkpoints().len();
+            assert!(tree.can_rewind_n(depth));
+            assert!(!tree.can_rewind_n(depth + 1));
+            for _ in 0..depth {
+                assert!(tree.rewind());
+            }
+            assert!(!tree.can_rewind());
+            assert!(!tree.rewind());
+        }
+
+        #[test]
+        fn prop_compact_checkpoints(
+            ops in proptest::collection::vec(
+                arb_operation((97u8..123).prop_map(|c| char::from(c).to_string()), 0usize..100),
+                0..100
+            )
+        ) {
+            let mut tree: BridgeTree<String, 8> = BridgeTree::new(100);
+            for op in ops {
+                apply_operation(&mut tree, op);
+            }
+
+            let mut compacted = tree.clone();
+            let removed = compacted.compact_checkpoints();
+            assert_eq!(compacted.checkpoints().len() + removed, tree.checkpoints().len());
+            assert_eq!(compacted.root(0), tree.root(0));
+
+            // each remaining checkpoint has a distinct position, and rewinding to it restores
+            // the state of the original tree at the most recent checkpoint at that position
+            let last_position = |t: &BridgeTree<String, 8>| {
+                t.checkpoints().last().map(|c| c.position(t.prior_bridges()))
+            };
+            let mut rewound = false;
+            while compacted.rewind() {
+                // skip the earlier checkpoints of the run most recently rewound to
+                while rewound && last_position(&tree) == Some(tree.current_position()) {
+                    assert!(tree.rewind());
+                }
+                assert!(tree.rewind());
+                rewound = true;
+
+                let position = compacted.current_position();
+                assert_eq!(tree.current_position(), position);
+                assert_ne!(last_position(&compacted), Some(position));
+                assert_eq!(compacted.root(0), tree.root(0));
+                assert_eq!(compacted.marked_positions(), tree.marked_positions());
+            }
+        }
+
+        #[test]
+        fn prop_garbage_collect(
+            tree in arb_bridgetree((97u8..123).prop_map(|c| char::from(c).to_string()), 100)
+        ) {
+            let mut tree_mut = tree.clone();
+            // ensure we have enough checkpoints to not rewind past the state `tree` is in
+            for _ in 0..10 {
+                tree_mut.checkpoint();
+            }
+
+            let stats = tree_mut.gc_stats();
+            let bridges_len = tree_mut.prior_bridges().len();
+            tree_mut.garbage_collect();
+            assert_eq!(stats.bridges(), bridges_len - tree_mut.prior_bridges().len());
+
+            tree_mut.rewind();
+
+            for pos in tree.saved.keys() {
+                assert_eq!(
+                    tree.witness(*pos, &tree.root(0).unwrap()),
+                    tree_mut.witness(*pos, &tree.root(0).unwrap())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn checkpoints_disabled() {
+        let mut t = BridgeTree::<String, 4>::new(0);
+        // checkpointing an empty tree does not record an empty checkpoint
+        assert!(!t.checkpoint());
+        assert!(t.checkpoints().is_empty());
+
+        t.append(&"a".to_string());
+        t.mark();
+        t.append(&"b".to_string());
+        let root = t.root(0);
+        let bridges_len = t.prior_bridges().len();
+
+        // checkpointing a non-empty tree does not create a new bridge
+        assert!(!t.checkpoint());
+        assert!(t.checkpoints().is_empty());
+        assert_eq!(t.prior_bridges().len(), bridges_len);
+        assert_eq!(t.root(1), None);
+
+        assert!(!t.rewind());
+        assert_eq!(t.root(0), root);
+        let witness = t.witness(0.into(), root.as_ref().unwrap());
+        assert!(witness.is_some());
+
+        t.garbage_collect();
+        assert_eq!(t.root(0), root);
+        assert_eq!(t.witness(0.into(), root.as_ref().unwrap()), witness);
+
+        // a tree with checkpointing disabled cannot be reconstructed with checkpoints
+        assert_eq!(
+            BridgeTree::<String, 4>::from_parts(
+                t.prior_bridges.clone(),
+                t.current_bridge.clone(),
+                t.saved.clone(),
+                BTreeMap::new(),
+                vec![Checkpoint::at_length(0, false, ())],
+                0
+            ),
+            Err(BridgeTreeError::CheckpointMismatch)
+        );
+    }
+
+    #[test]
+    fn retain_marks() {
+        let mut t = BridgeTree::<String, 6>::new(100);
+        for c in 'a'..'f' {
+            t.append(&c.to_string());
+            t.mark();
+        }
+        t.checkpoint();
+        for c in 'f'..'j' {
+            t.append(&c.to_string());
+            t.mark();
+        }
+        let all_marked = t.marked_positions();
+
+        // removing marks in bulk is equivalent to removing them one at a time
+        let mut expected = t.clone();
+        for pos in [1, 3, 5, 7] {
+            assert!(expected.remove_mark(pos.into()));
+        }
+        assert_eq!(t.retain_marks(|pos, _| !pos.is_odd()), 4);
+        assert_eq!(t, expected);
+        assert_eq!(t.retain_marks(|_, _| true), 0);
+
+        // remove the remaining marks created since the checkpoint by leaf value
+        assert_eq!(t.retain_marks(|_, leaf| leaf.as_str() < "f"), 2);
+        assert_eq!(
+            t.marked_positions(),
+            [0, 2, 4].iter().map(|p| Position::from(*p)).collect()
+        );
+        for pos in t.marked_positions() {
+            assert!(t.witness(pos, &t.root(0).unwrap()).is_some());
+        }
+
+        // rewinding restores the marks that existed at the checkpoint, but not
+        // those created since.
+        assert!(t.rewind());
+        assert_eq!(
+            t.marked_positions(),
+            all_marked
+                .iter()
+                .cloned()
+                .filter(|p| p < &5.into())
+                .collect()
+        );
+        for pos in t.marked_positions() {
+            assert!(t.witness(pos, &t.root(0).unwrap()).is_some());
+        }
+    }
+
+    #[test]
+    fn remove_mark_prunes_current_bridge() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        for c in 'a'..'h' {
+            t.append(&c.to_string());
+            if c == 'b' || c == 'e' {
+                t.mark();
+            }
+        }
+        assert!(!t.current_bridge().as_ref().unwrap().tracking().is_empty());
+
+        // once all marks are removed, nothing is tracked or propagated to successors
+        assert!(t.remove_mark(1.into()));
+        assert!(t.remove_mark(4.into()));
+        assert!(t.current_bridge().as_ref().unwrap().tracking().is_empty());
+        assert!(t.current_bridge().as_ref().unwrap().ommers().is_empty());
+        t.checkpoint();
+        t.append(&"h".to_string());
+        assert!(t.current_bridge().as_ref().unwrap().tracking().is_empty());
+
+        // removing one mark retains the information needed by the others
+        t.append(&"i".to_string());
+        let p0 = t.mark().unwrap();
+        t.append(&"j".to_string());
+        let p1 = t.mark().unwrap();
+        t.checkpoint();
+        for c in 'k'..'t' {
+            t.append(&c.to_string());
+        }
+        assert!(t.remove_mark(p0));
+        for addr in t.current_bridge().as_ref().unwrap().tracking() {
+            assert!(addr.position_range().contains(&p1));
+        }
+        for c in 't'..'z' {
+            t.append(&c.to_string());
+            t.checkpoint();
+        }
+        assert!(t.verify_root_consistency());
+        assert_eq!(t.marked_positions(), [p1].iter().cloned().collect());
+
+        // rewinding past the removal restores a usable mark
+        for _ in 0..7 {
+            assert!(t.rewind());
+        }
+        assert_eq!(t.marked_positions(), [p0, p1].iter().cloned().collect());
+        t.append(&"z".to_string());
+        assert!(t.verify_root_consistency());
+    }
+
+    #[test]
+    fn multiproof() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        let positions = [2usize, 3, 5, 17, 40]
+            .iter()
+            .map(|p| Position::from(*p))
+            .collect::<Vec<_>>();
+        let mut leaves = vec![];
+        for i in 0..50usize {
+            let leaf = format!("{:x}", i % 16);
+            t.append(&leaf);
+            if positions.contains(&i.into()) {
+                t.mark();
+                leaves.push((Position::from(i), leaf));
+            }
+        }
+        let root = t.root(0).unwrap();
+        assert_eq!(t.multiproof(&[]), None);
+        assert_eq!(t.multiproof(&[2.into(), 4.into()]), None);
+
+        let proof = t.multiproof(&positions).unwrap();
+        assert_eq!(proof.positions(), &positions[..]);
+        assert!(proof.verify(&leaves, &root));
+        // the order of the specified positions and leaves is irrelevant
+        let mut reversed = positions.clone();
+        reversed.reverse();
+        assert_eq!(t.multiproof(&reversed), Some(proof.clone()));
+        let mut reversed_leaves = leaves.clone();
+        reversed_leaves.reverse();
+        assert!(proof.verify(&reversed_leaves, &root));
+
+        // shared nodes appear only once, and nodes that can be computed from the proven leaves
+        // are omitted; for example, positions 2 and 3 are siblings, so neither requires the
+        // other's leaf.
+        assert!(proof.nodes().len() < positions.len() * 6);
+        assert_eq!(
+            proof
+                .nodes()
+                .iter()
+                .map(|(addr, _)| u8::from(addr.level()))
+                .collect::<Vec<_>>(),
+            vec![0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 3, 4]
+        );
+
+        // a tampered leaf, node or root fails verification
+        for i in 0..leaves.len() {
+            let mut tampered = leaves.clone();
+            tampered[i].1 = "x".to_string();
+            assert!(!proof.verify(&tampered, &root));
+        }
+        for i in 0..proof.nodes().len() {
+            let mut tampered = proof.clone();
+            tampered.nodes[i].1.push('x');
+            assert!(!tampered.verify(&leaves, &root));
+        }
+        assert!(!proof.verify(&leaves, &"x".repeat(64)));
+
+        // the leaves must be exactly those proven
+        assert!(!proof.verify(&leaves[1..], &root));
+        let mut extra = leaves.clone();
+        extra.push((6.into(), "6".to_string()));
+        assert!(!proof.verify(&extra, &root));
+        let mut duplicate = leaves.clone();
+        duplicate.push(leaves[0].clone());
+        assert!(!proof.verify(&duplicate, &root));
+
+        // unnecessary nodes are rejected
+        let mut padded = proof.clone();
+        padded
+            .nodes
+            .push((Address::from_parts(0.into(), 7), "7".to_string()));
+        assert!(!padded.verify(&leaves, &root));
+
+        let value = serde_json::to_value(&proof).unwrap();
+        assert_eq!(
+            serde_json::from_value::<MultiProof<String>>(value).unwrap(),
+            proof
+        );
+    }
+
+    #[test]
+    fn into_parts() {
+        let mut t = DynBridgeTree::<String, (), u32>::new(Level::from(6), 3);
+        for (i, c) in ('a'..'n').enumerate() {
+            t.append(&c.to_string());
+            if i % 3 == 0 {
+                t.mark_with(i as u32);
+            }
+            if i % 4 == 0 {
+                t.checkpoint();
+            }
+        }
+        t.remove_mark(9.into());
+
+        let parts = t.clone().into_parts();
+        assert_eq!(&parts.saved, t.marked_indices());
+        let restored = DynBridgeTree::from_parts(
+            t.depth(),
+            parts.prior_bridges,
+            parts.current_bridge,
+            parts.saved,
+            parts.mark_meta,
+            parts.checkpoints,
+            parts.max_checkpoints,
+        );
+        assert_eq!(restored, Ok(t.clone()));
+
+        // corrupted parts are rejected
+        let mut parts = t.into_parts();
+        parts.saved.insert(1.into(), 0);
+        assert!(DynBridgeTree::from_parts(
+            Level::from(6),
+            parts.prior_bridges,
+            parts.current_bridge,
+            parts.saved,
+            parts.mark_meta,
+            parts.checkpoints,
+            parts.max_checkpoints,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn mark_position() {
+        // `control` marks the leaf at `pos` as it is appended; `t` marks it retroactively.
+        let build = |pos: Option<usize>| {
+            let mut t = BridgeTree::<String, 5>::new(10);
+            for i in 0..8usize {
+                t.append(&i.to_string());
+                if i == 4 || Some(i) == pos {
+                    t.mark();
+                }
+                t.checkpoint();
+            }
+            t
+        };
+        for pos in 0..8usize {
+            let mut t = build(None);
+            let mut control = build(Some(pos));
+            assert_eq!(t.mark_position(pos.into()), Ok(()));
+            assert_eq!(t.marked_positions(), control.marked_positions());
+            for i in 8..20usize {
+                let root = t.root(0).unwrap();
+                assert_eq!(root, control.root(0).unwrap());
+                assert_eq!(
+                    t.witness_verified(pos.into(), &root),
+                    control.witness(pos.into(), &root)
+                );
+                assert!(t.witness(pos.into(), &root).is_some());
+                if i % 3 == 0 {
+                    t.checkpoint();
+                    control.checkpoint();
+                }
+                t.append(&i.to_string());
+                control.append(&i.to_string());
+            }
+            assert!(t.verify_root_consistency());
+            t.garbage_collect();
+            control.garbage_collect();
+
+            // rewinding to a checkpoint created after the marked leaf was appended retains the
+            // mark; the leaf at position 7 was marked after the checkpoint at that position was
+            // created, in the same fashion as `BridgeTree::mark`.
+            for _ in 0..(if pos < 7 { 5 } else { 4 }) {
+                assert!(t.rewind());
+                assert!(control.rewind());
+            }
+            let root = t.root(0).unwrap();
+            assert_eq!(t.marked_positions(), control.marked_positions());
+            assert_eq!(
+                t.witness(pos.into(), &root),
+                control.witness(pos.into(), &root)
+            );
+            t.append(&"x".to_string());
+            control.append(&"x".to_string());
+            let root = t.root(0).unwrap();
+            assert_eq!(
+                t.witness(pos.into(), &root),
+                control.witness(pos.into(), &root)
+            );
+        }
+
+        // nodes between sparse bridges are not retained
+        let mut t = BridgeTree::<String, 5>::new(10);
+        for i in 0..8usize {
+            t.append(&i.to_string());
+            if i == 2 {
+                t.checkpoint();
+            }
+        }
+        assert_eq!(
+            t.mark_position(3.into()),
+            Err(MarkError::BridgeNotFound(3.into()))
+        );
+        assert_eq!(
+            t.mark_position(8.into()),
+            Err(MarkError::PositionNotAppended(8.into()))
+        );
+        assert_eq!(
+            t.mark_position(2.into()),
+            Err(MarkError::NodeNotFound(Address::from_parts(0.into(), 3)))
+        );
+        assert!(t.marked_positions().is_empty());
+        assert_eq!(t.mark_position(7.into()), Ok(()));
+        assert_eq!(t.marked_positions(), [7.into()].iter().cloned().collect());
+    }
+
+    #[test]
+    fn as_of_checkpoint() {
+        let mut t = BridgeTree::<String, 6>::new(5);
+        for c in 'a'..'m' {
+            t.append(&c.to_string());
+            if c == 'c' || c == 'h' {
+                t.mark();
+            }
+            if c > 'e' {
+                t.checkpoint();
+            }
+        }
+        let original = t.clone();
+        assert_eq!(t.as_of_checkpoint(0), Some(t.clone()));
+        assert_eq!(t.as_of_checkpoint(6), None);
+
+        for depth in 1..=5 {
+            let mut rewound = t.clone();
+            for _ in 0..depth {
+                assert!(rewound.rewind());
+            }
+            let mut copy = t.as_of_checkpoint(depth).unwrap();
+            assert_eq!(copy, rewound);
+            assert_eq!(copy.root(0), t.root(depth));
+            assert_eq!(copy.checkpoints().len(), 5 - depth);
+            let root = copy.root(0).unwrap();
+            assert_eq!(copy.witness(2.into(), &root), t.witness(2.into(), &root));
+
+            // mutating the copy leaves the original unmodified
+            copy.append(&"z".to_string());
+            copy.mark();
+            copy.remove_mark(2.into());
+            copy.checkpoint();
+            assert_eq!(t, original);
+        }
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut t = BridgeTree::<String, 8>::new(200);
+        assert_eq!(t.dynamic_memory_usage(), 0);
+        t.checkpoint();
+        for i in 0..200usize {
+            t.append(&i.to_string());
+            if i % 2 == 0 {
+                t.mark();
+            }
+            t.checkpoint();
+        }
+        let full_usage = t.dynamic_memory_usage();
+
+        while t.checkpoints().len() > 2 {
+            assert!(t.rewind());
+        }
+        let rewound_usage = t.dynamic_memory_usage();
+        assert!(rewound_usage < full_usage);
+
+        // the capacity retained after the rewind is released
+        t.shrink_to_fit();
+        let shrunk_usage = t.dynamic_memory_usage();
+        assert!(shrunk_usage * 10 < rewound_usage);
+        assert!(shrunk_usage > 0);
+
+        // the tree continues to function normally
+        let root = t.root(0);
+        assert!(t.rewind());
+        assert!(t.rewind());
+        t.append(&"x".to_string());
+        assert!(t.verify_root_consistency());
+        assert_ne!(t.root(0), root);
+    }
+
+    #[test]
+    fn drain_checkpoints() {
+        let mut t = DepthBridgeTree::<String, ConstDepth<6>, u32>::new(5);
+        t.checkpoint_with(0);
+        for height in 1..=8 {
+            t.append(&height.to_string());
+            if height == 2 {
+                t.mark();
+            }
+            t.checkpoint_with(height);
+        }
+        let roots = (1..=5).map(|d| t.root(d).unwrap()).collect::<Vec<_>>();
+        let positions = (1..=5)
+            .map(|d| t.checkpoint_position(d))
+            .collect::<Vec<_>>();
+
+        assert_eq!(t.drain_checkpoints(5), vec![]);
+        let archived = t.drain_checkpoints(2);
+        assert_eq!(t.checkpoints().len(), 2);
+        assert_eq!(
+            archived.iter().map(|a| *a.meta()).collect::<Vec<_>>(),
+            vec![4, 5, 6]
+        );
+        for (a, d) in archived.iter().zip((3..=5).rev()) {
+            assert_eq!(a.root(), &roots[d - 1]);
+            assert_eq!(a.position(), positions[d - 1]);
+        }
+
+        // archiving each checkpoint before it would be evicted
+        let mut archive = archived;
+        let mut oldest_roots = vec![];
+        for height in 9..=14 {
+            t.append(&height.to_string());
+            oldest_roots.push(t.root(t.checkpoints().len()).unwrap());
+            archive.extend(t.drain_checkpoints(t.max_checkpoints() - 1));
+            t.checkpoint_with(height);
+        }
+        assert_eq!(
+            archive.iter().map(|a| *a.meta()).collect::<Vec<_>>(),
+            vec![4, 5, 6, 7, 8, 9]
+        );
+        assert_eq!(archive[3].position(), Some(6.into()));
+        assert_eq!(archive[3].root(), &oldest_roots[3]);
+        assert_eq!(archive[5].root(), &oldest_roots[5]);
+        assert!(t.verify_root_consistency());
+        assert!(t.witness(1.into(), &t.root(0).unwrap()).is_some());
+    }
+
+    #[test]
+    fn checkpoint_with_archive() {
+        let mut t = DepthBridgeTree::<String, ConstDepth<6>, u32>::new(3);
+        let mut archive = vec![];
+        let mut oldest = vec![];
+        for height in 1..=8 {
+            t.append(&height.to_string());
+            if height == 2 {
+                t.mark();
+            }
+            let depth = t.checkpoints().len();
+            if depth == t.max_checkpoints() {
+                oldest.push((t.root(depth).unwrap(), t.checkpoint_position(depth)));
+            }
+            assert!(t.checkpoint_with_archive(height, |a| archive.push(a)));
+            assert!(t.checkpoints().len() <= t.max_checkpoints());
+        }
+
+        // the roots of the evicted checkpoints are those that preceded their eviction
+        assert_eq!(
+            archive.iter().map(|a| *a.meta()).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert_eq!(archive.len(), oldest.len());
+        for (a, (root, position)) in archive.iter().zip(&oldest) {
+            assert_eq!(a.root(), root);
+            assert_eq!(a.position(), *position);
+        }
+        assert!(t.verify_root_consistency());
+        assert!(t.witness(1.into(), &t.root(0).unwrap()).is_some());
+
+        // the callback is not invoked when no checkpoint is evicted, or when checkpointing is
+        // disabled
+        let mut t = BridgeTree::<String, 4>::new(2);
+        t.append(&"a".to_string());
+        assert!(t.checkpoint_with_archive((), |_| panic!("no checkpoint was evicted")));
+        let mut t = BridgeTree::<String, 4>::new(0);
+        assert!(!t.checkpoint_with_archive((), |_| panic!("checkpointing is disabled")));
+    }
+
+    #[test]
+    fn explain_witness() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        for c in 'a'..'l' {
+            t.append(&c.to_string());
+            if c == 'f' || c == 'i' {
+                t.mark();
+            }
+            if c == 'g' {
+                t.checkpoint();
+            }
+        }
+
+        let root = t.root(0).unwrap();
+        let trace = t.explain_witness(5.into(), &root).unwrap();
+        assert_eq!(Some(trace.path()), t.witness(5.into(), &root));
+        assert_eq!(trace.position, Position::from(5));
+        assert_eq!(trace.tip, Position::from(10));
+        assert_eq!(
+            trace
+                .nodes
+                .iter()
+                .map(|n| (n.value.as_str(), n.source.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("e", WitnessSource::MarkedFrontier),
+                ("gh", WitnessSource::Bridge(2)),
+                ("abcd", WitnessSource::MarkedFrontier),
+                ("ijk_____", WitnessSource::TipFrontier),
+            ]
+        );
+
+        // as of the checkpoint, the witness is padded with empty roots
+        let root = t.root(1).unwrap();
+        let trace = t.explain_witness(5.into(), &root).unwrap();
+        assert_eq!(Some(trace.path()), t.witness(5.into(), &root));
+        assert_eq!(trace.tip, Position::from(6));
+        assert_eq!(trace.nodes[1].source, WitnessSource::TipFrontier);
+        assert_eq!(trace.nodes[3].source, WitnessSource::EmptyRoot);
+
+        // every witness produced by the tree is explained
+        let root = t.root(0).unwrap();
+        let trace = t.explain_witness(8.into(), &root).unwrap();
+        assert_eq!(Some(trace.path()), t.witness(8.into(), &root));
+        assert_eq!(t.explain_witness(7.into(), &root), None);
+
+        let value = serde_json::to_value(&trace).unwrap();
+        assert_eq!(
+            serde_json::from_value::<WitnessTrace<String>>(value).unwrap(),
+            trace
+        );
+    }
+
+    #[test]
+    fn rewind_retains_marked_bridges() {
+        let mut t = BridgeTree::<String, 5>::new(10);
+        let mut marked = vec![];
+        for (i, c) in ('a'..'t').enumerate() {
+            t.append(&c.to_string());
+            if i % 3 == 0 {
+                marked.push(t.mark().unwrap());
+            }
+            if i % 4 == 1 {
+                t.checkpoint();
+            }
+            if i == 10 {
+                assert!(t.remove_mark(marked[1]));
+            }
+        }
+
+        // after each rewind, every mark refers to the bridge for its position, and continues to
+        // produce valid witnesses as further leaves are appended
+        while t.rewind() {
+            let mut next = t.clone();
+            next.append(&"x".to_string());
+            let root = next.root(0).unwrap();
+            for (pos, idx) in next.marked_indices() {
+                assert_eq!(next.prior_bridges()[*idx].position(), *pos);
+                assert!(next.witness_verified(*pos, &root).is_some());
+            }
+            assert!(next.verify_root_consistency());
+        }
+        assert_eq!(t.marked_positions(), marked[..1].iter().cloned().collect());
+    }
+
+    #[test]
+    fn get_marked_leaf() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        let values = ('a'..'p').map(|c| c.to_string()).collect::<Vec<_>>();
+        for (i, v) in values.iter().enumerate() {
+            t.append(v);
+            match i % 4 {
+                // marking immediately after a checkpoint reuses the checkpointed bridge
+                0 => {
+                    t.checkpoint();
+                    t.mark();
+                }
+                1 => {
+                    t.mark();
+                    t.checkpoint();
+                }
+                2 => {
+                    t.mark();
+                    t.mark();
+                }
+                _ => {}
+            }
+        }
+        for (i, v) in values.iter().enumerate() {
+            let expected = if i % 4 == 3 { None } else { Some(v) };
+            assert_eq!(t.get_marked_leaf(i.into()), expected);
+        }
+        assert_eq!(t.get_marked_leaf(values.len().into()), None);
+
+        t.remove_mark(1.into());
+        assert_eq!(t.get_marked_leaf(1.into()), None);
+        t.garbage_collect();
+        assert_eq!(t.get_marked_leaf(0.into()), Some(&values[0]));
+        assert_eq!(t.get_marked_leaf(14.into()), Some(&values[14]));
+    }
+
+    /// Checks that the index of marked leaf values is exactly that derived from the marks of
+    /// the tree.
+    fn check_marked_values<H: Hashable + Ord + Clone + Debug, const DEPTH: u8>(
+        t: &BridgeTree<H, DEPTH>,
+    ) {
+        let mut expected = BTreeMap::<H, BTreeSet<Position>>::new();
+        for (position, value, _) in t.marked_leaves() {
+            expected.entry(value.clone()).or_default().insert(position);
+        }
+        assert_eq!(t.marked_values, expected);
+    }
+
+    #[test]
+    fn positions_of() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        let positions = |t: &BridgeTree<String, 6>, v: &str| {
+            check_marked_values(t);
+            t.positions_of(&v.to_string()).collect::<Vec<_>>()
+        };
+        t.append(&"a".to_string());
+        t.mark();
+        t.checkpoint();
+        t.append(&"b".to_string());
+        t.append(&"a".to_string());
+        assert_eq!(positions(&t, "a"), vec![0.into()]);
+        t.mark();
+        t.checkpoint();
+        assert_eq!(positions(&t, "a"), vec![0.into(), 2.into()]);
+        assert_eq!(positions(&t, "b"), vec![]);
+
+        assert!(t.remove_mark(0.into()));
+        assert_eq!(positions(&t, "a"), vec![2.into()]);
+        t.append(&"a".to_string());
+        t.mark();
+        assert_eq!(positions(&t, "a"), vec![2.into(), 3.into()]);
+
+        // rewinding restores the removed mark and discards the mark made since the checkpoint
+        assert!(t.rewind());
+        assert_eq!(positions(&t, "a"), vec![0.into(), 2.into()]);
+        // rewinding past the checkpointed mark discards it
+        assert!(t.rewind());
+        assert_eq!(positions(&t, "a"), vec![0.into()]);
+
+        // a value is removed from the index along with the last of its marks
+        assert!(t.remove_mark(0.into()));
+        assert_eq!(positions(&t, "a"), vec![]);
+        assert!(t.marked_values.is_empty());
+
+        // the index is rebuilt when a tree is reconstructed from its parts
+        t.append(&"c".to_string());
+        t.mark();
+        let parts = t.clone().into_parts();
+        let rebuilt = BridgeTree::<String, 6>::from_parts(
+            parts.prior_bridges,
+            parts.current_bridge,
+            parts.saved,
+            parts.mark_meta,
+            parts.checkpoints,
+            parts.max_checkpoints,
+        )
+        .unwrap();
+        assert_eq!(positions(&rebuilt, "c"), vec![1.into()]);
+        assert_eq!(rebuilt, t);
+    }
+
+    #[test]
+    fn remove_marks() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        let mut marked = vec![];
+        for c in 'a'..'j' {
+            t.append(&c.to_string());
+            if c < 'f' {
+                marked.push(t.mark().unwrap());
+            }
+        }
+        t.checkpoint();
+
+        // removals of positions that are not marked are ignored
+        let removed = [marked[0], marked[2], marked[4], 7.into()];
+        assert_eq!(t.remove_marks(&removed), 3);
+        assert_eq!(t.remove_marks(&removed), 0);
+        assert_eq!(
+            t.marked_positions(),
+            [marked[1], marked[3]].iter().cloned().collect()
+        );
+        t.append(&"j".to_string());
+        assert!(t.verify_root_consistency());
+
+        assert!(t.rewind());
+        assert_eq!(t.marked_positions().len(), 5);
+    }
+
+    #[test]
+    fn unmarked_prefix_collapses() {
+        use crate::testing::SipHashable;
+
+        // `control` retains its marks throughout, and so never collapses its bridges.
+        let mut t = BridgeTree::<SipHashable, 8>::new(5);
+        let mut control = BridgeTree::<SipHashable, 8>::new(5);
+        let mut marked = vec![];
+        for i in 0..100u64 {
+            t.append(&SipHashable(i));
+            control.append(&SipHashable(i));
+            if i % 3 == 0 {
+                marked.push(t.mark().unwrap());
+                control.mark();
+            }
+            if i % 4 == 0 {
+                t.checkpoint();
+                control.checkpoint();
+            }
+        }
+        assert!(t.prior_bridges().len() > 30);
+
+        // bridges are retained while a checkpoint may restore one of the removed marks; the
+        // last mark was created after the last checkpoint, and so will not be restored.
+        for pos in &marked {
+            assert!(t.remove_mark(*pos));
+        }
+        assert!(t.prior_bridges().len() > 30);
+        let mut rewound = t.clone();
+        assert!(rewound.rewind());
+        let root = rewound.root(0).unwrap();
+        assert_eq!(
+            rewound.marked_positions(),
+            marked[..marked.len() - 1].iter().cloned().collect()
+        );
+        for pos in rewound.marked_positions() {
+            assert_eq!(rewound.witness(pos, &root), control.witness(pos, &root));
+        }
+
+        // once the checkpoints that could restore the removed marks age out, the prefix is
+        // fused into a single bridge
+        for i in 100..110u64 {
+            t.append(&SipHashable(i));
+            control.append(&SipHashable(i));
+            t.checkpoint();
+            control.checkpoint();
+        }
+        assert!(t.prior_bridges().len() <= 6);
+        for d in 0..=5 {
+            assert_eq!(t.root(d), control.root(d));
+        }
+
+        // subsequent behavior is unchanged
+        t.append(&SipHashable(110));
+        control.append(&SipHashable(110));
+        let pos = t.mark().unwrap();
+        control.mark();
+        for i in 111..130u64 {
+            t.append(&SipHashable(i));
+            control.append(&SipHashable(i));
+        }
+        let root = t.root(0).unwrap();
+        assert_eq!(root, control.root(0).unwrap());
+        assert_eq!(t.witness(pos, &root), control.witness(pos, &root));
+        for _ in 0..5 {
+            assert!(t.rewind());
+            assert!(control.rewind());
+            assert_eq!(t.root(0), control.root(0));
+        }
+        assert!(!t.rewind());
+
+        // without checkpoints, removing the last mark collapses every prior bridge
+        let mut t = BridgeTree::<SipHashable, 8>::new(0);
+        for i in 0..20u64 {
+            t.append(&SipHashable(i));
+            t.mark();
+        }
+        let root = t.root(0).unwrap();
+        assert_eq!(t.retain_marks(|_, _| false), 20);
+        assert_eq!(t.prior_bridges().len(), 1);
+        assert_eq!(t.root(0), Some(root));
+    }
+
+    #[test]
+    fn compact_checkpoints() {
+        let mut t = DepthBridgeTree::<String, ConstDepth<6>, u32>::new(10);
+        t.append(&"a".to_string());
+        t.mark();
+        t.checkpoint_with(1);
+        t.append(&"b".to_string());
+        t.checkpoint_with(2);
+        // marks changed between checkpoints at the same position
+        t.mark();
+        assert!(t.remove_mark(Position::from(0)));
+        t.checkpoint_with(3);
+        t.checkpoint_with(4);
+        t.append(&"c".to_string());
+        t.checkpoint_with(5);
+        t.checkpoint_with(6);
+        t.append(&"d".to_string());
+
+        let mut compacted = t.clone();
+        assert_eq!(compacted.compact_checkpoints(), 3);
+        assert_eq!(
+            compacted
+                .checkpoints()
+                .iter()
+                .map(|c| *c.meta())
+                .collect::<Vec<_>>(),
+            vec![1, 4, 6]
+        );
+        assert_eq!(compacted.compact_checkpoints(), 0);
+
+        // rewinding to the most recent checkpoint of each run restores the same state as
+        // rewinding to it prior to compaction
+        assert_eq!(compacted.rewind_reporting().unwrap().into_meta(), 6);
+        assert_eq!(t.rewind_reporting().unwrap().into_meta(), 6);
+        assert_eq!(
+            compacted.clone().into_parts().saved,
+            t.clone().into_parts().saved
+        );
+        assert_eq!(compacted.root(0), t.root(0));
+        assert_eq!(compacted.marked_positions(), t.marked_positions());
+
+        assert_eq!(compacted.rewind_reporting().unwrap().into_meta(), 4);
+        for expected in [5, 4] {
+            assert_eq!(t.rewind_reporting().unwrap().into_meta(), expected);
+        }
+        assert_eq!(compacted.root(0), t.root(0));
+        assert_eq!(compacted.marked_positions(), t.marked_positions());
+        assert_eq!(
+            compacted.marked_positions(),
+            vec![Position::from(1)].into_iter().collect()
+        );
+
+        // the mark removed after the compacted checkpoint is restored by rewinding past it,
+        // and the mark created after it is removed
+        assert_eq!(compacted.rewind_reporting().unwrap().into_meta(), 1);
+        for expected in [3, 2, 1] {
+            assert_eq!(t.rewind_reporting().unwrap().into_meta(), expected);
+        }
+        assert_eq!(compacted, t);
+        assert_eq!(
+            compacted.marked_positions(),
+            vec![Position::from(0)].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn consecutive_checkpoints() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        t.append(&"a".to_string());
+        assert!(t.checkpoint());
+        assert!(t.checkpoint());
+        assert_eq!(t.checkpoints().len(), 2);
+        // the duplicate checkpoint shares its bridge with the first
+        assert_eq!(t.prior_bridges().len(), 1);
+        assert_eq!(t.checkpoint_position(1), t.checkpoint_position(2));
+
+        // each rewind removes a single checkpoint
+        t.append(&"b".to_string());
+        assert!(t.rewind());
+        assert_eq!(t.current_position(), Some(Position::from(0)));
+        assert_eq!(t.checkpoints().len(), 1);
+        assert!(t.rewind());
+        assert_eq!(t.current_position(), Some(Position::from(0)));
+        assert!(!t.rewind());
+    }
+
+    #[test]
+    fn checkpoint_if_changed() {
+        let mut t = BridgeTree::<String, 6>::new(3);
+        // the first checkpoint is always created, even for an empty tree
+        assert!(t.checkpoint_if_changed());
+        assert!(!t.checkpoint_if_changed());
+
+        // blocks alternate between adding leaves and adding nothing
+        for c in 'a'..'f' {
+            t.append(&c.to_string());
+            assert!(t.checkpoint_if_changed());
+            assert!(!t.checkpoint_if_changed());
+        }
+        assert_eq!(t.checkpoints().len(), 3);
+        assert_eq!(t.current_position(), Some(4.into()));
+
+        // each checkpoint corresponds to a block that added leaves
+        for expected in (2..5).rev() {
+            assert!(t.rewind());
+            assert_eq!(t.current_position(), Some(expected.into()));
+        }
+        assert!(!t.rewind());
+
+        // a regular checkpoint is still counted as the most recent checkpoint
+        assert!(t.checkpoint());
+        assert!(!t.checkpoint_if_changed());
+        t.append(&"f".to_string());
+        assert!(t.checkpoint_if_changed());
+
+        // checkpointing is never performed if it is disabled
+        let mut t = BridgeTree::<String, 6>::new(0);
+        t.append(&"a".to_string());
+        assert!(!t.checkpoint_if_changed());
+    }
+
+    #[test]
+    fn incremental_persistence() {
+        let mut t = BridgeTree::<String, 6>::new(100);
+        let mut stored: Vec<MerkleBridge<String>> = vec![];
+        for c in 'a'..'m' {
+            t.append(&c.to_string());
+            if c as u8 % 3 == 0 {
+                t.mark();
+            }
+            t.checkpoint();
+
+            // only the bridges created since the last block need to be stored
+            stored.extend_from_slice(&t.prior_bridges()[stored.len()..]);
+            assert_eq!(&stored[..], t.prior_bridges());
+
+            let restored = BridgeTree::from_parts(
+                stored.clone(),
+                t.current_bridge().clone(),
+                t.marked_indices().clone(),
+                t.marked_meta().clone(),
+                t.checkpoints().to_vec(),
+                t.max_checkpoints(),
+            );
+            assert_eq!(restored, Ok(t.clone()));
+        }
+    }
+
+    #[test]
+    fn rewind_reporting() {
+        let mut t = BridgeTree::<String, 6>::new(100);
+        assert_eq!(t.rewind_reporting(), Err(RewindError::NoCheckpoints));
+
+        // rewinding to a checkpoint of the empty tree
+        t.checkpoint();
+        t.append(&"a".to_string());
+        t.mark();
+        let result = t.rewind_reporting().unwrap();
+        assert_eq!(result.new_position(), None);
+        assert_eq!(result.leaves_dropped(), 1);
+        assert_eq!(result.marks_dropped(), &[Position::from(0)]);
+
+        // a rewind that does not alter the tree state
+        t.append(&"a".to_string());
+        t.mark();
+        t.checkpoint();
+        let result = t.rewind_reporting().unwrap();
+        assert_eq!(result.new_position(), Some(0.into()));
+        assert_eq!(result.leaves_dropped(), 0);
+        assert!(result.marks_dropped().is_empty());
+        assert_eq!(t.marked_positions(), [0.into()].iter().cloned().collect());
+
+        // marks created after the checkpoint are dropped; forgotten marks are restored
+        t.checkpoint();
+        assert!(t.remove_mark(0.into()));
+        for c in 'b'..'f' {
+            t.append(&c.to_string());
+            if c != 'c' {
+                t.mark();
+            }
+        }
+        let result = t.rewind_reporting().unwrap();
+        assert_eq!(result.new_position(), Some(0.into()));
+        assert_eq!(result.leaves_dropped(), 4);
+        assert_eq!(
+            result.marks_dropped(),
+            &[Position::from(1), Position::from(3), Position::from(4)]
+        );
+        assert_eq!(t.marked_positions(), [0.into()].iter().cloned().collect());
+        assert_eq!(t.rewind_reporting(), Err(RewindError::NoCheckpoints));
+    }
+
+    #[test]
+    fn rewind_to_root() {
+        let mut t = BridgeTree::<String, 6>::new(100);
+        assert_eq!(
+            t.rewind_to_root(&t.root(0).unwrap()),
+            Err(RewindError::NoCheckpoints)
+        );
+
+        let mut controls = vec![];
+        for c in 'a'..'h' {
+            t.append(&c.to_string());
+            if c == 'b' || c == 'e' {
+                t.mark();
+            }
+            t.checkpoint();
+            controls.push(t.clone());
+        }
+        t.append(&"h".to_string());
+        t.mark();
+        t.checkpoint();
+        t.append(&"i".to_string());
+
+        let unknown = BridgeTree::<String, 6>::new(100).root(0).unwrap();
+        let mut t0 = t.clone();
+        assert_eq!(t0.rewind_to_root(&unknown), Err(RewindError::RootNotFound));
+        assert_eq!(t0, t);
+
+        // the checkpoint created after 'h' has the most recent root
+        assert_eq!(t0.rewind_to_root(&t.root(1).unwrap()), Ok(1));
+
+        for (i, mut control) in controls.into_iter().enumerate() {
+            let mut t0 = t.clone();
+            let root = control.root(0).unwrap();
+            assert_eq!(t0.rewind_to_root(&root), Ok(8 - i));
+            assert_eq!(t0.root(0), Some(root));
+
+            // rewinding the control to the same checkpoint produces an identical tree
+            assert!(control.rewind());
+            assert_eq!(t0, control);
+        }
+
+        // when multiple checkpoints have the same root, the most recent is used
+        let mut t0 = t.clone();
+        t0.checkpoint();
+        t0.checkpoint();
+        assert_eq!(t0.rewind_to_root(&t.root(0).unwrap()), Ok(1));
+        assert_eq!(t0.checkpoints().len(), t.checkpoints().len() + 1);
+    }
+
+    #[test]
+    fn witness_verified() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        for c in 'a'..'e' {
+            t.append(&c.to_string());
+            if c == 'b' {
+                t.mark();
+                t.checkpoint();
+            }
+        }
+        let root = t.root(0).unwrap();
+        assert_eq!(
+            t.witness_verified(1.into(), &root),
+            t.witness(1.into(), &root)
+        );
+        assert!(t.witness_verified(1.into(), &root).is_some());
+        assert!(t.witness_verified(1.into(), &t.root(1).unwrap()).is_some());
+        assert_eq!(t.witness_verified(1.into(), &"wrong".to_string()), None);
+        // witnesses are only produced for marked positions
+        assert_eq!(t.witness_verified(0.into(), &root), None);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "Witness for position Position(1) does not produce the root for \
+        which it was requested; it first diverges from the frontier of the tree at Level(1)."
+    )]
+    fn witness_self_check() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        for c in 'a'..'e' {
+            t.append(&c.to_string());
+            if c == 'b' {
+                t.mark();
+            }
+        }
+        let root = t.root(0).unwrap();
+        assert!(t.witness(1.into(), &root).is_some());
+
+        // corrupt the ommer of the marked leaf's frontier
+        t.prior_bridges[0].frontier.ommers[0] = "z".to_string();
+        assert_eq!(t.witness_verified(1.into(), &root), None);
+        t.witness(1.into(), &root);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "Witness for position Position(1) does not produce the root for \
+        which it was requested; it first diverges from the frontier of the tree at Level(2)."
+    )]
+    fn witness_self_check_below_common_ancestor() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        for c in 'a'..'g' {
+            t.append(&c.to_string());
+            if c == 'b' {
+                t.mark();
+            }
+        }
+        let root = t.root(0).unwrap();
+        assert!(t.witness(1.into(), &root).is_some());
+
+        // corrupt the ommer at level 1 that was completed after the marked leaf; the tip at
+        // position 5 shares no ancestor with the marked leaf below level 3, so the divergence is
+        // first detected at level 2, where the tip's sibling is the ancestor of the marked leaf
+        let addr = Address::from_parts(1.into(), 1);
+        *t.current_bridge
+            .as_mut()
+            .unwrap()
+            .ommers
+            .get_mut(&addr)
+            .unwrap() = "zz".to_string();
+        assert_eq!(t.witness_verified(1.into(), &root), None);
+        t.witness(1.into(), &root);
+    }
+
+    #[test]
+    fn checkpoint_meta() {
+        let mut t = DepthBridgeTree::<String, ConstDepth<6>, u32>::new(3);
+        assert_eq!(t.checkpoint_meta(1), None);
+        for height in 1..=5 {
+            t.append(&height.to_string());
+            assert!(t.checkpoint_with(height));
+        }
+
+        // metadata is dropped along with the oldest checkpoints
+        assert_eq!(t.checkpoint_meta(0), None);
+        assert_eq!(t.checkpoint_meta(1), Some(&5));
+        assert_eq!(t.checkpoint_meta(3), Some(&3));
+        assert_eq!(t.checkpoint_meta(4), None);
+        assert_eq!(
+            t.checkpoints()
+                .iter()
+                .map(|c| *c.meta())
+                .collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+
+        // metadata is returned on rewind
+        t.append(&"6".to_string());
+        assert_eq!(t.rewind_reporting().map(|r| r.into_meta()), Ok(5));
+        assert_eq!(t.checkpoint_meta(1), Some(&4));
+        assert_eq!(t.rewind_to_root(&t.root(2).unwrap()), Ok(2));
+        assert_eq!(t.checkpoints().len(), 0);
+
+        // checkpoints created without explicit metadata use the default value
+        t.checkpoint();
+        assert_eq!(t.checkpoint_meta(1), Some(&0));
+    }
+
+    #[test]
+    fn root_at_position() {
+        let mut t = BridgeTree::<String, 6>::new(100);
+        assert_eq!(t.root_at_position(0.into()), None);
+
+        let mut roots = vec![];
+        for (i, c) in ('a'..'u').enumerate() {
+            t.append(&c.to_string());
+            if i % 3 == 0 {
+                t.mark();
+                roots.push((Position::from(i), t.root(0).unwrap()));
+            } else if i % 7 == 0 {
+                t.checkpoint();
+                roots.push((Position::from(i), t.root(0).unwrap()));
+            }
+        }
+        assert!(roots.len() > 7);
+        for (pos, root) in &roots {
+            assert_eq!(t.root_at_position(*pos).as_ref(), Some(root));
+        }
+        // the current bridge always provides the current root
+        assert_eq!(t.root_at_position(19.into()), t.root(0));
+        // no bridge ends at an unmarked, uncheckpointed leaf
+        assert_eq!(t.root_at_position(1.into()), None);
+        assert_eq!(t.root_at_position(20.into()), None);
+    }
+
+    #[test]
+    fn witness_bundle() {
+        let mut frontier = NonEmptyFrontier::new("a".to_string());
+        for c in 'b'..='e' {
+            frontier.append(c.to_string());
+        }
+        let mut bundle = WitnessBundle::new(frontier.clone());
+        let mut expected = BridgeTree::<String, 4>::from_frontier(10, frontier);
+        expected.mark();
+        assert_eq!(
+            BridgeTree::from_witness_bundle(10, bundle.clone()),
+            expected
+        );
+
+        for c in 'f'..='n' {
+            bundle.append(c.to_string());
+            expected.append(&c.to_string());
+            let root = expected.root(0).unwrap();
+            assert_eq!(bundle.root(4), root);
+            assert_eq!(
+                Some(bundle.path(4).unwrap()),
+                expected.witness(Position::from(4), &root)
+            );
+            assert_eq!(
+                BridgeTree::from_witness_bundle(10, bundle.clone()),
+                expected
+            );
+        }
+
+        assert!(WitnessBundle::from_parts(bundle.tip().clone(), bundle.bridge().clone()).is_none());
+    }
+
+    #[test]
+    fn witness_layout() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        for (i, c) in ('a'..='p').enumerate() {
+            t.append(&c.to_string());
+            t.mark();
+            let tip = Position::from(i);
+            for pos in (0..=i).map(Position::from) {
+                let path = t.witness(pos, &t.root(0).unwrap()).unwrap();
+                let frontier = t.frontier_at(pos).unwrap();
+                for ((level, kind), value) in pos.witness_layout(4, tip).into_iter().zip(path) {
+                    match kind {
+                        SlotKind::OmmerFromFrontier => {
+                            assert_eq!(Some(&value), frontier.ommer_at(level));
+                        }
+                        SlotKind::CollectedFromFragment => {
+                            // the sibling begins with a leaf appended after the witnessed leaf
+                            assert!(value[..1] > frontier.leaf()[..]);
+                        }
+                        SlotKind::EmptyRoot => {
+                            assert_eq!(value, String::empty_root(level));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frontier_at() {
+        let mut t = BridgeTree::<String, 6>::new(100);
+        assert_eq!(t.frontier_at(0.into()), None);
+
+        let mut frontiers = vec![];
+        for (i, c) in ('a'..'u').enumerate() {
+            t.append(&c.to_string());
+            if i % 3 == 0 {
+                t.mark();
+                frontiers.push(t.frontier().unwrap().clone());
+            }
+        }
+        assert_eq!(frontiers.len(), 7);
+        for f in &frontiers {
+            assert_eq!(t.frontier_at(f.position()), Some(f));
+            assert_eq!(
+                t.root_at_position(f.position()),
+                Some(f.root(Some(6.into())))
+            );
+        }
+        assert_eq!(t.frontier_at(19.into()), t.frontier());
+        assert_eq!(t.frontier_at(1.into()), None);
+        assert_eq!(t.frontier_at(20.into()), None);
+    }
+
+    #[test]
+    fn root_if_nonempty() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        assert_eq!(t.root_if_nonempty(), None);
+        assert_eq!(t.root(0), Some(String::empty_root(4.into())));
+
+        t.append(&"a".to_string());
+        assert_eq!(t.root_if_nonempty(), t.root(0));
+        assert_eq!(t.root_if_nonempty(), Some("a_______________".to_string()));
+    }
+
+    #[test]
+    fn checkpoint_position() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        assert!(t.checkpoint());
+        assert_eq!(t.checkpoints()[0].position(t.prior_bridges()), None);
+        assert_eq!(t.checkpoint_position(1), None);
+
+        for c in 'a'..'e' {
+            t.append(&c.to_string());
+            if c == 'b' {
+                t.mark();
+            }
+            t.checkpoint();
+        }
+        let positions = t
+            .checkpoints()
+            .iter()
+            .map(|c| c.position(t.prior_bridges()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![
+                None,
+                Some(0.into()),
+                Some(1.into()),
+                Some(2.into()),
+                Some(3.into())
+            ]
+        );
+        assert_eq!(t.checkpoint_position(1), Some(3.into()));
+        assert_eq!(t.checkpoint_position(4), Some(0.into()));
+        assert_eq!(t.checkpoint_position(5), None);
+        assert_eq!(t.checkpoint_position(6), None);
+        assert_eq!(t.checkpoint_position(0), None);
+
+        // checkpoint positions survive garbage collection
+        t.garbage_collect();
+        assert_eq!(t.checkpoint_position(1), Some(3.into()));
+        assert_eq!(t.checkpoint_position(4), Some(0.into()));
+    }
+
+    #[test]
+    fn checkpoint_meta_serde_compatibility() {
+        let strip_meta = |mut value: serde_json::Value| {
+            for c in value["checkpoints"].as_array_mut().unwrap() {
+                assert!(c.as_object_mut().unwrap().remove("meta").is_some());
+            }
+            value
+        };
+
+        let mut t = BridgeTree::<String, 6>::new(10);
+        t.append(&"a".to_string());
+        t.checkpoint();
+        t.append(&"b".to_string());
+        t.checkpoint();
+        let legacy = strip_meta(serde_json::to_value(&t).unwrap());
+        assert_eq!(
+            serde_json::from_value::<BridgeTree<String, 6>>(legacy).unwrap(),
+            t
+        );
+
+        let mut t = DepthBridgeTree::<String, ConstDepth<6>, u32>::new(10);
+        t.append(&"a".to_string());
+        t.checkpoint_with(7);
+        let value = serde_json::to_value(&t).unwrap();
+        assert_eq!(
+            serde_json::from_value::<DepthBridgeTree<String, ConstDepth<6>, u32>>(value).unwrap(),
+            t
+        );
+        let legacy = strip_meta(serde_json::to_value(&t).unwrap());
+        let restored =
+            serde_json::from_value::<DepthBridgeTree<String, ConstDepth<6>, u32>>(legacy).unwrap();
+        assert_eq!(restored.checkpoint_meta(1), Some(&0));
+    }
+
+    #[test]
+    fn tree_frontier() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        assert!(t.frontier().is_none());
+        for c in 'a'..'l' {
+            t.append(&c.to_string());
+            if c == 'c' {
+                t.mark();
+            }
+            if c == 'f' {
+                t.checkpoint();
+            }
+            let frontier = t.frontier().unwrap();
+            assert_eq!(frontier.position(), t.current_position().unwrap());
+            assert_eq!(frontier.leaf(), t.current_leaf().unwrap());
+            assert_eq!(frontier.root(Some(t.depth())), t.root(0).unwrap());
+        }
+    }
+
+    #[test]
+    fn mark_meta() {
+        type NoteTree = DepthBridgeTree<String, ConstDepth<6>, (), u32>;
+        let mut t = NoteTree::new(2);
+        assert_eq!(t.mark_with(99), None);
+
+        t.append(&"a".to_string());
+        assert_eq!(t.mark_with(10), Some(0.into()));
+        t.append(&"b".to_string());
+        t.mark();
+        t.append(&"c".to_string());
+        assert_eq!(t.mark_with(12), Some(2.into()));
+        t.checkpoint();
+        assert_eq!(t.mark_meta(0.into()), Some(&10));
+        assert_eq!(t.mark_meta(1.into()), None);
+        assert_eq!(
+            t.marked_leaves()
+                .map(|(pos, leaf, meta)| (pos, leaf.as_str(), meta.cloned()))
+                .collect::<Vec<_>>(),
+            vec![
+                (0.into(), "a", Some(10)),
+                (1.into(), "b", None),
+                (2.into(), "c", Some(12)),
+            ]
+        );
+
+        // metadata for a mark created since the checkpoint is discarded with the mark, but
+        // metadata for a mark that may be restored is retained until it can no longer be.
+        t.append(&"d".to_string());
+        t.mark_with(13);
+        assert!(t.remove_mark(3.into()));
+        assert!(t.remove_mark(0.into()));
+        assert_eq!(t.mark_meta(0.into()), None);
+        assert_eq!(
+            t.marked_meta().keys().cloned().collect::<Vec<_>>(),
+            vec![0.into(), 2.into()]
+        );
+
+        // rewinding restores the removed mark along with its metadata; the metadata of the
+        // checkpointed leaf's mark survives the rewind.
+        assert!(t.rewind());
+        assert_eq!(t.mark_meta(0.into()), Some(&10));
+        assert_eq!(t.mark_meta(2.into()), Some(&12));
+        assert_eq!(t.mark_meta(3.into()), None);
+
+        // once the checkpoint that could restore a removed mark is dropped, the metadata for
+        // the mark is discarded.
+        t.checkpoint();
+        assert!(t.remove_mark(0.into()));
+        assert_eq!(t.marked_meta().len(), 2);
+        t.append(&"e".to_string());
+        t.checkpoint();
+        t.append(&"f".to_string());
+        t.checkpoint();
+        assert_eq!(
+            t.marked_meta().keys().cloned().collect::<Vec<_>>(),
+            vec![2.into()]
+        );
+
+        // the tree can be reconstructed from its parts, including mark metadata
+        let reconstructed = NoteTree::from_parts(
+            t.prior_bridges().to_vec(),
+            t.current_bridge().clone(),
+            t.marked_indices().clone(),
+            t.marked_meta().clone(),
+            t.checkpoints().to_vec(),
+            t.max_checkpoints(),
+        );
+        assert_eq!(reconstructed, Ok(t.clone()));
+        let mut invalid_meta = t.marked_meta().clone();
+        invalid_meta.insert(4.into(), 14);
+        assert_eq!(
+            NoteTree::from_parts(
+                t.prior_bridges().to_vec(),
+                t.current_bridge().clone(),
+                t.marked_indices().clone(),
+                invalid_meta,
+                t.checkpoints().to_vec(),
+                t.max_checkpoints(),
+            ),
+            Err(BridgeTreeError::MarkMetaMismatch(4.into()))
+        );
+
+        // trees serialized without mark metadata can be deserialized
+        let mut t = NoteTree::new(2);
+        t.append(&"a".to_string());
+        t.mark_with(10);
+        t.checkpoint();
+        let value = serde_json::to_value(&t).unwrap();
+        assert_eq!(
+            serde_json::from_value::<NoteTree>(value.clone()).unwrap(),
+            t
+        );
+        let mut value = value;
+        assert!(value.as_object_mut().unwrap().remove("mark_meta").is_some());
+        let restored = serde_json::from_value::<NoteTree>(value).unwrap();
+        assert_eq!(restored.marked_positions(), t.marked_positions());
+        assert!(restored.marked_meta().is_empty());
+    }
+
+    #[test]
+    fn mark_groups() {
+        use crate::testing::SipHashable;
+
+        type WalletTree = DepthBridgeTree<SipHashable, ConstDepth<9>, (), u32>;
+        let mut t = WalletTree::new(10);
+        for i in 0..240u64 {
+            t.append(&SipHashable(i));
+            match i % 7 {
+                0 | 3 => {
+                    t.mark_in_group(1);
+                }
+                2 | 5 => {
+                    t.mark_in_group(2);
+                }
+                6 => {
+                    t.mark();
+                }
+                _ => {}
+            }
+            if i % 40 == 39 {
+                t.checkpoint();
+            }
+        }
+
+        let root = t.root(0).unwrap();
+        let group_2 = t.witnesses_in_group(&2, &root);
+        assert_eq!(group_2.len(), t.marked_leaves_in_group(&2).count());
+        assert!(group_2.keys().all(|pos| u64::from(*pos) % 7 % 3 == 2));
+        let ungrouped = t
+            .marked_leaves()
+            .filter(|(_, _, meta)| meta.is_none())
+            .map(|(pos, _, _)| (pos, t.witness(pos, &root).unwrap()))
+            .collect::<BTreeMap<_, _>>();
+
+        let group_1_len = t.marked_leaves_in_group(&1).count();
+        assert_eq!(t.remove_group(&1), group_1_len);
+        assert_eq!(t.remove_group(&1), 0);
+        assert_eq!(t.marked_leaves_in_group(&1).count(), 0);
+        assert!(t.witnesses_in_group(&1, &root).is_empty());
+
+        // the other groups' witnesses are undisturbed, both as of the removal and as more
+        // leaves are appended.
+        assert_eq!(t.witnesses_in_group(&2, &root), group_2);
+        for (pos, path) in &ungrouped {
+            assert_eq!(t.witness(*pos, &root).as_ref(), Some(path));
+        }
+        for i in 240..280u64 {
+            t.append(&SipHashable(i));
+        }
+        let root = t.root(0).unwrap();
+        let group_2 = t.witnesses_in_group(&2, &root);
+        assert_eq!(group_2.len(), t.marked_leaves_in_group(&2).count());
+        for (pos, path) in &group_2 {
+            assert_eq!(t.witness_verified(*pos, &root).as_ref(), Some(path));
+        }
+        assert!(t.verify_root_consistency());
+
+        // rewinding restores the removed group
+        t.checkpoint();
+        t.remove_group(&2);
+        assert!(t.rewind());
+        assert_eq!(t.witnesses_in_group(&2, &root), group_2);
+    }
+
+    #[test]
+    fn gc_stats() {
+        let mut t = BridgeTree::<String, 6>::new(3);
+        for c in 'a'..='h' {
+            t.append(&c.to_string());
+            t.mark();
+        }
+        for pos in 1..5 {
+            assert!(t.remove_mark(pos.into()));
+        }
+        // garbage collection is not possible until the maximum number of checkpoints exist, but
+        // the index of the four remaining marked values is reported
+        let index_bytes = 4 * (size_of::<String>() + size_of::<Position>());
+        assert_eq!(
+            t.gc_stats(),
+            GcStats {
+                index_bytes,
+                ..GcStats::default()
+            }
+        );
+
+        for _ in 0..3 {
+            t.checkpoint();
+        }
+        let stats = t.gc_stats();
+        assert_eq!(stats.bridges(), 4);
+        assert!(stats.bytes() >= 4 * size_of::<MerkleBridge<String>>());
+        assert_eq!(stats.index_bytes(), index_bytes);
+
+        // a mark removed since the oldest checkpoint may be restored, and so its bridge is not
+        // collectable, although its value is removed from the index
+        assert!(t.remove_mark(5.into()));
+        let index_bytes = 3 * (size_of::<String>() + size_of::<Position>());
+        assert_eq!(
+            t.gc_stats(),
+            GcStats {
+                index_bytes,
+                ..stats
+            }
+        );
+
+        let bridges_len = t.prior_bridges().len();
+        let usage = t.dynamic_memory_usage();
+        t.garbage_collect();
+        t.shrink_to_fit();
+        assert_eq!(t.prior_bridges().len(), bridges_len - stats.bridges());
+        assert!(t.dynamic_memory_usage() < usage);
+        assert_eq!(t.gc_stats().bridges(), 0);
+        assert_eq!(t.gc_stats().index_bytes(), index_bytes);
+    }
+
+    #[test]
+    fn prune_to_marks() {
+        let mut t = BridgeTree::<String, 6>::new(10);
+        for (i, c) in ('a'..='z').enumerate() {
+            t.append(&c.to_string());
+            if c == 'c' || c == 'q' {
+                t.mark();
+            }
+            if i % 3 == 0 {
+                t.checkpoint();
+            }
+        }
+        let root = t.root(0).unwrap();
+        let expected: Vec<_> = t
+            .marked_positions()
+            .into_iter()
+            .map(|pos| (pos, t.witness(pos, &root).unwrap()))
+            .collect();
+
+        t.prune_to_marks();
+        assert!(t.checkpoints().is_empty());
+        // one bridge ending at each marked position, plus the bridge leading up to the
+        // current bridge
+        assert_eq!(t.prior_bridges().len(), 3);
+        assert_eq!(t.root(0), Some(root.clone()));
+        for (pos, path) in expected {
+            let leaf = t.get_marked_leaf(pos).unwrap().clone();
+            assert_eq!(t.witness(pos, &root), Some(path.clone()));
+            assert_eq!(root_from_witness(leaf, pos, &path, String::combine), root);
+        }
+
+        // the pruned tree can no longer be rewound, but continues to maintain witnesses
+        assert!(!t.rewind());
+        t.append(&"A".to_string());
+        let root = t.root(0).unwrap();
+        for pos in t.marked_positions() {
+            assert!(t.witness(pos, &root).is_some());
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics_counters() {
+        use metrics::{
+            Counter, CounterFn, Gauge, Histogram, Key, KeyName, Recorder, SharedString, Unit,
+        };
+        use std::cell::RefCell;
+        use std::sync::Arc;
+
+        thread_local! {
+            static COUNTS: RefCell<BTreeMap<String, u64>> = RefCell::new(BTreeMap::new());
+        }
+
+        // Counts are recorded per thread so that they are not affected by other tests that are
+        // run concurrently.
+        struct ThreadCounter(String);
+        impl CounterFn for ThreadCounter {
+            fn increment(&self, value: u64) {
+                COUNTS.with(|c| *c.borrow_mut().entry(self.0.clone()).or_insert(0) += value);
+            }
+
+            fn absolute(&self, _: u64) {
+                unimplemented!()
+            }
+        }
+
+        struct ThreadRecorder;
+        impl Recorder for ThreadRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+            fn register_counter(&self, key: &Key) -> Counter {
+                Counter::from_arc(Arc::new(ThreadCounter(key.name().to_string())))
+            }
+
+            fn register_gauge(&self, _: &Key) -> Gauge {
+                Gauge::noop()
+            }
+
+            fn register_histogram(&self, _: &Key) -> Histogram {
+                Histogram::noop()
+            }
+        }
+
+        static RECORDER: ThreadRecorder = ThreadRecorder;
+        metrics::set_recorder(&RECORDER).unwrap();
+        let count = |name: &str| COUNTS.with(|c| c.borrow().get(name).cloned().unwrap_or(0));
+
+        let mut t = BridgeTree::<String, 4>::new(2);
+        t.append(&"a".to_string());
+        t.mark();
+        t.mark();
+        t.append(&"b".to_string());
+        t.checkpoint();
+        t.append(&"c".to_string());
+        t.mark();
+        t.checkpoint();
+        // exceeds max_checkpoints, and so drops the first checkpoint
+        t.checkpoint();
+
+        let combines = count("bridgetree_combines");
+        assert!(combines > 0);
+        assert!(t.witness(0.into(), &t.root(0).unwrap()).is_some());
+        assert!(count("bridgetree_combines") > combines);
+
+        assert!(t.remove_mark(2.into()));
+        assert!(!t.remove_mark(3.into()));
+        assert!(t.rewind());
+        assert!(t.rewind());
+        assert!(!t.rewind());
+        assert_eq!(t.drain_checkpoints(0).len(), 0);
+
+        assert_eq!(count("bridgetree_appends"), 3);
+        assert_eq!(count("bridgetree_marks_added"), 2);
+        assert_eq!(count("bridgetree_marks_removed"), 1);
+        assert_eq!(count("bridgetree_checkpoints_added"), 3);
+        assert_eq!(count("bridgetree_checkpoints_dropped"), 1);
+        assert_eq!(count("bridgetree_rewinds"), 3);
+        assert_eq!(count("bridgetree_rewinds_failed"), 1);
+        assert_eq!(count("bridgetree_witnesses"), 1);
+
+        // garbage collection computes no hashes or witnesses, and the root consistency check
+        // that follows it in debug builds is not counted, so the counts do not depend upon the
+        // build profile
+        for c in 'd'..='g' {
+            t.append(&c.to_string());
+            t.mark();
+            t.checkpoint();
+        }
+        let combines = count("bridgetree_combines");
+        assert_eq!(t.prior_bridges().len(), 7);
+        t.garbage_collect();
+        // only the bridge ending at the formerly checkpointed position 1 is fused away
+        assert_eq!(t.prior_bridges().len(), 6);
+        assert_eq!(count("bridgetree_combines"), combines);
+        assert_eq!(count("bridgetree_witnesses"), 1);
+
+        // the witness for position 3 computes the roots of the two retained checkpoints at
+        // positions 5 and 6 with 4 hashes each in order to find the checkpoint that matches the
+        // requested root, and 2 hashes for the root of the subtree spanning positions 4 to 7
+        let root = t.root(0).unwrap();
+        let combines = count("bridgetree_combines");
+        assert!(t.witness(3.into(), &root).is_some());
+        assert_eq!(count("bridgetree_combines"), combines + 10);
+        assert_eq!(count("bridgetree_witnesses"), 2);
+    }
+
+    #[test]
+    fn mark_status() {
+        let mut t = BridgeTree::<String, 4>::new(10);
+        assert_eq!(t.mark_status(), MarkStatus::EmptyTree);
+
+        t.append(&"a".to_string());
+        assert_eq!(t.mark_status(), MarkStatus::New(0.into()));
+        assert_eq!(t.mark_status(), MarkStatus::AlreadyMarked(0.into()));
+
+        // marking a checkpointed leaf
+        t.append(&"b".to_string());
+        t.checkpoint();
+        assert_eq!(t.mark_status(), MarkStatus::New(1.into()));
+        assert_eq!(t.mark_status(), MarkStatus::AlreadyMarked(1.into()));
+        assert_eq!(t.mark(), Some(1.into()));
+
+        // the mark is removed by a rewind, after which the leaf may be marked again
+        assert!(t.rewind());
+        assert_eq!(t.mark_status(), MarkStatus::New(1.into()));
+        assert!(t.remove_mark(0.into()));
+        assert_eq!(t.marked_positions(), BTreeSet::from([1.into()]));
+    }
+
+    #[test]
+    fn remark_after_remove() {
+        fn check_witness(t: &BridgeTree<String, 4>, pos: Position, leaf: &str) {
+            let root = t.root(0).unwrap();
+            let path = t.witness(pos, &root).unwrap();
+            assert_eq!(
+                root_from_witness(leaf.to_string(), pos, &path, String::combine),
+                root
             );
         }
 
-        #[test]
-        fn prop_garbage_collect(
-            tree in arb_bridgetree((97u8..123).prop_map(|c| char::from(c).to_string()), 100)
-        ) {
-            let mut tree_mut = tree.clone();
-            // ensure we have enough checkpoints to not rewind past the state `tree` is in
-            for _ in 0..10 {
-                tree_mut.checkpoint();
+        // mark, remove, and re-mark without appending: the mark refers to the same position
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.append(&"b".to_string());
+        assert_eq!(t.mark_status(), MarkStatus::New(1.into()));
+        assert!(t.remove_mark(1.into()));
+        assert_eq!(t.mark_status(), MarkStatus::New(1.into()));
+        assert_eq!(t.marked_positions(), BTreeSet::from([1.into()]));
+        t.append(&"c".to_string());
+        check_witness(&t, 1.into(), "b");
+
+        // mark, remove, append the same value and re-mark: the mark refers to the new position,
+        // and no witness is available for the prior occurrence
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.append(&"b".to_string());
+        assert_eq!(t.mark(), Some(1.into()));
+        assert!(t.remove_mark(1.into()));
+        t.append(&"b".to_string());
+        assert_eq!(t.mark_status(), MarkStatus::New(2.into()));
+        assert_eq!(t.marked_positions(), BTreeSet::from([2.into()]));
+        let root = t.root(0).unwrap();
+        assert_eq!(t.witness(1.into(), &root), None);
+        check_witness(&t, 2.into(), "b");
+
+        // the same, with the removal occurring within a checkpoint span; re-marking the new
+        // occurrence does not restore the prior mark, but a rewind does
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.append(&"b".to_string());
+        t.mark();
+        t.checkpoint();
+        assert!(t.remove_mark(1.into()));
+        t.append(&"b".to_string());
+        assert_eq!(t.mark_status(), MarkStatus::New(2.into()));
+        assert_eq!(t.marked_positions(), BTreeSet::from([2.into()]));
+        check_witness(&t, 2.into(), "b");
+        assert!(t.rewind());
+        assert_eq!(t.marked_positions(), BTreeSet::from([1.into()]));
+        check_witness(&t, 1.into(), "b");
+
+        // removing and re-marking at the tip within a checkpoint span, then rewinding, leaves
+        // the original mark in place
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.mark();
+        t.checkpoint();
+        assert!(t.remove_mark(0.into()));
+        assert_eq!(t.mark_status(), MarkStatus::New(0.into()));
+        assert!(t.rewind());
+        assert_eq!(t.marked_positions(), BTreeSet::from([0.into()]));
+        t.append(&"a".to_string());
+        check_witness(&t, 0.into(), "a");
+
+        // repeated removal and re-marking without appending does not accumulate bridges
+        let mut t = BridgeTree::<String, 4>::new(10);
+        t.append(&"a".to_string());
+        t.mark();
+        let bridges_len = t.prior_bridges().len();
+        for _ in 0..3 {
+            assert!(t.remove_mark(0.into()));
+            assert_eq!(t.mark_status(), MarkStatus::New(0.into()));
+        }
+        assert!(t.prior_bridges().len() <= bridges_len);
+        t.append(&"b".to_string());
+        check_witness(&t, 0.into(), "a");
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize() {
+        use std::cell::Cell;
+        use zeroize::{Zeroize, ZeroizeOnDrop};
+
+        thread_local! {
+            static LIVE: Cell<usize> = Cell::new(0);
+            static ZEROIZED: Cell<usize> = Cell::new(0);
+            static DROPPED_UNSCRUBBED: Cell<usize> = Cell::new(0);
+        }
+
+        // A hash type that counts live values, calls to `zeroize`, and values that are dropped
+        // without having been zeroized. It does not zeroize itself on drop, so that every call
+        // to `zeroize` is made by the tree.
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Canary(String);
+
+        impl Canary {
+            fn new(value: String) -> Self {
+                LIVE.with(|c| c.set(c.get() + 1));
+                Canary(value)
             }
+        }
 
-            tree_mut.garbage_collect();
+        impl Clone for Canary {
+            fn clone(&self) -> Self {
+                Canary::new(self.0.clone())
+            }
+        }
 
-            tree_mut.rewind();
+        impl Zeroize for Canary {
+            fn zeroize(&mut self) {
+                self.0.zeroize();
+                ZEROIZED.with(|c| c.set(c.get() + 1));
+            }
+        }
 
-            for pos in tree.saved.keys() {
-                assert_eq!(
-                    tree.witness(*pos, &tree.root(0).unwrap()),
-                    tree_mut.witness(*pos, &tree.root(0).unwrap())
-                );
+        impl Drop for Canary {
+            fn drop(&mut self) {
+                if !self.0.is_empty() {
+                    DROPPED_UNSCRUBBED.with(|c| c.set(c.get() + 1));
+                }
+                LIVE.with(|c| c.set(c.get() - 1));
+            }
+        }
+
+        impl Hashable for Canary {
+            fn empty_leaf() -> Self {
+                Canary::new(String::empty_leaf())
+            }
+
+            fn combine(level: Level, a: &Self, b: &Self) -> Self {
+                Canary::new(String::combine(level, &a.0, &b.0))
+            }
+
+            fn scrub(&mut self) {
+                self.zeroize();
+            }
+        }
+
+        // the tree types zeroize on drop whenever their hash type does
+        fn assert_zeroize_on_drop<T: ZeroizeOnDrop>() {}
+        #[allow(dead_code)]
+        fn zeroize_on_drop<H: Hashable + ZeroizeOnDrop>() {
+            assert_zeroize_on_drop::<NonEmptyFrontier<H>>();
+            assert_zeroize_on_drop::<super::Frontier<H, 4>>();
+            assert_zeroize_on_drop::<MerkleBridge<H>>();
+            assert_zeroize_on_drop::<BridgeTree<H, 4>>();
+            assert_zeroize_on_drop::<DynBridgeTree<H>>();
+        }
+
+        let live = || LIVE.with(|c| c.get());
+        let zeroized = || ZEROIZED.with(|c| c.get());
+        let unscrubbed = || DROPPED_UNSCRUBBED.with(|c| c.get());
+
+        let mut t = BridgeTree::<Canary, 4>::new(10);
+        for c in 'a'..'h' {
+            t.append_owned(Canary::new(c.to_string()));
+            if c == 'b' || c == 'd' {
+                t.mark();
+            }
+            if c == 'e' {
+                t.checkpoint();
             }
         }
+        assert_eq!(t.root(0).unwrap().0, "abcdefg_________");
+
+        // each value discarded by rewinding or removing marks, including the bridges that are
+        // fused once no marks remain, is zeroized by the tree before it is dropped
+        let (live_before, zeroized_before, unscrubbed_before) = (live(), zeroized(), unscrubbed());
+        assert!(t.rewind());
+        assert!(t.remove_mark(1.into()));
+        assert!(t.remove_mark(3.into()));
+        assert!(live() < live_before);
+        assert!(zeroized() > zeroized_before);
+        assert_eq!(unscrubbed(), unscrubbed_before);
+
+        // ommers replaced as leaves are appended are zeroized in the same fashion
+        let unscrubbed_before = unscrubbed();
+        let mut frontier = super::Frontier::<Canary, 4>::empty();
+        for c in 'a'..'h' {
+            frontier.append_owned(Canary::new(c.to_string()));
+        }
+        assert_eq!(unscrubbed(), unscrubbed_before);
+
+        // zeroizing the tree scrubs every value that it holds, and leaves it empty
+        assert!(live() > 0);
+        t.zeroize();
+        frontier.zeroize();
+        assert_eq!(live(), 0);
+        assert_eq!(unscrubbed(), unscrubbed_before);
+        assert_eq!(t.current_position(), None);
+        assert_eq!(t.marked_positions(), BTreeSet::new());
+        assert_eq!(frontier, super::Frontier::empty());
+        assert_eq!(t.root(0).unwrap().0, "________________");
     }
 
     #[test]
@@ -1472,6 +7880,394 @@ mod tests {
         tests::check_rewind_remove_mark(BridgeTree::<String, 4>::new);
     }
 
+    #[test]
+    fn rewind_to() {
+        tests::check_rewind_to(BridgeTree::<String, 4>::new);
+    }
+
+    #[test]
+    fn tree_conformance() {
+        run_tree_conformance(|| BridgeTree::<String, 4>::new(5));
+        run_tree_conformance(|| BridgeTree::<SipHashable, 8>::new(100));
+        run_tree_conformance(|| DynBridgeTree::<String>::new(Level::from(3), 5));
+    }
+
+    #[test]
+    fn dyn_root_hashes() {
+        tests::check_root_hashes(|max_c| DynBridgeTree::<String>::new(Level::from(4), max_c));
+    }
+
+    #[test]
+    fn dyn_witnesss() {
+        tests::check_witnesss(|max_c| DynBridgeTree::<String>::new(Level::from(4), max_c));
+    }
+
+    #[test]
+    fn dyn_checkpoint_rewind() {
+        tests::check_checkpoint_rewind(|max_c| DynBridgeTree::<String>::new(Level::from(4), max_c));
+    }
+
+    #[test]
+    fn dyn_rewind_remove_mark() {
+        tests::check_rewind_remove_mark(|max_c| {
+            DynBridgeTree::<String>::new(Level::from(4), max_c)
+        });
+    }
+
+    #[test]
+    fn dyn_rewind_to() {
+        tests::check_rewind_to(|max_c| DynBridgeTree::<String>::new(Level::from(4), max_c));
+    }
+
+    #[test]
+    fn dyn_tree_depth() {
+        let mut tree = DynBridgeTree::<String>::new(Level::from(3), 100);
+        for c in 'a'..'i' {
+            assert!(tree.append(&c.to_string()))
+        }
+        assert!(!tree.append(&'i'.to_string()));
+    }
+
+    #[test]
+    fn dyn_tree_conversions() {
+        let mut tree = BridgeTree::<String, 4>::new(100);
+        tree.append(&"a".to_string());
+        tree.mark();
+        tree.checkpoint();
+        tree.append(&"b".to_string());
+
+        let dyn_tree = DynBridgeTree::from(tree.clone());
+        assert_eq!(dyn_tree.depth(), Level::from(4));
+        assert_eq!(dyn_tree.root(0), tree.root(0));
+        assert_eq!(dyn_tree.root(1), tree.root(1));
+        assert_eq!(
+            dyn_tree.witness(0.into(), &dyn_tree.root(0).unwrap()),
+            tree.witness(0.into(), &tree.root(0).unwrap())
+        );
+
+        assert_eq!(
+            BridgeTree::<String, 5>::try_from(dyn_tree.clone()),
+            Err(BridgeTreeError::DepthMismatch {
+                expected: Level::from(5),
+                found: Level::from(4)
+            })
+        );
+        assert_eq!(BridgeTree::<String, 4>::try_from(dyn_tree), Ok(tree));
+    }
+
+    #[test]
+    fn domain_separated_leaves() {
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Tagged(String);
+
+        impl Tagged {
+            // the leaf hash is applied by the caller before values are appended
+            fn leaf(data: &str) -> Self {
+                Tagged(format!("L({})", data))
+            }
+        }
+
+        impl Hashable for Tagged {
+            fn empty_leaf() -> Self {
+                Tagged("E".to_string())
+            }
+
+            fn combine(level: Level, a: &Self, b: &Self) -> Self {
+                Tagged(format!("N{}({},{})", u8::from(level), a.0, b.0))
+            }
+        }
+
+        let mut tree = BridgeTree::<Tagged, 2>::new(10);
+        let mut frontier = super::Frontier::<Tagged, 2>::empty();
+        for data in ["a", "b", "c"] {
+            assert!(tree.append(&Tagged::leaf(data)));
+            assert!(frontier.append(&Tagged::leaf(data)));
+            if data == "a" {
+                tree.mark();
+            }
+        }
+
+        let root = Tagged("N1(N0(L(a),L(b)),N0(L(c),E))".to_string());
+        assert_eq!(tree.root(0), Some(root.clone()));
+        assert_eq!(frontier.root(), root);
+        assert_eq!(
+            tree.witness(Position::from(0), &root),
+            Some(vec![Tagged::leaf("b"), Tagged("N0(L(c),E)".to_string())])
+        );
+    }
+
+    #[test]
+    fn serde_fixture() {
+        let mut tree = BridgeTree::<String, 3>::new(10);
+        tree.append(&"a".to_string());
+        tree.append(&"b".to_string());
+        tree.checkpoint();
+        tree.append(&"c".to_string());
+        tree.mark();
+
+        // the serialized form of a tree is persisted by callers, and so must not change
+        // without also providing for the deserialization of previously serialized trees
+        let fixture = serde_json::json!({
+            "depth": 3,
+            "prior_bridges": [
+                {
+                    "prior_position": null,
+                    "tracking": [],
+                    "ommers": {},
+                    "frontier": { "position": 1, "leaf": "b", "ommers": ["a"] }
+                },
+                {
+                    "prior_position": 1,
+                    "tracking": [{ "level": 0, "index": 2 }],
+                    "ommers": {},
+                    "frontier": { "position": 2, "leaf": "c", "ommers": ["ab"] }
+                }
+            ],
+            "current_bridge": {
+                "prior_position": 2,
+                "tracking": [{ "level": 0, "index": 2 }],
+                "ommers": {},
+                "frontier": { "position": 2, "leaf": "c", "ommers": ["ab"] }
+            },
+            "saved": { "2": 1 },
+            "mark_meta": {},
+            "checkpoints": [
+                { "bridges_len": 1, "is_marked": false, "marked": [2], "forgotten": {}, "meta": null }
+            ],
+            "max_checkpoints": 10
+        });
+        assert_eq!(serde_json::to_value(&tree).unwrap(), fixture);
+        assert_eq!(
+            serde_json::from_value::<BridgeTree<String, 3>>(fixture.clone()).unwrap(),
+            tree
+        );
+
+        // trees serialized before the introduction of mark and checkpoint metadata
+        let mut legacy = fixture;
+        legacy.as_object_mut().unwrap().remove("mark_meta");
+        legacy["checkpoints"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("meta");
+        assert_eq!(
+            serde_json::from_value::<BridgeTree<String, 3>>(legacy).unwrap(),
+            tree
+        );
+
+        // the same tree as serialized by the initial release, before the introduction of the
+        // depth of the tree and of metadata
+        let baseline = concat!(
+            r#"{"prior_bridges":[{"prior_position":null,"tracking":[],"ommers":{},"#,
+            r#""frontier":{"position":1,"leaf":"b","ommers":["a"]}},"#,
+            r#"{"prior_position":1,"tracking":[{"level":0,"index":2}],"ommers":{},"#,
+            r#""frontier":{"position":2,"leaf":"c","ommers":["ab"]}}],"#,
+            r#""current_bridge":{"prior_position":2,"tracking":[{"level":0,"index":2}],"#,
+            r#""ommers":{},"frontier":{"position":2,"leaf":"c","ommers":["ab"]}},"#,
+            r#""saved":{"2":1},"#,
+            r#""checkpoints":[{"bridges_len":1,"is_marked":false,"marked":[2],"forgotten":{}}],"#,
+            r#""max_checkpoints":10}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<BridgeTree<String, 3>>(baseline).unwrap(),
+            tree
+        );
+    }
+
+    #[test]
+    fn serde_invariants() {
+        let mut tree = BridgeTree::<String, 3>::new(10);
+        tree.append(&"a".to_string());
+        tree.append(&"b".to_string());
+        tree.checkpoint();
+        tree.append(&"c".to_string());
+        tree.mark();
+        let fixture = serde_json::to_value(&tree).unwrap();
+        let from_value_err = |value: serde_json::Value| {
+            serde_json::from_value::<BridgeTree<String, 3>>(value)
+                .unwrap_err()
+                .to_string()
+        };
+
+        // a mark that refers to a bridge that does not exist
+        let mut invalid = fixture.clone();
+        invalid["saved"] = serde_json::json!({ "2": 5 });
+        assert_eq!(
+            from_value_err(invalid),
+            "a mark refers to bridge 5, which does not exist"
+        );
+
+        // bridges that are out of order
+        let mut invalid = fixture.clone();
+        invalid["prior_bridges"].as_array_mut().unwrap().swap(0, 1);
+        assert_eq!(
+            from_value_err(invalid),
+            format!(
+                "the mark at {:?} refers to a bridge ending at {:?}",
+                Position::from(2),
+                Position::from(1)
+            )
+        );
+
+        // checkpoints that are out of order
+        let mut invalid = fixture.clone();
+        let checkpoint = invalid["checkpoints"][0].clone();
+        invalid["checkpoints"][0]["bridges_len"] = serde_json::json!(2);
+        invalid["checkpoints"]
+            .as_array_mut()
+            .unwrap()
+            .push(checkpoint);
+        assert_eq!(
+            from_value_err(invalid),
+            "checkpoint 1 retains fewer bridges than the checkpoint before it"
+        );
+
+        // a frontier with fewer ommers than its position requires
+        let mut invalid = fixture.clone();
+        invalid["current_bridge"]["frontier"]["ommers"]
+            .as_array_mut()
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(
+            from_value_err(invalid),
+            format!(
+                "a frontier at {:?} requires 1 ommers, found 0",
+                Position::from(2)
+            )
+        );
+        // the same frontier is rejected when the tree is constructed from its parts
+        let mut parts = tree.clone().into_parts();
+        parts.current_bridge.as_mut().unwrap().frontier.ommers.pop();
+        assert_eq!(
+            BridgeTree::<String, 3>::from_parts(
+                parts.prior_bridges,
+                parts.current_bridge,
+                parts.saved,
+                parts.mark_meta,
+                parts.checkpoints,
+                parts.max_checkpoints,
+            ),
+            Err(BridgeTreeError::InvalidFrontier(tree.prior_bridges().len()))
+        );
+
+        // a leaf beyond the capacity of the tree
+        let mut invalid = fixture;
+        invalid["depth"] = serde_json::json!(1);
+        assert!(serde_json::from_value::<DynBridgeTree<String>>(invalid.clone()).is_err());
+        assert_eq!(
+            serde_json::from_value::<DepthBridgeTree<String, Level>>(invalid)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "the leaf at {:?} exceeds the capacity of the tree",
+                Position::from(2)
+            )
+        );
+    }
+
+    #[test]
+    fn serde_depth_validation() {
+        let mut frontier = super::Frontier::<String, 8>::empty();
+        frontier.append(&"a".to_string());
+        let bytes = serde_json::to_vec(&frontier).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<super::Frontier<String, 8>>(&bytes).unwrap(),
+            frontier
+        );
+        let err = serde_json::from_slice::<super::Frontier<String, 4>>(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("tree depth mismatch: expected 4, found 8"));
+
+        // a frontier whose position exceeds the capacity of a tree of the serialized depth
+        let mut frontier = super::Frontier::<String, 8>::empty();
+        for i in 0..101 {
+            frontier.append(&i.to_string());
+        }
+        let mut value = serde_json::to_value(&frontier).unwrap();
+        value["depth"] = serde_json::json!(3);
+        assert_eq!(
+            serde_json::from_value::<super::Frontier<String, 3>>(value)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "a frontier at {:?} exceeds the capacity of a tree of depth 3",
+                Position::from(100)
+            )
+        );
+
+        // a frontier with fewer ommers than its position requires
+        let mut value = serde_json::to_value(&frontier).unwrap();
+        value["frontier"]["ommers"].as_array_mut().unwrap().pop();
+        assert_eq!(
+            serde_json::from_value::<super::Frontier<String, 8>>(value)
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "a frontier at {:?} requires 3 ommers, found 2",
+                Position::from(100)
+            )
+        );
+
+        let mut tree = BridgeTree::<String, 8>::new(10);
+        tree.append(&"a".to_string());
+        tree.mark();
+        tree.checkpoint();
+        let bytes = serde_json::to_vec(&tree).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<BridgeTree<String, 8>>(&bytes).unwrap(),
+            tree
+        );
+        let err = serde_json::from_slice::<BridgeTree<String, 4>>(&bytes).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("tree depth mismatch: expected 4, found 8"));
+
+        // a tree with a runtime depth can read the depth of a tree serialized with a
+        // statically known depth
+        let dyn_tree = serde_json::from_slice::<DynBridgeTree<String>>(&bytes).unwrap();
+        assert_eq!(dyn_tree.depth(), Level::from(8));
+    }
+
+    #[test]
+    fn serde_legacy_depth() {
+        // frontiers and trees serialized before the depth was included in the serialized form
+        let legacy_frontier = r#"{"frontier":{"position":0,"leaf":"a","ommers":[]}}"#;
+        let legacy_tree = concat!(
+            r#"{"prior_bridges":[{"prior_position":null,"tracking":[],"ommers":{},"#,
+            r#""frontier":{"position":0,"leaf":"a","ommers":[]}},"#,
+            r#"{"prior_position":0,"tracking":[{"level":1,"index":0}],"ommers":{},"#,
+            r#""frontier":{"position":1,"leaf":"b","ommers":["a"]}}],"#,
+            r#""current_bridge":{"prior_position":1,"tracking":[{"level":1,"index":0}],"#,
+            r#""ommers":{},"frontier":{"position":1,"leaf":"b","ommers":["a"]}},"#,
+            r#""saved":{"1":1},"#,
+            r#""checkpoints":[{"bridges_len":1,"is_marked":false,"marked":[1],"forgotten":{}}],"#,
+            r#""max_checkpoints":10}"#
+        );
+
+        // a missing depth is taken to be the depth of the type being deserialized
+        let mut frontier = super::Frontier::<String, 8>::empty();
+        frontier.append(&"a".to_string());
+        assert_eq!(
+            serde_json::from_str::<super::Frontier<String, 8>>(legacy_frontier).unwrap(),
+            frontier
+        );
+
+        let mut tree = BridgeTree::<String, 8>::new(10);
+        tree.append(&"a".to_string());
+        tree.checkpoint();
+        tree.append(&"b".to_string());
+        tree.mark();
+        assert_eq!(
+            serde_json::from_str::<BridgeTree<String, 8>>(legacy_tree).unwrap(),
+            tree
+        );
+
+        // the depth of a tree whose depth is not known statically must be present
+        let err = serde_json::from_str::<DynBridgeTree<String>>(legacy_tree).unwrap_err();
+        assert!(err.to_string().contains("missing field `depth`"));
+    }
+
     #[test]
     fn garbage_collect() {
         let mut t = BridgeTree::<String, 7>::new(10);
@@ -1500,14 +8296,15 @@ mod tests {
         assert_eq!(t.prior_bridges().len(), 20 + 14 - 2);
         let witnesss = has_witness
             .iter()
-            .map(|pos| match t.witness_inner(*pos, &t.root(0).unwrap()) {
-                Ok(path) => path,
+            .map(|pos| match t.witness_with_leaf(*pos, &t.root(0).unwrap()) {
+                Ok((path, _)) => path,
                 Err(e) => panic!("Failed to get auth path: {:?}", e),
             })
             .collect::<Vec<_>>();
         t.garbage_collect();
-        // 20 = 32 - 10 (removed checkpoints) + 1 (not removed due to mark) - 3 (removed marks)
-        assert_eq!(t.prior_bridges().len(), 32 - 10 + 1 - 3);
+        // 21 = 32 - 10 (removed checkpoints) + 1 (not removed due to mark) - 3 (removed marks)
+        //      + 1 (the oldest retained checkpoint)
+        assert_eq!(t.prior_bridges().len(), 32 - 10 + 1 - 3 + 1);
         let retained_witnesss = has_witness
             .iter()
             .map(|pos| {
@@ -1,9 +1,10 @@
 //! Types that describe positions within a Merkle tree
 
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::num::TryFromIntError;
+use core::ops::{Add, AddAssign, Range, Sub};
 use serde::{Deserialize, Serialize};
-use std::convert::{TryFrom, TryInto};
-use std::num::TryFromIntError;
-use std::ops::{Add, AddAssign, Range};
 
 /// A type-safe wrapper for indexing into "levels" of a binary tree, such that
 /// nodes at level `0` are leaves, nodes at level `1` are parents of nodes at
@@ -14,10 +15,35 @@ use std::ops::{Add, AddAssign, Range};
 pub struct Level(u8);
 
 impl Level {
+    /// Returns an iterator over the levels from `self` (inclusive) to `other` (exclusive),
+    /// in ascending order.
+    ///
+    /// This is useful when folding a value up the tree one level at a time, as is done
+    /// when computing a root from a leaf and its witness.
+    ///
+    /// ```
+    /// use bridgetree::Level;
+    ///
+    /// let levels: Vec<u8> = Level::from(0).iter_to(Level::from(3)).map(u8::from).collect();
+    /// assert_eq!(levels, vec![0, 1, 2]);
+    /// ```
     // TODO: replace with an instance for `Step<Level>` once `step_trait`
     // is stabilized
     pub fn iter_to(self, other: Level) -> impl Iterator<Item = Self> {
-        (self.0..other.0).into_iter().map(Level)
+        (self.0..other.0).map(Level)
+    }
+
+    /// Returns an iterator over the levels in the half-open range `from..to`. This is
+    /// equivalent to `from.iter_to(to)`.
+    ///
+    /// ```
+    /// use bridgetree::Level;
+    ///
+    /// assert_eq!(Level::range(Level::from(2), Level::from(2)).count(), 0);
+    /// assert!(Level::range(Level::from(0), Level::from(3)).eq(Level::from(0).iter_to(Level::from(3))));
+    /// ```
+    pub fn range(from: Level, to: Level) -> impl Iterator<Item = Self> {
+        from.iter_to(to)
     }
 }
 
@@ -52,6 +78,25 @@ impl From<Level> for usize {
 pub struct Position(usize);
 
 impl Position {
+    /// Returns the position of the first leaf of the subtree with the specified index among the
+    /// subtrees whose roots are at the specified level. This is the inverse of obtaining the
+    /// index of a position's ancestor at that level, for the leftmost leaf of each subtree.
+    pub fn for_subtree(level: Level, subtree_index: usize) -> Self {
+        Position(subtree_index << level.0)
+    }
+
+    /// Returns the position `n` leaves after this one, or `None` if that position is not
+    /// representable.
+    pub fn checked_add(self, n: usize) -> Option<Self> {
+        self.0.checked_add(n).map(Position)
+    }
+
+    /// Returns the number of leaves from `other` to this position, or `None` if `other` is
+    /// after this position.
+    pub fn checked_sub(self, other: Position) -> Option<usize> {
+        self.0.checked_sub(other.0)
+    }
+
     /// Return whether the position is odd-valued.
     pub fn is_odd(&self) -> bool {
         self.0 & 0x1 == 1
@@ -60,7 +105,15 @@ impl Position {
     /// Returns the minimum possible level of the root of a binary tree containing at least
     /// `self + 1` nodes.
     pub fn root_level(&self) -> Level {
-        Level(64 - self.0.leading_zeros() as u8)
+        Level((usize::BITS - self.0.leading_zeros()) as u8)
+    }
+
+    /// Returns the level of the lowest common ancestor of the leaves at this position and the
+    /// specified position; the witnesses for the two positions share each node above this level.
+    /// Every position is its own ancestor at level 0, so this returns level 0 for equal
+    /// positions.
+    pub fn common_ancestor_level(&self, other: Position) -> Level {
+        Position(self.0 ^ other.0).root_level()
     }
 
     /// Returns the number of cousins and/or ommers required to construct an authentication
@@ -92,6 +145,25 @@ impl Position {
             ommer_count: 0,
         }
     }
+
+    /// Returns the level and kind of each slot of a witness for this position in a tree of the
+    /// specified depth whose most recently appended leaf is at `tip`, beginning with the sibling
+    /// of the leaf at this position. This describes how [`BridgeTree::witness`] assembles the
+    /// witness.
+    ///
+    /// [`BridgeTree::witness`]: crate::BridgeTree::witness
+    pub fn witness_layout(&self, depth: u8, tip: Position) -> Vec<(Level, SlotKind)> {
+        self.witness_addrs(Level(depth))
+            .map(|(addr, source)| {
+                let kind = match source {
+                    Source::Past(_) => SlotKind::OmmerFromFrontier,
+                    Source::Future if tip < addr.position_range().start => SlotKind::EmptyRoot,
+                    Source::Future => SlotKind::CollectedFromFragment,
+                };
+                (addr.level(), kind)
+            })
+            .collect()
+    }
 }
 
 impl From<Position> for usize {
@@ -119,6 +191,16 @@ impl AddAssign<usize> for Position {
     }
 }
 
+/// Returns the number of leaves between two positions. As with subtraction of unsigned
+/// integers, this panics on underflow in debug builds; use [`Position::checked_sub`] when the
+/// order of the positions is not known.
+impl Sub<Position> for Position {
+    type Output = usize;
+    fn sub(self, other: Position) -> usize {
+        self.0 - other.0
+    }
+}
+
 impl From<usize> for Position {
     fn from(sz: usize) -> Self {
         Self(sz)
@@ -151,6 +233,21 @@ pub(crate) enum Source {
     Future,
 }
 
+/// Describes how a slot of a witness is filled; see [`Position::witness_layout`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlotKind {
+    /// The slot is filled by an ommer of the frontier as of the witnessed position; the sibling
+    /// lies entirely to the left of the witnessed leaf.
+    OmmerFromFrontier,
+    /// The slot is filled by the root of a subtree containing leaves appended after the witnessed
+    /// leaf, which is obtained from the ommers collected by the bridges that follow the
+    /// witnessed position or, if it contains the tip of the tree, from the tree's frontier.
+    CollectedFromFragment,
+    /// The slot is filled by the root of an empty subtree, because the sibling lies entirely to
+    /// the right of the tip of the tree.
+    EmptyRoot,
+}
+
 impl Address {
     pub fn from_parts(level: Level, index: usize) -> Self {
         Address { level, index }
@@ -158,8 +255,8 @@ impl Address {
 
     pub fn position_range(&self) -> Range<Position> {
         Range {
-            start: (self.index << self.level.0).try_into().unwrap(),
-            end: ((self.index + 1) << self.level.0).try_into().unwrap(),
+            start: Position(self.index << self.level.0),
+            end: Position((self.index + 1) << self.level.0),
         }
     }
 
@@ -294,7 +391,122 @@ impl Iterator for WitnessAddrsIter {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use super::{Address, Level, Position, Source};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use super::{Address, Level, Position, SlotKind, Source};
+
+    #[test]
+    fn position_arithmetic() {
+        assert_eq!(Position(3) + 4, Position(7));
+        assert_eq!(Position(7) - Position(3), 4);
+        assert_eq!(Position(7) - Position(7), 0);
+
+        assert_eq!(Position(3).checked_add(4), Some(Position(7)));
+        assert_eq!(
+            Position(usize::MAX - 1).checked_add(1),
+            Some(Position(usize::MAX))
+        );
+        assert_eq!(Position(usize::MAX).checked_add(1), None);
+
+        assert_eq!(Position(7).checked_sub(Position(3)), Some(4));
+        assert_eq!(
+            Position(usize::MAX).checked_sub(Position(0)),
+            Some(usize::MAX)
+        );
+        assert_eq!(Position(3).checked_sub(Position(7)), None);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic]
+    fn position_sub_underflow() {
+        let _ = Position(3) - Position(7);
+    }
+
+    #[test]
+    fn witness_layout() {
+        use SlotKind::*;
+        let layout = |pos: usize, tip: usize| {
+            Position(pos)
+                .witness_layout(4, Position(tip))
+                .into_iter()
+                .map(|(level, kind)| (u8::from(level), kind))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            layout(0, 0),
+            vec![
+                (0, EmptyRoot),
+                (1, EmptyRoot),
+                (2, EmptyRoot),
+                (3, EmptyRoot)
+            ]
+        );
+        assert_eq!(
+            layout(0, 1),
+            vec![
+                (0, CollectedFromFragment),
+                (1, EmptyRoot),
+                (2, EmptyRoot),
+                (3, EmptyRoot)
+            ]
+        );
+        assert_eq!(
+            layout(2, 2),
+            vec![
+                (0, EmptyRoot),
+                (1, OmmerFromFrontier),
+                (2, EmptyRoot),
+                (3, EmptyRoot)
+            ]
+        );
+        assert_eq!(
+            layout(2, 4),
+            vec![
+                (0, CollectedFromFragment),
+                (1, OmmerFromFrontier),
+                (2, CollectedFromFragment),
+                (3, EmptyRoot)
+            ]
+        );
+        assert_eq!(
+            layout(5, 9),
+            vec![
+                (0, OmmerFromFrontier),
+                (1, CollectedFromFragment),
+                (2, OmmerFromFrontier),
+                (3, CollectedFromFragment)
+            ]
+        );
+    }
+
+    #[test]
+    fn position_for_subtree() {
+        assert_eq!(Position::for_subtree(Level(0), 5), Position(5));
+        assert_eq!(Position::for_subtree(Level(2), 0), Position(0));
+        assert_eq!(Position::for_subtree(Level(2), 3), Position(12));
+        assert_eq!(Position::for_subtree(Level(16), 1), Position(1 << 16));
+
+        for level in 0u8..8 {
+            for index in 0..16 {
+                let pos = Position::for_subtree(Level(level), index);
+                assert_eq!(
+                    Address::from_parts(Level(level), index)
+                        .position_range()
+                        .start,
+                    pos
+                );
+
+                // the ancestor of the subtree's first leaf at the subtree's level is the
+                // subtree's root
+                let ancestor = (0..level).fold(Address::from(pos), |addr, _| addr.parent());
+                assert_eq!(ancestor, Address::from_parts(Level(level), index));
+                assert_eq!(ancestor.index(), index);
+            }
+        }
+    }
 
     #[test]
     fn position_is_complete_subtree() {
@@ -328,6 +540,44 @@ pub(crate) mod tests {
         assert_eq!(Level(3), Position(4).root_level());
         assert_eq!(Level(3), Position(7).root_level());
         assert_eq!(Level(4), Position(8).root_level());
+        assert_eq!(Level(32), Position(1 << 31).root_level());
+        assert_eq!(Level(32), Position(u32::MAX as usize).root_level());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "32")]
+    fn position_root_level_32() {
+        assert_eq!(Level(32), Position(usize::MAX).root_level());
+        assert_eq!(Level(31), Position(usize::MAX >> 1).root_level());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn position_root_level_64() {
+        assert_eq!(Level(33), Position(1 << 32).root_level());
+        assert_eq!(Level(64), Position(usize::MAX).root_level());
+        assert_eq!(Level(63), Position(usize::MAX >> 1).root_level());
+    }
+
+    #[test]
+    fn position_common_ancestor_level() {
+        assert_eq!(Level(0), Position(3).common_ancestor_level(Position(3)));
+        assert_eq!(Level(1), Position(0).common_ancestor_level(Position(1)));
+        assert_eq!(Level(2), Position(0).common_ancestor_level(Position(2)));
+        assert_eq!(Level(2), Position(5).common_ancestor_level(Position(7)));
+        assert_eq!(Level(4), Position(7).common_ancestor_level(Position(8)));
+        assert_eq!(Level(4), Position(8).common_ancestor_level(Position(7)));
+        assert_eq!(
+            Level(usize::BITS as u8),
+            Position(0).common_ancestor_level(Position(usize::MAX))
+        );
+        for (a, b) in [(3, 12), (16, 17), (21, 30)] {
+            let level = Position(a).common_ancestor_level(Position(b));
+            let ancestor = |p: usize| Address::from_parts(level, p >> u8::from(level));
+            assert_eq!(ancestor(a), ancestor(b));
+            let below = Level(u8::from(level) - 1);
+            assert_ne!(a >> u8::from(below), b >> u8::from(below));
+        }
     }
 
     #[test]
@@ -0,0 +1,590 @@
+//! A Merkle tree composed of fixed-height subtrees, for use with very large trees.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use crate::{combine, DynBridgeTree, Hashable, Level, Position};
+
+/// A subtree of a [`ShardedTree`].
+#[derive(Clone, PartialEq, Eq)]
+enum Shard<H> {
+    /// A complete shard that contains no marked leaves and cannot be altered by a rewind,
+    /// represented only by its root.
+    Pruned(H),
+    /// A shard that contains marked leaves, may be altered by a rewind, or is the shard to which
+    /// leaves are currently being appended.
+    Tree(Box<DynBridgeTree<H>>),
+}
+
+impl<H: Debug> Debug for Shard<H> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match self {
+            Shard::Pruned(root) => f.debug_tuple("Pruned").field(root).finish(),
+            Shard::Tree(tree) => f.debug_tuple("Tree").field(tree).finish(),
+        }
+    }
+}
+
+impl<H: Hashable + Ord + Clone> Shard<H> {
+    fn root(&self, checkpoint_depth: usize) -> H {
+        match self {
+            Shard::Pruned(root) => root.clone(),
+            Shard::Tree(tree) => tree.root(checkpoint_depth).expect(
+                "a shard has a checkpoint for each checkpoint of the tree following its creation",
+            ),
+        }
+    }
+}
+
+/// A Merkle tree of depth `DEPTH` whose leaves are stored in subtrees, or shards, of height
+/// `SHARD_HEIGHT`.
+///
+/// Each shard is a [`BridgeTree`](crate::BridgeTree) of depth `SHARD_HEIGHT`. Once a shard is
+/// complete, contains no marked leaves, and is not referenced by any of the tree's checkpoints,
+/// it is reduced to its root; the roots of the shards are combined to obtain the root of the
+/// tree. This bounds the memory used by a tree containing a very large number of leaves, only
+/// a few of which are marked, more tightly than a single `BridgeTree` does.
+///
+/// The tree supports the same operations as a `BridgeTree`, with the same results; witnesses
+/// are obtained by extending the witness produced by the shard containing a marked leaf with
+/// the roots of the other shards.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ShardedTree<H, const DEPTH: u8, const SHARD_HEIGHT: u8> {
+    /// The shards of the tree, in order of position. The last shard is never pruned.
+    shards: Vec<Shard<H>>,
+    /// The position of the most recently appended leaf as of each checkpoint, oldest first.
+    checkpoints: Vec<Option<Position>>,
+    /// The maximum number of checkpoints to retain.
+    max_checkpoints: usize,
+}
+
+impl<H: Debug, const DEPTH: u8, const SHARD_HEIGHT: u8> Debug
+    for ShardedTree<H, DEPTH, SHARD_HEIGHT>
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        f.debug_struct("ShardedTree")
+            .field("depth", &DEPTH)
+            .field("shard_height", &SHARD_HEIGHT)
+            .field("shards", &self.shards)
+            .field("checkpoints", &self.checkpoints)
+            .field("max_checkpoints", &self.max_checkpoints)
+            .finish()
+    }
+}
+
+impl<H: Hashable + Ord + Clone, const DEPTH: u8, const SHARD_HEIGHT: u8>
+    ShardedTree<H, DEPTH, SHARD_HEIGHT>
+{
+    /// Constructs an empty tree with the specified maximum number of checkpoints.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `SHARD_HEIGHT` is greater than `DEPTH`, or if it is not less than the width of
+    /// `usize` in bits, as positions within the tree are then not representable.
+    pub fn new(max_checkpoints: usize) -> Self {
+        assert!(
+            SHARD_HEIGHT <= DEPTH,
+            "Shard height {} exceeds tree depth {}",
+            SHARD_HEIGHT,
+            DEPTH
+        );
+        assert!(
+            u32::from(SHARD_HEIGHT) < usize::BITS,
+            "Shard height {} exceeds the width of a position",
+            SHARD_HEIGHT
+        );
+        ShardedTree {
+            shards: vec![],
+            checkpoints: vec![],
+            max_checkpoints,
+        }
+    }
+
+    /// Returns the number of shards that have been reduced to their roots.
+    pub fn pruned_shard_count(&self) -> usize {
+        self.shards
+            .iter()
+            .filter(|s| matches!(s, Shard::Pruned(_)))
+            .count()
+    }
+
+    /// Returns the number of shards that are retained in full.
+    pub fn retained_shard_count(&self) -> usize {
+        self.shards.len() - self.pruned_shard_count()
+    }
+
+    /// Appends a new value to the tree at the next available slot. Returns true if successful
+    /// and false if the tree would exceed the maximum allowed depth.
+    pub fn append(&mut self, value: &H) -> bool {
+        self.append_owned(value.clone())
+    }
+
+    /// Appends a new value to the tree at the next available slot, taking ownership of the value
+    /// so that it need not be cloned, in the same fashion as [`ShardedTree::append`].
+    pub fn append_owned(&mut self, value: H) -> bool {
+        if let Some(Shard::Tree(tree)) = self.shards.last_mut() {
+            let is_full = tree
+                .current_position()
+                .map_or(false, |p| p.is_complete_subtree(Level::from(SHARD_HEIGHT)));
+            if !is_full {
+                return tree.append_owned(value);
+            }
+        }
+
+        let max_shards = 1usize
+            .checked_shl(u32::from(DEPTH - SHARD_HEIGHT))
+            .unwrap_or(usize::MAX);
+        if self.shards.len() >= max_shards {
+            return false;
+        }
+
+        let mut tree = DynBridgeTree::new(Level::from(SHARD_HEIGHT), self.max_checkpoints);
+        tree.append_owned(value);
+        self.shards.push(Shard::Tree(Box::new(tree)));
+        self.prune();
+        true
+    }
+
+    /// Returns the position of the most recently appended leaf, or `None` if the tree is empty.
+    pub fn current_position(&self) -> Option<Position> {
+        self.current_tree()
+            .and_then(|tree| tree.current_position())
+            .map(|pos| Self::position(self.shards.len() - 1, pos))
+    }
+
+    /// Returns the most recently appended leaf value, or `None` if the tree is empty.
+    pub fn current_leaf(&self) -> Option<&H> {
+        self.current_tree().and_then(|tree| tree.current_leaf())
+    }
+
+    /// Returns the leaf at the specified position if the tree can produce a witness for it.
+    pub fn get_marked_leaf(&self, position: Position) -> Option<&H> {
+        let (index, local) = Self::shard_position(position);
+        match self.shards.get(index) {
+            Some(Shard::Tree(tree)) => tree.get_marked_leaf(local),
+            _ => None,
+        }
+    }
+
+    /// Marks the current leaf as one for which we're interested in producing a witness, in the
+    /// same fashion as [`BridgeTree::mark`](crate::BridgeTree::mark).
+    pub fn mark(&mut self) -> Option<Position> {
+        let index = self.shards.len().checked_sub(1)?;
+        match self.shards.last_mut() {
+            Some(Shard::Tree(tree)) => tree.mark().map(|pos| Self::position(index, pos)),
+            _ => None,
+        }
+    }
+
+    /// Returns the set of all marked positions.
+    pub fn marked_positions(&self) -> BTreeSet<Position> {
+        self.shards
+            .iter()
+            .enumerate()
+            .filter_map(|(index, shard)| match shard {
+                Shard::Tree(tree) => Some(
+                    tree.marked_indices()
+                        .keys()
+                        .map(move |pos| Self::position(index, *pos)),
+                ),
+                Shard::Pruned(_) => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Removes the mark at the specified position, in the same fashion as
+    /// [`BridgeTree::remove_mark`](crate::BridgeTree::remove_mark).
+    pub fn remove_mark(&mut self, position: Position) -> bool {
+        let (index, local) = Self::shard_position(position);
+        let removed = match self.shards.get_mut(index) {
+            Some(Shard::Tree(tree)) => tree.remove_mark(local),
+            _ => false,
+        };
+        if removed {
+            self.prune();
+        }
+        removed
+    }
+
+    /// Creates a new checkpoint for the current tree state, in the same fashion as
+    /// [`BridgeTree::checkpoint`](crate::BridgeTree::checkpoint).
+    pub fn checkpoint(&mut self) -> bool {
+        if self.max_checkpoints == 0 {
+            return false;
+        }
+
+        for shard in &mut self.shards {
+            if let Shard::Tree(tree) = shard {
+                tree.checkpoint();
+            }
+        }
+        self.checkpoints.push(self.current_position());
+        if self.checkpoints.len() > self.max_checkpoints {
+            self.checkpoints.remove(0);
+        }
+        self.prune();
+        true
+    }
+
+    /// Returns the number of checkpoints to which the tree may be rewound.
+    pub fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Rewinds the tree state to the previous checkpoint, and then removes that checkpoint, in
+    /// the same fashion as [`BridgeTree::rewind`](crate::BridgeTree::rewind).
+    pub fn rewind(&mut self) -> bool {
+        match self.checkpoints.pop() {
+            Some(position) => {
+                // Shards created since the checkpoint are discarded, and each of the remaining
+                // shards that has not been pruned has a corresponding checkpoint.
+                self.shards.truncate(Self::shard_count(position));
+                for shard in &mut self.shards {
+                    if let Shard::Tree(tree) = shard {
+                        let rewound = tree.rewind();
+                        debug_assert!(rewound);
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Obtains the root of the tree at the specified checkpoint depth. Returns `None` if there
+    /// are not enough checkpoints available to reach the requested checkpoint depth.
+    pub fn root(&self, checkpoint_depth: usize) -> Option<H> {
+        self.shard_roots(checkpoint_depth)
+            .map(|roots| Self::cap_root_and_path(roots, 0).0)
+    }
+
+    /// Obtains a witness to the value at the specified position as of the tree state
+    /// corresponding to the given root, in the same fashion as
+    /// [`BridgeTree::witness`](crate::BridgeTree::witness).
+    pub fn witness(&self, position: Position, as_of_root: &H) -> Option<Vec<H>> {
+        // Select the tree state in the same way as `BridgeTree::witness`: the earliest of the
+        // most recent checkpoints at or after the position to have the specified root, or
+        // otherwise the current state of the tree.
+        let checkpoint_depth = self
+            .checkpoints
+            .iter()
+            .rev()
+            .zip(1..)
+            .take_while(|(checkpoint_position, _)| **checkpoint_position >= Some(position))
+            .filter(|(_, depth)| self.root(*depth).as_ref() == Some(as_of_root))
+            .last()
+            .map(|(_, depth)| depth)
+            .or_else(|| {
+                if self.root(0).as_ref() == Some(as_of_root) {
+                    Some(0)
+                } else {
+                    None
+                }
+            })?;
+
+        let (index, local) = Self::shard_position(position);
+        let mut path = match self.shards.get(index)? {
+            Shard::Tree(tree) => tree.witness_at_checkpoint_depth(local, checkpoint_depth)?,
+            Shard::Pruned(_) => return None,
+        };
+        let roots = self.shard_roots(checkpoint_depth)?;
+        path.extend(Self::cap_root_and_path(roots, index).1);
+        Some(path)
+    }
+
+    fn current_tree(&self) -> Option<&DynBridgeTree<H>> {
+        match self.shards.last() {
+            Some(Shard::Tree(tree)) => Some(tree.as_ref()),
+            _ => None,
+        }
+    }
+
+    // Returns the position within the tree of the specified position within a shard.
+    fn position(index: usize, local: Position) -> Position {
+        Position::from((index << SHARD_HEIGHT) + usize::from(local))
+    }
+
+    // Returns the index of the shard containing the specified position, along with the position
+    // within that shard.
+    fn shard_position(position: Position) -> (usize, Position) {
+        let pos = usize::from(position);
+        (
+            pos >> SHARD_HEIGHT,
+            Position::from(pos & ((1 << SHARD_HEIGHT) - 1)),
+        )
+    }
+
+    // Returns the number of shards in a tree whose most recently appended leaf is at the
+    // specified position.
+    fn shard_count(position: Option<Position>) -> usize {
+        position.map_or(0, |pos| Self::shard_position(pos).0 + 1)
+    }
+
+    // Returns the roots of the shards of the tree as of the specified checkpoint depth.
+    fn shard_roots(&self, checkpoint_depth: usize) -> Option<Vec<H>> {
+        let shard_count = match checkpoint_depth {
+            0 => self.shards.len(),
+            depth => {
+                let i = self.checkpoints.len().checked_sub(depth)?;
+                Self::shard_count(self.checkpoints[i])
+            }
+        };
+        Some(
+            self.shards[..shard_count]
+                .iter()
+                .map(|shard| shard.root(checkpoint_depth))
+                .collect(),
+        )
+    }
+
+    // Computes the root of the tree having the specified shard roots, along with the siblings
+    // of the nodes on the path from the root of the shard at the given index to the root of the
+    // tree.
+    fn cap_root_and_path(mut nodes: Vec<H>, mut index: usize) -> (H, Vec<H>) {
+        let mut path = vec![];
+        for level in Level::from(SHARD_HEIGHT).iter_to(Level::from(DEPTH)) {
+            let empty = H::empty_root(level);
+            if nodes.len() % 2 == 1 {
+                nodes.push(empty.clone());
+            }
+            path.push(nodes.get(index ^ 1).cloned().unwrap_or(empty));
+            nodes = nodes
+                .chunks(2)
+                .map(|pair| combine(level, &pair[0], &pair[1]))
+                .collect();
+            index >>= 1;
+        }
+        let root = nodes
+            .pop()
+            .unwrap_or_else(|| H::empty_root(Level::from(DEPTH)));
+        (root, path)
+    }
+
+    // Reduces to its root each shard that contains no marks, that was followed by another shard
+    // as of the oldest checkpoint, and that has no removed marks that could be restored by a
+    // rewind, as such a shard cannot be altered by any subsequent operation. The final shard is
+    // never pruned, as its most recently appended leaf may be marked, and so neither is a
+    // shard that would become the final shard as the result of a rewind.
+    fn prune(&mut self) {
+        let last = self.shards.len().saturating_sub(1);
+        let oldest_checkpoint = self.checkpoints.first().cloned();
+        for (index, shard) in self.shards[..last].iter_mut().enumerate() {
+            if let Shard::Tree(tree) = shard {
+                let final_position = Position::from(((index + 1) << SHARD_HEIGHT) - 1);
+                if tree.marked_indices().is_empty()
+                    && oldest_checkpoint.map_or(true, |pos| pos > Some(final_position))
+                    && tree.checkpoints().iter().all(|c| c.forgotten().is_empty())
+                {
+                    *shard = Shard::Pruned(tree.root(0).expect("the current root is available"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // the property tests are not run under wasm-bindgen-test, which leaves their helpers unused
+    #![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    use proptest::prelude::*;
+    use std::fmt::Debug;
+
+    use super::ShardedTree;
+    use crate::{
+        testing::{
+            arb_operation, run_tree_conformance, tests, Counting, Operation, SipHashable, Tree,
+        },
+        BridgeTree, Hashable, Position,
+    };
+
+    #[test]
+    #[should_panic(expected = "Shard height 64 exceeds the width of a position")]
+    fn shard_height_exceeds_position_width() {
+        ShardedTree::<String, 64, 64>::new(10);
+    }
+
+    #[test]
+    fn root_hashes() {
+        tests::check_root_hashes(ShardedTree::<String, 4, 2>::new);
+        tests::check_root_hashes(ShardedTree::<String, 4, 0>::new);
+        tests::check_root_hashes(ShardedTree::<String, 4, 4>::new);
+    }
+
+    #[test]
+    fn witnesss() {
+        tests::check_witnesss(ShardedTree::<String, 4, 2>::new);
+        tests::check_witnesss(ShardedTree::<String, 4, 1>::new);
+    }
+
+    #[test]
+    fn checkpoint_rewind() {
+        tests::check_checkpoint_rewind(ShardedTree::<String, 4, 2>::new);
+    }
+
+    #[test]
+    fn rewind_remove_mark() {
+        tests::check_rewind_remove_mark(ShardedTree::<String, 4, 2>::new);
+    }
+
+    #[test]
+    fn rewind_to() {
+        tests::check_rewind_to(ShardedTree::<String, 4, 2>::new);
+    }
+
+    #[test]
+    fn tree_conformance() {
+        run_tree_conformance(|| ShardedTree::<String, 4, 2>::new(5));
+        run_tree_conformance(|| ShardedTree::<String, 4, 0>::new(5));
+    }
+
+    #[test]
+    fn append_owned() {
+        let mut t = ShardedTree::<Counting, 4, 2>::new(10);
+        // prevent the pruning of completed shards, which computes their roots
+        t.checkpoint();
+        Counting::reset_counts();
+        for c in 'a'..='f' {
+            assert!(t.append_owned(Counting(c.to_string())));
+        }
+        // the appended values are moved into the shards, including the first value of each
+        // new shard
+        assert_eq!(Counting::clone_count(), 0);
+        assert_eq!(t.current_position(), Some(Position::from(5)));
+        assert_eq!(t.root(0).unwrap().0, "abcdef__________");
+    }
+
+    #[test]
+    fn pruning() {
+        let mut sharded = ShardedTree::<SipHashable, 12, 4>::new(5);
+        let mut monolithic = BridgeTree::<SipHashable, 12>::new(5);
+        for i in 0..1000u64 {
+            for t in [&mut sharded as &mut dyn Tree<SipHashable>, &mut monolithic] {
+                t.append(&SipHashable(i));
+                if i % 300 == 7 {
+                    t.mark();
+                }
+                if i % 50 == 0 {
+                    t.checkpoint();
+                }
+            }
+        }
+
+        // 63 shards have been created; the three marked shards that precede the oldest checkpoint
+        // at position 750 are retained, along with each shard that was incomplete as of that
+        // checkpoint.
+        assert_eq!(sharded.shards.len(), 63);
+        assert_eq!(sharded.retained_shard_count(), 3 + (63 - 46));
+
+        let root = sharded.root(0).unwrap();
+        assert_eq!(Some(&root), monolithic.root(0).as_ref());
+        for pos in monolithic.marked_positions() {
+            assert_eq!(sharded.witness(pos, &root), monolithic.witness(pos, &root));
+            assert!(sharded.witness(pos, &root).is_some());
+        }
+
+        // once a mark has been removed and the checkpoints that could restore it have been
+        // discarded, the shard that contained it is pruned; only the marked shards and the final
+        // shard remain.
+        assert!(sharded.remove_mark(7.into()));
+        for _ in 0..5 {
+            sharded.checkpoint();
+        }
+        assert_eq!(sharded.retained_shard_count(), 4);
+        assert_eq!(sharded.root(0), Some(root));
+    }
+
+    fn check_operations<H: Hashable + Ord + Clone + Debug>(
+        ops: &[Operation<H>],
+        max_checkpoints: usize,
+    ) -> Result<(), TestCaseError> {
+        let mut sharded = ShardedTree::<H, 4, 2>::new(max_checkpoints);
+        let mut monolithic = BridgeTree::<H, 4>::new(max_checkpoints);
+        for op in ops {
+            match op {
+                Operation::Append(value) => {
+                    prop_assert_eq!(sharded.append(value), monolithic.append(value));
+                }
+                Operation::CurrentPosition => {
+                    prop_assert_eq!(sharded.current_position(), monolithic.current_position());
+                }
+                Operation::CurrentLeaf => {
+                    prop_assert_eq!(sharded.current_leaf(), monolithic.current_leaf());
+                }
+                Operation::Mark => {
+                    prop_assert_eq!(sharded.mark(), monolithic.mark());
+                }
+                Operation::MarkedLeaf(position) => {
+                    prop_assert_eq!(
+                        sharded.get_marked_leaf(*position),
+                        monolithic.get_marked_leaf(*position)
+                    );
+                }
+                Operation::MarkedPositions => {
+                    prop_assert_eq!(sharded.marked_positions(), monolithic.marked_positions());
+                }
+                Operation::Unmark(position) => {
+                    prop_assert_eq!(
+                        sharded.remove_mark(*position),
+                        monolithic.remove_mark(*position)
+                    );
+                }
+                Operation::Checkpoint => {
+                    prop_assert_eq!(sharded.checkpoint(), monolithic.checkpoint());
+                }
+                Operation::Rewind => {
+                    prop_assert_eq!(sharded.rewind(), monolithic.rewind());
+                }
+                Operation::Authpath(position, depth) => {
+                    let root = monolithic.root(*depth);
+                    prop_assert_eq!(&sharded.root(*depth), &root);
+                    if let Some(root) = root {
+                        prop_assert_eq!(
+                            sharded.witness(*position, &root),
+                            monolithic.witness(*position, &root)
+                        );
+                    }
+                }
+                Operation::GarbageCollect => {
+                    monolithic.garbage_collect();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1000))]
+
+        #[test]
+        fn sharded_matches_bridgetree_u64(
+            ops in proptest::collection::vec(
+                arb_operation((0..32u64).prop_map(SipHashable), 0usize..20),
+                1..100
+            )
+        ) {
+            check_operations(&ops, 10)?;
+            check_operations(&ops, 2)?;
+        }
+
+        #[test]
+        fn sharded_matches_bridgetree_str(
+            ops in proptest::collection::vec(
+                arb_operation((97u8..123).prop_map(|c| char::from(c).to_string()), 0usize..20),
+                1..100
+            )
+        ) {
+            check_operations::<String>(&ops, 10)?;
+            check_operations::<String>(&ops, 2)?;
+        }
+    }
+}
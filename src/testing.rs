@@ -1,11 +1,15 @@
 mod complete_tree;
 
 use proptest::prelude::*;
+#[cfg(test)]
+use std::cell::Cell;
 use std::collections::BTreeSet;
+use std::fmt::Debug;
 
 use super::{
     hashing::Hashable,
     position::{Level, Position},
+    root_from_witness, Depth, DepthBridgeTree, ShardedTree,
 };
 
 //
@@ -19,6 +23,12 @@ pub trait Frontier<H> {
     /// the maximum allowed depth.
     fn append(&mut self, value: &H) -> bool;
 
+    /// Appends a new value to the frontier at the next available slot, taking ownership of the
+    /// value. Implementations should override this to avoid cloning the value.
+    fn append_owned(&mut self, value: H) -> bool {
+        self.append(&value)
+    }
+
     /// Obtains the current root of this Merkle frontier by hashing
     /// against empty nodes up to the maximum height of the pruned
     /// tree that the frontier represents.
@@ -33,6 +43,12 @@ pub trait Tree<H> {
     /// the maximum allowed depth.
     fn append(&mut self, value: &H) -> bool;
 
+    /// Appends a new value to the tree at the next available slot, taking ownership of the
+    /// value. Implementations should override this to avoid cloning the value.
+    fn append_owned(&mut self, value: H) -> bool {
+        self.append(&value)
+    }
+
     /// Returns the most recently appended leaf value.
     fn current_position(&self) -> Option<Position>;
 
@@ -72,8 +88,9 @@ pub trait Tree<H> {
 
     /// Creates a new checkpoint for the current tree state. It is valid to
     /// have multiple checkpoints for the same tree state, and each `rewind`
-    /// call will remove a single checkpoint.
-    fn checkpoint(&mut self);
+    /// call will remove a single checkpoint. Returns false if checkpointing
+    /// is disabled.
+    fn checkpoint(&mut self) -> bool;
 
     /// Rewinds the tree state to the previous checkpoint, and then removes
     /// that checkpoint record. If there are multiple checkpoints at a given
@@ -81,6 +98,281 @@ pub trait Tree<H> {
     /// at that tree state have been removed using `rewind`. This function
     /// return false and leave the tree unmodified if no checkpoints exist.
     fn rewind(&mut self) -> bool;
+
+    /// Returns the number of checkpoints to which the tree may be rewound.
+    fn checkpoint_count(&self) -> usize;
+
+    /// Rewinds the tree state to the checkpoint at the specified checkpoint depth, where a depth
+    /// of 1 refers to the most recent checkpoint, removing that checkpoint along with all more
+    /// recent checkpoints. Returns false and leaves the tree unmodified if fewer than
+    /// `checkpoint_depth` checkpoints exist.
+    fn rewind_to(&mut self, checkpoint_depth: usize) -> bool {
+        if checkpoint_depth > self.checkpoint_count() {
+            return false;
+        }
+        for _ in 0..checkpoint_depth {
+            assert!(self.rewind(), "checkpoint depth was verified");
+        }
+        true
+    }
+}
+
+impl<H: Hashable + Clone, const DEPTH: u8> Frontier<H> for super::Frontier<H, DEPTH> {
+    fn append(&mut self, value: &H) -> bool {
+        super::Frontier::append(self, value)
+    }
+
+    fn append_owned(&mut self, value: H) -> bool {
+        super::Frontier::append_owned(self, value)
+    }
+
+    fn root(&self) -> H {
+        super::Frontier::root(self)
+    }
+}
+
+impl<H: Hashable + Ord + Clone, D: Depth> Tree<H> for DepthBridgeTree<H, D> {
+    fn append(&mut self, value: &H) -> bool {
+        DepthBridgeTree::append(self, value)
+    }
+
+    fn append_owned(&mut self, value: H) -> bool {
+        DepthBridgeTree::append_owned(self, value)
+    }
+
+    fn current_position(&self) -> Option<Position> {
+        DepthBridgeTree::current_position(self)
+    }
+
+    fn current_leaf(&self) -> Option<&H> {
+        DepthBridgeTree::current_leaf(self)
+    }
+
+    fn get_marked_leaf(&self, position: Position) -> Option<&H> {
+        DepthBridgeTree::get_marked_leaf(self, position)
+    }
+
+    fn mark(&mut self) -> Option<Position> {
+        DepthBridgeTree::mark(self)
+    }
+
+    fn marked_positions(&self) -> BTreeSet<Position> {
+        DepthBridgeTree::marked_positions(self)
+    }
+
+    fn root(&self, checkpoint_depth: usize) -> Option<H> {
+        DepthBridgeTree::root(self, checkpoint_depth)
+    }
+
+    fn witness(&self, position: Position, as_of_root: &H) -> Option<Vec<H>> {
+        DepthBridgeTree::witness(self, position, as_of_root)
+    }
+
+    fn remove_mark(&mut self, position: Position) -> bool {
+        DepthBridgeTree::remove_mark(self, position)
+    }
+
+    fn checkpoint(&mut self) -> bool {
+        DepthBridgeTree::checkpoint(self)
+    }
+
+    fn rewind(&mut self) -> bool {
+        DepthBridgeTree::rewind(self)
+    }
+
+    fn checkpoint_count(&self) -> usize {
+        DepthBridgeTree::checkpoints(self).len()
+    }
+}
+
+impl<H: Hashable + Ord + Clone, const DEPTH: u8, const SHARD_HEIGHT: u8> Tree<H>
+    for ShardedTree<H, DEPTH, SHARD_HEIGHT>
+{
+    fn append(&mut self, value: &H) -> bool {
+        ShardedTree::append(self, value)
+    }
+
+    fn append_owned(&mut self, value: H) -> bool {
+        ShardedTree::append_owned(self, value)
+    }
+
+    fn current_position(&self) -> Option<Position> {
+        ShardedTree::current_position(self)
+    }
+
+    fn current_leaf(&self) -> Option<&H> {
+        ShardedTree::current_leaf(self)
+    }
+
+    fn get_marked_leaf(&self, position: Position) -> Option<&H> {
+        ShardedTree::get_marked_leaf(self, position)
+    }
+
+    fn mark(&mut self) -> Option<Position> {
+        ShardedTree::mark(self)
+    }
+
+    fn marked_positions(&self) -> BTreeSet<Position> {
+        ShardedTree::marked_positions(self)
+    }
+
+    fn root(&self, checkpoint_depth: usize) -> Option<H> {
+        ShardedTree::root(self, checkpoint_depth)
+    }
+
+    fn witness(&self, position: Position, as_of_root: &H) -> Option<Vec<H>> {
+        ShardedTree::witness(self, position, as_of_root)
+    }
+
+    fn remove_mark(&mut self, position: Position) -> bool {
+        ShardedTree::remove_mark(self, position)
+    }
+
+    fn checkpoint(&mut self) -> bool {
+        ShardedTree::checkpoint(self)
+    }
+
+    fn rewind(&mut self) -> bool {
+        ShardedTree::rewind(self)
+    }
+
+    fn checkpoint_count(&self) -> usize {
+        ShardedTree::checkpoint_count(self)
+    }
+}
+
+//
+// Conformance tests for implementations of `Tree`.
+//
+
+/// Runs a suite of checks against an implementation of [`Tree`], panicking if the
+/// implementation fails to uphold the invariants of appending, marking, witnessing,
+/// checkpointing and rewinding that are expected of any such tree.
+///
+/// `make` must return a new empty tree that has a depth of at least 3 and that retains at
+/// least 5 checkpoints. The leaves appended by the suite are the empty roots of successive
+/// levels, so that no particular construction of leaf values is required of `H`. Witnesses
+/// are checked by recomputing the root from the witnessed leaf, rather than against fixed
+/// expected values, so the suite may be run with any hash function.
+///
+/// ```
+/// use bridgetree::{testing::run_tree_conformance, BridgeTree};
+///
+/// run_tree_conformance::<BridgeTree<String, 4>, String>(|| BridgeTree::new(10));
+/// ```
+pub fn run_tree_conformance<T: Tree<H>, H: Hashable + Clone + PartialEq + Debug>(
+    make: impl Fn() -> T,
+) {
+    let leaf = |i: u8| H::empty_root(Level::from(i + 1));
+    let check_witness = |tree: &T, position: Position, depth: usize| {
+        let root = tree.root(depth).expect("the checkpoint exists");
+        let path = tree
+            .witness(position, &root)
+            .expect("a witness is available for a marked position");
+        let leaf = tree
+            .get_marked_leaf(position)
+            .expect("the position is marked")
+            .clone();
+        assert_eq!(root_from_witness(leaf, position, &path, H::combine), root);
+    };
+
+    // an empty tree
+    let mut tree = make();
+    assert_eq!(tree.current_position(), None);
+    assert_eq!(tree.current_leaf(), None);
+    assert_eq!(tree.mark(), None);
+    assert!(tree.marked_positions().is_empty());
+    assert_eq!(tree.checkpoint_count(), 0);
+    assert!(!tree.rewind());
+    assert!(tree.rewind_to(0));
+    assert!(tree.root(0).is_some());
+    assert_eq!(tree.root(1), None);
+
+    // appending advances the position and sets the current leaf
+    for i in 0..3 {
+        assert!(tree.append(&leaf(i)));
+        assert_eq!(
+            tree.current_position(),
+            Some(Position::from(usize::from(i)))
+        );
+        assert_eq!(tree.current_leaf(), Some(&leaf(i)));
+    }
+
+    // marking is idempotent, and only marked leaves are retrievable
+    assert_eq!(tree.mark(), Some(Position::from(2)));
+    assert_eq!(tree.mark(), Some(Position::from(2)));
+    assert_eq!(tree.get_marked_leaf(Position::from(2)), Some(&leaf(2)));
+    assert_eq!(tree.get_marked_leaf(Position::from(1)), None);
+    assert_eq!(
+        tree.marked_positions(),
+        vec![Position::from(2)].into_iter().collect()
+    );
+
+    // witnesses remain valid as leaves are appended and other leaves are marked
+    check_witness(&tree, Position::from(2), 0);
+    assert!(tree.checkpoint());
+    assert_eq!(tree.checkpoint_count(), 1);
+    assert_eq!(tree.root(1), tree.root(0));
+    for i in 3..6 {
+        assert!(tree.append(&leaf(i)));
+        check_witness(&tree, Position::from(2), 0);
+        check_witness(&tree, Position::from(2), 1);
+    }
+    assert_eq!(tree.mark(), Some(Position::from(5)));
+    check_witness(&tree, Position::from(5), 0);
+    assert!(tree
+        .witness(Position::from(1), &tree.root(0).unwrap())
+        .is_none());
+
+    // rewinding restores the state of the tree at the checkpoint
+    let checkpoint_root = tree.root(1);
+    assert!(tree.checkpoint());
+    assert!(tree.append(&leaf(6)));
+    assert!(tree.rewind());
+    assert_eq!(tree.current_position(), Some(Position::from(5)));
+    assert_eq!(tree.checkpoint_count(), 1);
+    assert!(tree.rewind());
+    assert_eq!(tree.root(0), checkpoint_root);
+    assert_eq!(tree.current_position(), Some(Position::from(2)));
+    assert_eq!(tree.current_leaf(), Some(&leaf(2)));
+    assert_eq!(tree.checkpoint_count(), 0);
+    assert!(!tree.rewind());
+
+    // a mark made after the checkpoint is removed by rewinding
+    assert_eq!(
+        tree.marked_positions(),
+        vec![Position::from(2)].into_iter().collect()
+    );
+    check_witness(&tree, Position::from(2), 0);
+
+    // removing a mark is reported once, and is undone by rewinding
+    assert!(tree.checkpoint());
+    assert!(tree.remove_mark(Position::from(2)));
+    assert!(!tree.remove_mark(Position::from(2)));
+    assert!(tree.marked_positions().is_empty());
+    assert!(tree.rewind());
+    assert_eq!(tree.get_marked_leaf(Position::from(2)), Some(&leaf(2)));
+    check_witness(&tree, Position::from(2), 0);
+
+    // repeated checkpoints are rewound one at a time, and `rewind_to` leaves the tree
+    // unmodified if too few checkpoints exist
+    let mut tree = make();
+    let mut roots = vec![];
+    for i in 0..4 {
+        assert!(tree.append(&leaf(i)));
+        roots.push(tree.root(0));
+        assert!(tree.checkpoint());
+    }
+    assert!(tree.checkpoint());
+    assert_eq!(tree.checkpoint_count(), 5);
+    assert!(!tree.rewind_to(6));
+    assert_eq!(tree.checkpoint_count(), 5);
+    assert!(tree.rewind());
+    assert_eq!(tree.current_position(), Some(Position::from(3)));
+    assert!(tree.rewind_to(2));
+    assert_eq!(tree.root(0), roots[2]);
+    assert_eq!(tree.current_position(), Some(Position::from(2)));
+    assert_eq!(tree.checkpoint_count(), 2);
 }
 
 //
@@ -116,6 +408,73 @@ impl Hashable for String {
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    static CLONE_COUNT: Cell<usize> = Cell::new(0);
+    static COMBINE_COUNT: Cell<usize> = Cell::new(0);
+    static PANIC_ON_COMBINE: Cell<Option<usize>> = Cell::new(None);
+}
+
+/// A string-valued hashable that counts the number of times that values of the type have been
+/// cloned or combined on the current thread.
+#[cfg(test)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Counting(pub(crate) String);
+
+#[cfg(test)]
+impl Counting {
+    /// Returns the number of clones performed on the current thread since the last reset.
+    pub(crate) fn clone_count() -> usize {
+        CLONE_COUNT.with(|c| c.get())
+    }
+
+    /// Returns the number of calls to `combine` performed on the current thread since the last
+    /// reset.
+    pub(crate) fn combine_count() -> usize {
+        COMBINE_COUNT.with(|c| c.get())
+    }
+
+    /// Causes the `n`th call to `combine` on the current thread following the last reset to
+    /// panic.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn panic_on_combine(n: usize) {
+        PANIC_ON_COMBINE.with(|c| c.set(Some(n)));
+    }
+
+    /// Resets the counters and any pending panic for the current thread.
+    pub(crate) fn reset_counts() {
+        CLONE_COUNT.with(|c| c.set(0));
+        COMBINE_COUNT.with(|c| c.set(0));
+        PANIC_ON_COMBINE.with(|c| c.set(None));
+    }
+}
+
+#[cfg(test)]
+impl Clone for Counting {
+    fn clone(&self) -> Self {
+        CLONE_COUNT.with(|c| c.set(c.get() + 1));
+        Counting(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+impl Hashable for Counting {
+    fn empty_leaf() -> Self {
+        Counting(String::empty_leaf())
+    }
+
+    fn combine(level: Level, a: &Self, b: &Self) -> Self {
+        let count = COMBINE_COUNT.with(|c| {
+            c.set(c.get() + 1);
+            c.get()
+        });
+        if PANIC_ON_COMBINE.with(|c| c.get()) == Some(count) {
+            panic!("combine panicked on call {}", count);
+        }
+        Counting(String::combine(level, &a.0, &b.0))
+    }
+}
+
 //
 // Operations
 //
@@ -239,6 +598,12 @@ pub fn apply_operation<H, T: Tree<H>>(tree: &mut T, op: Operation<H>) {
 
 #[cfg(test)]
 pub(crate) mod tests {
+    // the property tests are not run under wasm-bindgen-test, which leaves their helpers unused
+    #![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
     use proptest::prelude::*;
     use std::collections::BTreeSet;
     use std::fmt::Debug;
@@ -482,7 +847,6 @@ pub(crate) mod tests {
         );
 
         let ops = ('a'..='l')
-            .into_iter()
             .map(|c| Append(c.to_string()))
             .chain(Some(Mark))
             .chain(Some(Append('m'.to_string())))
@@ -546,6 +910,43 @@ pub(crate) mod tests {
         assert_eq!(t.root(0).unwrap(), "ab______________");
     }
 
+    pub(crate) fn check_rewind_to<T: Tree<String>, F: Fn(usize) -> T>(new_tree: F) {
+        let mut t = new_tree(100);
+        assert_eq!(t.checkpoint_count(), 0);
+        assert!(t.rewind_to(0));
+        assert!(!t.rewind_to(1));
+
+        for c in 'a'..='e' {
+            t.append(&c.to_string());
+            t.checkpoint();
+        }
+        // a repeated checkpoint is counted separately
+        t.checkpoint();
+        t.append(&"f".to_string());
+        assert_eq!(t.checkpoint_count(), 6);
+
+        // the tree is left unmodified if not enough checkpoints exist
+        assert!(!t.rewind_to(7));
+        assert_eq!(t.checkpoint_count(), 6);
+        assert_eq!(t.current_position(), Some(Position::from(5)));
+
+        assert!(t.rewind_to(3));
+        assert_eq!(t.checkpoint_count(), 3);
+        assert_eq!(t.current_position(), Some(Position::from(3)));
+        assert_eq!(t.root(0).unwrap(), "abcd____________");
+
+        // checkpoints beyond the maximum are discarded
+        let mut t = new_tree(2);
+        for c in 'a'..='e' {
+            t.append(&c.to_string());
+            t.checkpoint();
+        }
+        assert_eq!(t.checkpoint_count(), 2);
+        assert!(!t.rewind_to(3));
+        assert!(t.rewind_to(2));
+        assert_eq!(t.root(0).unwrap(), "abcd____________");
+    }
+
     fn append(x: &str) -> Operation<String> {
         Operation::Append(x.to_string())
     }
@@ -608,7 +1009,7 @@ pub(crate) mod tests {
         // test framework itself previously did not correctly handle
         // chain state restoration.
 
-        let samples = vec![
+        let samples = [
             vec![append("x"), Checkpoint, Mark, Rewind, unmark(0)],
             vec![append("d"), Checkpoint, Mark, unmark(0), Rewind, unmark(0)],
             vec![
@@ -729,9 +1130,11 @@ pub(crate) mod tests {
             a
         }
 
-        fn checkpoint(&mut self) {
-            self.inefficient.checkpoint();
-            self.efficient.checkpoint();
+        fn checkpoint(&mut self) -> bool {
+            let a = self.inefficient.checkpoint();
+            let b = self.efficient.checkpoint();
+            assert_eq!(a, b);
+            a
         }
 
         fn rewind(&mut self) -> bool {
@@ -740,6 +1143,13 @@ pub(crate) mod tests {
             assert_eq!(a, b);
             a
         }
+
+        fn checkpoint_count(&self) -> usize {
+            let a = self.inefficient.checkpoint_count();
+            let b = self.efficient.checkpoints().len();
+            assert_eq!(a, b);
+            a
+        }
     }
 
     pub(crate) fn compute_root_from_witness<H: Hashable>(
@@ -817,7 +1227,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_witness_consistency() {
-        let samples = vec![
+        let samples = [
             // Reduced examples
             vec![append("a"), append("b"), Checkpoint, Mark, witness(0, 1)],
             vec![append("c"), append("d"), Mark, Checkpoint, witness(1, 1)],
@@ -942,7 +1352,7 @@ pub(crate) mod tests {
     // correctly handle chain state restoration.
     #[test]
     fn test_rewind_remove_mark_consistency() {
-        let samples = vec![
+        let samples = [
             vec![append("x"), Checkpoint, Mark, Rewind, unmark(0)],
             vec![append("d"), Checkpoint, Mark, unmark(0), Rewind, unmark(0)],
             vec![
@@ -1002,7 +1412,7 @@ pub(crate) mod tests {
                 CurrentPosition => {
                     if let Some(pos) = tree.current_position() {
                         prop_assert!(tree_size > 0);
-                        prop_assert_eq!(tree_size - 1, pos.into());
+                        prop_assert_eq!(tree_size - 1, usize::from(pos));
                     }
                 }
                 CurrentLeaf => {
@@ -1073,6 +1483,7 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     proptest! {
         #![proptest_config(ProptestConfig::with_cases(100000))]
 
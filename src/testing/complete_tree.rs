@@ -209,10 +209,15 @@ impl<H: Hashable + PartialEq + Clone + std::fmt::Debug> Tree<H> for CompleteTree
         self.tree_state.remove_mark(position)
     }
 
-    fn checkpoint(&mut self) {
-        self.checkpoints.push(self.tree_state.clone());
-        if self.checkpoints.len() > self.max_checkpoints {
-            self.drop_oldest_checkpoint();
+    fn checkpoint(&mut self) -> bool {
+        if self.max_checkpoints == 0 {
+            false
+        } else {
+            self.checkpoints.push(self.tree_state.clone());
+            if self.checkpoints.len() > self.max_checkpoints {
+                self.drop_oldest_checkpoint();
+            }
+            true
         }
     }
 
@@ -224,6 +229,10 @@ impl<H: Hashable + PartialEq + Clone + std::fmt::Debug> Tree<H> for CompleteTree
             false
         }
     }
+
+    fn checkpoint_count(&self) -> usize {
+        self.checkpoints.len()
+    }
 }
 
 pub(crate) fn lazy_root<H: Hashable + Clone>(mut leaves: Vec<H>) -> H {
@@ -252,6 +261,9 @@ pub(crate) fn lazy_root<H: Hashable + Clone>(mut leaves: Vec<H>) -> H {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
     use std::convert::TryFrom;
 
     use super::CompleteTree;
@@ -259,6 +271,7 @@ mod tests {
         hashing::Hashable,
         position::{Level, Position},
         testing::{
+            run_tree_conformance,
             tests::{self, compute_root_from_witness},
             SipHashable, Tree,
         },
@@ -279,7 +292,7 @@ mod tests {
     #[test]
     fn correct_root() {
         const DEPTH: usize = 3;
-        let values = (0..(1 << DEPTH)).into_iter().map(SipHashable);
+        let values = (0..(1 << DEPTH)).map(SipHashable);
 
         let mut tree = CompleteTree::<SipHashable>::new(DEPTH, 100);
         for value in values {
@@ -317,7 +330,7 @@ mod tests {
     #[test]
     fn correct_witness() {
         const DEPTH: usize = 3;
-        let values = (0..(1 << DEPTH)).into_iter().map(SipHashable);
+        let values = (0..(1 << DEPTH)).map(SipHashable);
 
         let mut tree = CompleteTree::<SipHashable>::new(DEPTH, 100);
         for value in values {
@@ -361,4 +374,14 @@ mod tests {
     fn rewind_remove_mark() {
         tests::check_rewind_remove_mark(|max_c| CompleteTree::<String>::new(4, max_c));
     }
+
+    #[test]
+    fn rewind_to() {
+        tests::check_rewind_to(|max_c| CompleteTree::<String>::new(4, max_c));
+    }
+
+    #[test]
+    fn tree_conformance() {
+        run_tree_conformance(|| CompleteTree::<String>::new(4, 5));
+    }
 }